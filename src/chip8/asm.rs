@@ -0,0 +1,512 @@
+use super::emu::PROGRAM_START;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+// An error in the source, along with the line it occurred on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+// Assemble source written in the syntax of the Octo assembler into a rom
+// that loads at the start of program memory. Supported are the CHIP-8,
+// SUPER-CHIP and XO-CHIP instructions, labels (": name"), register aliases
+// (":alias name vx"), constants (":const name value"), subroutine calls by
+// label name or ":call", and data bytes given as plain numbers. Comments
+// start with '#'. Numbers may be decimal, hex (0x) or binary (0b).
+//
+// The structured control flow of Octo (loop, while, begin/else/end) and
+// macros are not supported.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler::new(source);
+    while asm.pos < asm.tokens.len() {
+        asm.statement()?;
+    }
+    asm.resolve_fixups()?;
+    Ok(asm.rom)
+}
+
+#[derive(Copy, Clone)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+// A reference to a label that gets filled in once all labels are known.
+struct Fixup<'a> {
+    offset: usize,
+    label: Token<'a>,
+    // A 16-bit address in its own word, rather than the nnn of an opcode.
+    long: bool,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    rom: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    aliases: HashMap<&'a str, u8>,
+    consts: HashMap<&'a str, i32>,
+    fixups: Vec<Fixup<'a>>,
+}
+
+impl<'a> Assembler<'a> {
+
+    fn new(source: &'a str) -> Self {
+        let tokens = source.lines().enumerate()
+            .flat_map(|(idx, line)| {
+                let code = line.split('#').next().unwrap_or("");
+                code.split_whitespace()
+                    .map(move |text| Token { text, line: idx + 1 })
+            })
+            .collect();
+        Assembler {
+            tokens,
+            pos: 0,
+            rom: Vec::new(),
+            labels: HashMap::new(),
+            aliases: HashMap::new(),
+            consts: HashMap::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let tok = self.next()?;
+        match tok.text {
+            ":" => {
+                let name = self.name()?;
+                let addr = self.here();
+                if self.labels.insert(name.text, addr).is_some() {
+                    return Err(error(name, "label defined twice"));
+                }
+            },
+            ":alias" => {
+                let name = self.name()?;
+                let x = self.register()?;
+                self.aliases.insert(name.text, x as u8);
+            },
+            ":const" => {
+                let name = self.name()?;
+                let value = self.number()?;
+                self.consts.insert(name.text, value);
+            },
+            ":call" => self.addr(0x2000)?,
+            "clear" => self.emit(0x00e0),
+            "return" | ";" => self.emit(0x00ee),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(0x00c0 | n);
+            },
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(0x00d0 | n);
+            },
+            "scroll-right" => self.emit(0x00fb),
+            "scroll-left" => self.emit(0x00fc),
+            "exit" => self.emit(0x00fd),
+            "lores" => self.emit(0x00fe),
+            "hires" => self.emit(0x00ff),
+            "jump" => self.addr(0x1000)?,
+            "jump0" => self.addr(0xb000)?,
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(0xd000 | x << 8 | y << 4 | n);
+            },
+            "bcd" => self.fx(0x33)?,
+            "saveflags" => self.fx(0x75)?,
+            "loadflags" => self.fx(0x85)?,
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.peek() == Some("-") {
+                    self.pos += 1;
+                    let y = self.register()?;
+                    let n = if tok.text == "save" {0x2} else {0x3};
+                    self.emit(0x5000 | x << 8 | y << 4 | n);
+                } else {
+                    let nn = if tok.text == "save" {0x55} else {0x65};
+                    self.emit(0xf000 | x << 8 | nn);
+                }
+            },
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(0xf001 | n << 8);
+            },
+            "audio" => self.emit(0xf002),
+            "delay" => { self.expect(":=")?; self.fx(0x15)?; },
+            "buzzer" => { self.expect(":=")?; self.fx(0x18)?; },
+            "pitch" => { self.expect(":=")?; self.fx(0x3a)?; },
+            "i" => self.index_statement()?,
+            "if" => self.if_statement()?,
+            _ if parse_number(tok.text).is_some() => {
+                let byte = self.byte_value(tok)?;
+                self.rom.push(byte);
+            },
+            _ if self.reg_value(tok.text).is_some() =>
+                self.register_statement(tok)?,
+            // Anything else calls the subroutine with that label.
+            _ => {
+                self.pos -= 1;
+                self.addr(0x2000)?;
+            },
+        }
+        Ok(())
+    }
+
+    // i := nnn, i := hex vx, i := bighex vx, i := long nnnn or i += vx.
+    fn index_statement(&mut self) -> Result<(), AsmError> {
+        let op = self.next()?;
+        match op.text {
+            ":=" => match self.peek() {
+                Some("hex") => { self.pos += 1; self.fx(0x29) },
+                Some("bighex") => { self.pos += 1; self.fx(0x30) },
+                Some("long") => {
+                    self.pos += 1;
+                    self.emit(0xf000);
+                    self.long_addr()
+                },
+                _ => self.addr(0xa000),
+            },
+            "+=" => self.fx(0x1e),
+            _ => Err(unexpected(op, "':=' or '+='")),
+        }
+    }
+
+    // if CONDITION then, which skips the next instruction unless CONDITION
+    // holds.
+    fn if_statement(&mut self) -> Result<(), AsmError> {
+        let x = self.register()?;
+        let op = self.next()?;
+        match op.text {
+            "==" | "!=" => {
+                let rhs = self.next()?;
+                let equal = op.text == "==";
+                match self.reg_value(rhs.text) {
+                    Some(y) => self.emit(
+                        if equal {0x9000} else {0x5000} | x << 8 | y << 4),
+                    None => {
+                        let nn = self.byte_value(rhs)? as u16;
+                        self.emit(
+                            if equal {0x4000} else {0x3000} | x << 8 | nn);
+                    },
+                }
+            },
+            "key" => self.emit(0xe0a1 | x << 8),
+            "-key" => self.emit(0xe09e | x << 8),
+            _ => return Err(unexpected(op, "'==', '!=', 'key' or '-key'")),
+        }
+        self.expect("then")
+    }
+
+    // vx followed by an assignment operator.
+    fn register_statement(&mut self, tok: Token<'a>) -> Result<(), AsmError> {
+        let x = self.reg_value(tok.text).unwrap_or(0);
+        let op = self.next()?;
+        let rhs = self.next()?;
+        let y = self.reg_value(rhs.text);
+        let opcode = match (op.text, rhs.text, y) {
+            (":=", "random", _) => 0xc000 | self.byte()? as u16,
+            (":=", "delay", _) => 0xf007,
+            (":=", "key", _) => 0xf00a,
+            (":=", _, Some(y)) => 0x8000 | y << 4,
+            (":=", _, None) => 0x6000 | self.byte_value(rhs)? as u16,
+            ("+=", _, Some(y)) => 0x8004 | y << 4,
+            ("+=", _, None) => 0x7000 | self.byte_value(rhs)? as u16,
+            ("-=", _, Some(y)) => 0x8005 | y << 4,
+            ("-=", _, None) =>
+                0x7000 | self.byte_value(rhs)?.wrapping_neg() as u16,
+            ("|=", _, Some(y)) => 0x8001 | y << 4,
+            ("&=", _, Some(y)) => 0x8002 | y << 4,
+            ("^=", _, Some(y)) => 0x8003 | y << 4,
+            (">>=", _, Some(y)) => 0x8006 | y << 4,
+            ("=-", _, Some(y)) => 0x8007 | y << 4,
+            ("<<=", _, Some(y)) => 0x800e | y << 4,
+            ("|=", ..) | ("&=", ..) | ("^=", ..) | (">>=", ..) | ("=-", ..) |
+            ("<<=", ..) => return Err(unexpected(rhs, "register")),
+            _ => return Err(unexpected(op, "assignment operator")),
+        };
+        self.emit(opcode | x << 8);
+        Ok(())
+    }
+
+    // An fx.. instruction, taking the register x from the next token.
+    fn fx(&mut self, nn: u16) -> Result<(), AsmError> {
+        let x = self.register()?;
+        self.emit(0xf000 | x << 8 | nn);
+        Ok(())
+    }
+
+    // An opcode taking a 12-bit address nnn, given by number, constant or
+    // label.
+    fn addr(&mut self, opcode: u16) -> Result<(), AsmError> {
+        let tok = self.next()?;
+        let offset = self.rom.len();
+        match self.value(tok.text) {
+            Some(nnn) if (0..=0xfff).contains(&nnn) =>
+                self.emit(opcode | nnn as u16),
+            Some(_) => return Err(error(tok, "address out of range")),
+            None => {
+                self.emit(opcode);
+                self.fixup(offset, tok, false)?;
+            },
+        }
+        Ok(())
+    }
+
+    // A 16-bit address in a word of its own, given by number, constant or
+    // label.
+    fn long_addr(&mut self) -> Result<(), AsmError> {
+        let tok = self.next()?;
+        let offset = self.rom.len();
+        match self.value(tok.text) {
+            Some(nnnn) if (0..=0xffff).contains(&nnnn) =>
+                self.emit(nnnn as u16),
+            Some(_) => return Err(error(tok, "address out of range")),
+            None => {
+                self.emit(0);
+                self.fixup(offset, tok, true)?;
+            },
+        }
+        Ok(())
+    }
+
+    fn fixup(&mut self, offset: usize, label: Token<'a>, long: bool)
+        -> Result<(), AsmError> {
+        if !is_name(label.text) {
+            return Err(unexpected(label, "address or label"));
+        }
+        self.fixups.push(Fixup { offset, label, long });
+        Ok(())
+    }
+
+    fn resolve_fixups(&mut self) -> Result<(), AsmError> {
+        for fixup in &self.fixups {
+            let addr = match self.labels.get(fixup.label.text) {
+                Some(&addr) => addr,
+                None => return Err(error(fixup.label, "undefined label")),
+            };
+            let bytes = &mut self.rom[fixup.offset..fixup.offset + 2];
+            if fixup.long {
+                bytes.copy_from_slice(&[(addr >> 8) as u8, addr as u8]);
+            } else if addr <= 0xfff {
+                bytes[0] |= (addr >> 8) as u8;
+                bytes[1] |= addr as u8;
+            } else {
+                return Err(error(fixup.label, "label out of range"));
+            }
+        }
+        Ok(())
+    }
+
+    // The address the next instruction will be assembled at.
+    fn here(&self) -> u16 {
+        (PROGRAM_START + self.rom.len()) as u16
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.push((opcode >> 8) as u8);
+        self.rom.push(opcode as u8);
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|tok| tok.text)
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, AsmError> {
+        match self.tokens.get(self.pos) {
+            Some(&tok) => {
+                self.pos += 1;
+                Ok(tok)
+            },
+            None => Err(AsmError {
+                line: self.tokens.last().map_or(1, |tok| tok.line),
+                message: "unexpected end of input".to_string(),
+            }),
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let tok = self.next()?;
+        match tok.text == text {
+            true => Ok(()),
+            false => Err(unexpected(tok, &format!("'{}'", text))),
+        }
+    }
+
+    fn name(&mut self) -> Result<Token<'a>, AsmError> {
+        let tok = self.next()?;
+        match is_name(tok.text) && reg_number(tok.text).is_none() {
+            true => Ok(tok),
+            false => Err(unexpected(tok, "name")),
+        }
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let tok = self.next()?;
+        self.reg_value(tok.text).ok_or_else(|| unexpected(tok, "register"))
+    }
+
+    fn reg_value(&self, text: &str) -> Option<u16> {
+        reg_number(text)
+            .or_else(|| self.aliases.get(text).cloned())
+            .map(|x| x as u16)
+    }
+
+    fn number(&mut self) -> Result<i32, AsmError> {
+        let tok = self.next()?;
+        self.value(tok.text).ok_or_else(|| unexpected(tok, "number"))
+    }
+
+    // A number or the value of a constant.
+    fn value(&self, text: &str) -> Option<i32> {
+        parse_number(text).or_else(|| self.consts.get(text).cloned())
+    }
+
+    fn byte(&mut self) -> Result<u8, AsmError> {
+        let tok = self.next()?;
+        self.byte_value(tok)
+    }
+
+    // Bytes may be given as signed or unsigned numbers.
+    fn byte_value(&self, tok: Token) -> Result<u8, AsmError> {
+        match self.value(tok.text) {
+            Some(value) if (-128..=255).contains(&value) => Ok(value as u8),
+            Some(_) => Err(error(tok, "byte out of range")),
+            None => Err(unexpected(tok, "byte")),
+        }
+    }
+
+    fn nibble(&mut self) -> Result<u16, AsmError> {
+        let tok = self.next()?;
+        match self.value(tok.text) {
+            Some(value) if (0..=0xf).contains(&value) => Ok(value as u16),
+            Some(_) => Err(error(tok, "nibble out of range")),
+            None => Err(unexpected(tok, "nibble")),
+        }
+    }
+
+}
+
+fn parse_number(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16)
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i32::from_str_radix(bin, 2)
+    } else {
+        digits.parse()
+    };
+    value.ok().map(|value| if negative {-value} else {value})
+}
+
+// The x in vx, for registers v0 to vf.
+fn reg_number(text: &str) -> Option<u8> {
+    match text.as_bytes() {
+        [b'v', digit] | [b'V', digit] =>
+            (*digit as char).to_digit(16).map(|x| x as u8),
+        _ => None,
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') &&
+        text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+}
+
+fn error(tok: Token, message: &str) -> AsmError {
+    AsmError { line: tok.line, message: message.to_string() }
+}
+
+fn unexpected(tok: Token, expected: &str) -> AsmError {
+    AsmError {
+        line: tok.line,
+        message: format!("expected {}, found '{}'", expected, tok.text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{assemble, AsmError};
+    use super::super::Mode;
+    use super::super::disasm::{self, Syntax};
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            :alias x v3
+            :const speed 2
+            : main
+                clear
+                x := 0
+                i := sprite
+            : loop
+                sprite x v4 5    # draw
+                x += speed
+                if x != 64 then jump loop
+                draw-score
+                jump main
+            : draw-score
+                v0 := key
+                i := hex v0
+                save v0 - v2
+                return
+            : sprite
+                0xf0 0b10010000 -1
+        ";
+        assert_eq!(Ok(vec![0x00, 0xe0, 0x63, 0x00, 0xa2, 0x1a,
+                           0xd3, 0x45, 0x73, 0x02, 0x33, 0x40, 0x12, 0x06,
+                           0x22, 0x12, 0x12, 0x00,
+                           0xf0, 0x0a, 0xf0, 0x29, 0x50, 0x22, 0x00, 0xee,
+                           0xf0, 0x90, 0xff]),
+                   assemble(source));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Err(AsmError { line: 2,
+                                  message: "undefined label".to_string() }),
+                   assemble("clear\njump nowhere"));
+        assert_eq!(Err(AsmError {
+                       line: 1,
+                       message: "expected register, found 'vg'".to_string()
+                   }),
+                   assemble("sprite v0 vg 1"));
+        assert_eq!(Err(AsmError { line: 3,
+                                  message: "label defined twice".to_string() }),
+                   assemble(": a\nclear\n: a"));
+        assert_eq!(Err(AsmError { line: 1,
+                                  message: "byte out of range".to_string() }),
+                   assemble("v0 := 256"));
+        assert_eq!(Err(AsmError { line: 1,
+                                  message: "unexpected end of input"
+                                           .to_string() }),
+                   assemble("if v0 == 1"));
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        for rom in &[&include_bytes!("../../roms/brix.ch8")[..],
+                     &include_bytes!("../../roms/ant.sch8")[..]] {
+            let source = disasm::disassemble(rom, Mode::Standard, Syntax::Octo);
+            assert_eq!(Ok(rom.to_vec()), assemble(&source));
+        }
+    }
+
+}
@@ -0,0 +1,261 @@
+use super::gamepad::Buttons;
+use super::keymap::{self, Keymap, Layout};
+use super::palette::{self, Palette};
+use super::quirks::{self, Quirks};
+use super::scheduler;
+use super::timing::{self, Timing};
+use super::ui::{self, Scaling};
+use serde_json;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// The directory holding the user's settings, following the XDG base
+// directory specification.
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| {
+            PathBuf::from(home).join(".config")
+        }))
+        .map(|dir| dir.join("chip8"))
+}
+
+// The user's defaults, read from config.json in the config directory.
+// Settings left out fall back to the defaults of the emulator. Settings
+// for a particular rom, from the rom database, and those given on the
+// command line take precedence. For instance:
+//
+//     {
+//       "scale": 6,
+//       "scaling": "integer",
+//       "quirks": "vip",
+//       "cycles_per_frame": 12,
+//       "volume": 50,
+//       "phosphor": true,
+//       "theme": "amber",
+//       "colors": ["#000000"],
+//       "layout": "azerty",
+//       "bindings": { "up": "5", "down": "8", "p": "pause" },
+//       "buttons": { "a": "5", "x": "rewind" }
+//     }
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Window settings.
+    pub scale: Option<usize>,
+    pub fullscreen: bool,
+    // One of the ui::SCALING_NAMES.
+    pub scaling: Option<String>,
+    pub mute: bool,
+    // One of the quirks::PRESET_NAMES.
+    pub quirks: Option<String>,
+    // One of the timing::TIMING_NAMES.
+    pub timing: Option<String>,
+    // Given fixed timing.
+    pub cycles_per_frame: Option<u64>,
+    // Multiple of the normal speed.
+    pub speed: Option<f64>,
+    // Loudness of the beep as a percentage.
+    pub volume: Option<u8>,
+    // Pitch of the standard beep, in Hz.
+    pub tone: Option<f64>,
+    // Whether pixels fade out, and how long they take to in milliseconds,
+    // see Phosphor.
+    pub phosphor: bool,
+    pub decay: Option<u64>,
+    // One of the palette::THEME_NAMES.
+    pub theme: Option<String>,
+    // Up to four colors in hex, in place of those of the theme, see
+    // Palette::with_colors.
+    pub colors: Option<Vec<String>>,
+    // One of the keymap::LAYOUT_NAMES, by which keys are named.
+    pub layout: Option<String>,
+    // Keypad keys and actions by keyboard key, see Keymap::bind.
+    pub bindings: HashMap<String, String>,
+    // Keypad keys and actions by controller button, see Buttons::bind.
+    pub buttons: HashMap<String, String>,
+}
+
+impl Config {
+
+    pub fn parse(json: &str) -> Result<Config, Box<dyn Error>> {
+        let config: Config = serde_json::from_str(json)?;
+        config.check()?;
+        Ok(config)
+    }
+
+    // The config file, if there is one.
+    pub fn load() -> Result<Config, Box<dyn Error>> {
+        let path = match config_dir() {
+            Some(dir) => dir.join("config.json"),
+            None => return Ok(Config::default()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(json) => Ok(Config::parse(&json)
+                .map_err(|err| format!("{}: {}", path.display(), err))?),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound =>
+                Ok(Config::default()),
+            Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    pub fn scaling(&self) -> Option<Scaling> {
+        self.scaling.as_ref().and_then(|name| Scaling::from_name(name))
+    }
+
+    pub fn quirks(&self) -> Option<Quirks> {
+        self.quirks.as_ref().and_then(|name| Quirks::preset(name))
+    }
+
+    pub fn timing(&self) -> Option<Timing> {
+        let timing = self.timing.as_ref()
+            .and_then(|name| Timing::from_name(name));
+        timing::with_cycles_per_frame(timing, self.cycles_per_frame)
+    }
+
+    pub fn palette(&self) -> Palette {
+        let theme = self.theme.as_ref()
+            .and_then(|name| Palette::theme(name))
+            .unwrap_or_default();
+        self.colors.as_ref()
+            .and_then(|colors| theme.with_colors(colors).ok())
+            .unwrap_or(theme)
+    }
+
+    pub fn keymap(&self) -> Keymap {
+        let layout = self.layout.as_ref()
+            .and_then(|name| Layout::from_name(name))
+            .unwrap_or_default();
+        let mut keymap = Keymap::new(layout);
+        keymap.bind(&self.bindings).expect("bindings checked by parse");
+        keymap
+    }
+
+    pub fn buttons(&self) -> Buttons {
+        let mut buttons = Buttons::default();
+        buttons.bind(&self.buttons).expect("buttons checked by parse");
+        buttons
+    }
+
+    // Settings out of range, or names that do not match anything.
+    fn check(&self) -> Result<(), String> {
+        if self.scale == Some(0) {
+            return Err("expected scale above 0".to_string());
+        }
+        if self.scaling.is_some() && self.scaling().is_none() {
+            return Err(format!("expected scaling, one of: {}",
+                               ui::SCALING_NAMES.join(", ")));
+        }
+        if self.quirks.is_some() && self.quirks().is_none() {
+            return Err(format!("expected quirks, one of: {}",
+                               quirks::PRESET_NAMES.join(", ")));
+        }
+        let timing = self.timing.as_ref().map(|name| Timing::from_name(name));
+        if timing == Some(None) {
+            return Err(format!("expected timing, one of: {}",
+                               timing::TIMING_NAMES.join(", ")));
+        }
+        if self.cycles_per_frame == Some(0) {
+            return Err("expected cycles per frame above 0".to_string());
+        }
+        let speeds = scheduler::MIN_SPEED..=scheduler::MAX_SPEED;
+        if self.speed.is_some_and(|speed| !speeds.contains(&speed)) {
+            return Err(format!("expected speed from {} to {}",
+                               scheduler::MIN_SPEED, scheduler::MAX_SPEED));
+        }
+        if self.volume.is_some_and(|volume| volume > 100) {
+            return Err("expected volume from 0 to 100".to_string());
+        }
+        if self.tone.is_some_and(|tone| tone <= 0.0) {
+            return Err("expected tone above 0".to_string());
+        }
+        if self.decay == Some(0) {
+            return Err("expected decay above 0".to_string());
+        }
+        let theme = self.theme.as_ref().map(|name| Palette::theme(name));
+        if theme == Some(None) {
+            return Err(format!("expected theme, one of: {}",
+                               palette::THEME_NAMES.join(", ")));
+        }
+        if let Some(ref colors) = self.colors {
+            Palette::default().with_colors(colors)?;
+        }
+        let layout = self.layout.as_ref().map(|name| Layout::from_name(name));
+        if layout == Some(None) {
+            return Err(format!("expected layout, one of: {}",
+                               keymap::LAYOUT_NAMES.join(", ")));
+        }
+        Keymap::default().bind(&self.bindings)?;
+        Buttons::default().bind(&self.buttons)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Config;
+    use super::super::gamepad::Gamepad;
+    use super::super::keymap::Action;
+    use super::super::palette::Palette;
+    use super::super::quirks::Quirks;
+    use super::super::timing::Timing;
+    use super::super::ui::Scaling;
+    use sdl2::controller::Button;
+    use sdl2::event::Event;
+    use sdl2::keyboard::Scancode;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(r##"{
+            "scale": 6,
+            "scaling": "integer",
+            "quirks": "vip",
+            "cycles_per_frame": 12,
+            "volume": 50,
+            "theme": "amber",
+            "colors": ["#000000"],
+            "layout": "azerty",
+            "bindings": { "up": "5", "a": "pause" },
+            "buttons": { "a": "5" }
+        }"##).unwrap();
+        assert_eq!(Some(6), config.scale);
+        assert_eq!(Some(Scaling::Integer), config.scaling());
+        assert_eq!(Some(Quirks::cosmac_vip()), config.quirks());
+        assert_eq!(Some(Timing::Fixed(12)), config.timing());
+        let amber = Palette::theme("amber").unwrap();
+        assert_eq!([0, 0, 0], config.palette().0[0]);
+        assert_eq!(amber.0[1..], config.palette().0[1..]);
+        let keymap = config.keymap();
+        assert!(keymap.keys(|scancode| scancode == Scancode::Up)[5]);
+        assert_eq!(Some(Action::Pause), keymap.action(Scancode::Q, false));
+        let mut gamepad = Gamepad::new(config.buttons());
+        gamepad.handle(&Event::ControllerButtonDown {
+            timestamp: 0, which: 0, button: Button::A,
+        });
+        assert!(gamepad.keys()[5]);
+        assert_eq!(Config::default(), Config::parse("{}").unwrap());
+    }
+
+    #[test]
+    fn test_rejects_invalid_settings() {
+        assert!(Config::parse(r#"{"scale": 0}"#).is_err());
+        assert!(Config::parse(r#"{"scaling": "stretch"}"#).is_err());
+        assert!(Config::parse(r#"{"quirks": "fast"}"#).is_err());
+        assert!(Config::parse(r#"{"speed": 100}"#).is_err());
+        assert!(Config::parse(r#"{"volume": 101}"#).is_err());
+        assert!(Config::parse(r#"{"tone": 0}"#).is_err());
+        assert!(Config::parse(r#"{"colors": ["red"]}"#).is_err());
+        assert!(Config::parse(r#"{"theme": "pink"}"#).is_err());
+        assert!(Config::parse(r#"{"decay": 0}"#).is_err());
+        assert!(Config::parse(r#"{"bindings": {"nope": "5"}}"#).is_err());
+        assert!(Config::parse(r#"{"layout": "colemak"}"#).is_err());
+        assert!(Config::parse(r#"{"buttons": {"a": "jump"}}"#).is_err());
+        assert!(Config::parse(r#"{"sclae": 6}"#).is_err());
+    }
+
+}
@@ -0,0 +1,451 @@
+use super::emu::Emu;
+use super::watch::{WatchAction, Watchpoint};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+const HELP: &str = "\
+Commands (addresses and values in hex):
+  b [ADDR]      set a breakpoint at ADDR, or list the breakpoints
+  d ADDR        delete the breakpoint at ADDR
+  w [WP [log]]  halt when the watchpoint WP is hit, or just log the hit, or
+                list the watchpoints. WP is one of r:ADDR (ram read), w:ADDR
+                (ram written), vX or i (register changed), vX=VAL or i=VAL
+                (register changed to VAL)
+  dw WP         delete the watchpoint WP
+  s [N]         step N instructions (default 1)
+  n             step, treating a subroutine call as a single instruction
+  f             run until the current subroutine returns
+  c             continue running
+  p             pause running
+  r             show the registers
+  bt            show the stack
+  m ADDR [LEN]  show LEN bytes of memory from ADDR (default 16)
+  h             show this help";
+
+// Commands accepted by the debugger, as typed on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Break(Option<u16>),
+    Delete(u16),
+    Watch(Option<(Watchpoint, WatchAction)>),
+    DeleteWatch(Watchpoint),
+    Step(usize),
+    Next,
+    Finish,
+    Continue,
+    Pause,
+    Registers,
+    Stack,
+    Memory(u16, usize),
+    Help,
+}
+
+impl Command {
+
+    // Parse a single line of input into a command.
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let cmd = match (name, args.len()) {
+            ("b", 0) => Command::Break(None),
+            ("b", 1) => Command::Break(Some(parse_hex(args[0])?)),
+            ("d", 1) => Command::Delete(parse_hex(args[0])?),
+            ("w", 0) => Command::Watch(None),
+            ("w", 1) => Command::Watch(
+                Some((Watchpoint::parse(args[0])?, WatchAction::Halt))),
+            ("w", 2) if args[1] == "log" => Command::Watch(
+                Some((Watchpoint::parse(args[0])?, WatchAction::Log))),
+            ("dw", 1) => Command::DeleteWatch(Watchpoint::parse(args[0])?),
+            ("s", 0) => Command::Step(1),
+            ("s", 1) => Command::Step(parse_count(args[0])?),
+            ("n", 0) => Command::Next,
+            ("f", 0) => Command::Finish,
+            ("c", 0) => Command::Continue,
+            ("p", 0) => Command::Pause,
+            ("r", 0) => Command::Registers,
+            ("bt", 0) => Command::Stack,
+            ("m", 1) => Command::Memory(parse_hex(args[0])?, 16),
+            ("m", 2) => Command::Memory(parse_hex(args[0])?, 
+                                        parse_hex(args[1])? as usize),
+            ("h", 0) => Command::Help,
+            _ => return Err(format!("Invalid command: {}. Type h for help.",
+                                    line.trim())),
+        };
+        Ok(cmd)
+    }
+
+}
+
+fn parse_hex(s: &str) -> Result<u16, String> {
+    let digits = s.trim_start_matches("0x");
+    u16::from_str_radix(digits, 16)
+        .map_err(|_| format!("Invalid hex number: {}", s))
+}
+
+fn parse_count(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("Invalid count: {}", s))
+}
+
+// What the debugger lets the emulator do next.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    // Wait for a command before executing anything.
+    Stopped,
+    // Execute until a breakpoint is hit.
+    Running,
+    // Execute this many more instructions.
+    Stepping(usize),
+    // Execute until the stack pointer drops to this depth or below, i.e.
+    // until a subroutine returns.
+    RunningToDepth(usize),
+}
+
+// Controls the emulator from a command line: stops at breakpoints, steps
+// through instructions and inspects the processor state. The emulator must
+// consult before_cycle and after_cycle around each cycle it executes.
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    state: State,
+    // A breakpoint at this address was just reported. It must not stop the
+    // emulator again before the instruction has executed.
+    resumed_from: Option<u16>,
+}
+
+impl Debugger {
+
+    // Create a debugger that holds the emulator until told to continue.
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            state: State::Stopped,
+            resumed_from: None
+        }
+    }
+
+    // Whether the emulator may execute instructions.
+    pub fn running(&self) -> bool {
+        self.state != State::Stopped
+    }
+
+    // To be called before executing a cycle. Stops the emulator if a
+    // breakpoint is hit, in which case a report is returned.
+    pub fn before_cycle(&mut self, emu: &Emu) -> Option<String> {
+        if !self.running() {
+            return None;
+        }
+        let pc = emu.pc();
+        if self.resumed_from.take() == Some(pc) {
+            return None;
+        }
+        if self.breakpoints.contains(&pc) {
+            self.state = State::Stopped;
+            self.resumed_from = Some(pc);
+            return Some(format!("Breakpoint at {:03x}\n{}", pc, location(emu)));
+        }
+        None
+    }
+
+    // To be called after executing a cycle. Stops the emulator once a step
+    // has completed or a halting watchpoint was hit, in which case a report
+    // is returned. Logged watchpoint hits are reported as well.
+    pub fn after_cycle(&mut self, emu: &mut Emu) -> Option<String> {
+        self.resumed_from = None;
+        let hits = emu.take_watch_hits();
+        let halt = hits.iter().any(|hit| hit.action == WatchAction::Halt);
+        let mut report = hits.iter()
+            .map(|hit| hit.to_string())
+            .collect::<Vec<String>>();
+        let done = halt || match self.state {
+            State::Stepping(n) => {
+                self.state = State::Stepping(n - 1);
+                n == 1
+            },
+            State::RunningToDepth(depth) => emu.sp() <= depth,
+            State::Running | State::Stopped => false,
+        };
+        if done {
+            self.state = State::Stopped;
+            report.push(location(emu));
+        }
+        match report.is_empty() {
+            true => None,
+            false => Some(report.join("\n")),
+        }
+    }
+
+    // Carry out a command, returning the output to show.
+    pub fn handle(&mut self, cmd: Command, emu: &mut Emu) -> String {
+        let pc = emu.pc();
+        match cmd {
+            Command::Break(Some(addr)) => {
+                self.breakpoints.insert(addr);
+                format!("Breakpoint set at {:03x}", addr)
+            },
+            Command::Break(None) if self.breakpoints.is_empty() =>
+                "No breakpoints".to_string(),
+            Command::Break(None) => self.breakpoints.iter()
+                .map(|addr| format!("{:03x}", addr))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Command::Delete(addr) => match self.breakpoints.remove(&addr) {
+                true => format!("Breakpoint deleted at {:03x}", addr),
+                false => format!("No breakpoint at {:03x}", addr),
+            },
+            Command::Watch(None) => watchpoints(emu),
+            Command::Watch(Some((watchpoint, action))) => {
+                emu.add_watchpoint(watchpoint, action);
+                format!("Watchpoint set on {}", watchpoint)
+            },
+            Command::DeleteWatch(watchpoint) => 
+                match emu.remove_watchpoint(watchpoint) {
+                    true => format!("Watchpoint deleted on {}", watchpoint),
+                    false => format!("No watchpoint on {}", watchpoint),
+                },
+            Command::Step(0) => String::new(),
+            Command::Step(n) => self.resume(pc, State::Stepping(n)),
+            Command::Next => match next_opcode(emu) & 0xf000 {
+                // Run until the subroutine returns to this depth.
+                0x2000 => self.resume(pc, State::RunningToDepth(emu.sp())),
+                _ => self.resume(pc, State::Stepping(1)),
+            },
+            Command::Finish if emu.sp() == 0 =>
+                "Not in a subroutine".to_string(),
+            Command::Finish =>
+                self.resume(pc, State::RunningToDepth(emu.sp() - 1)),
+            Command::Continue => self.resume(pc, State::Running),
+            Command::Pause => {
+                self.state = State::Stopped;
+                location(emu)
+            },
+            Command::Registers => registers(emu),
+            Command::Stack => stack(emu),
+            Command::Memory(addr, len) => memory(emu, addr as usize, len),
+            Command::Help => HELP.to_string(),
+        }
+    }
+
+    // Let the emulator execute again, without stopping at a breakpoint on
+    // the current instruction straight away.
+    fn resume(&mut self, pc: u16, state: State) -> String {
+        self.state = state;
+        self.resumed_from = Some(pc);
+        String::new()
+    }
+
+}
+
+// The opcode the program counter is pointing to.
+fn next_opcode(emu: &Emu) -> u16 {
+    let ram = emu.ram();
+    let pc = emu.pc() as usize;
+    match (ram.get(pc), ram.get(pc + 1)) {
+        (Some(&hbyte), Some(&lbyte)) => (hbyte as u16) << 8 | lbyte as u16,
+        _ => 0,
+    }
+}
+
+fn location(emu: &Emu) -> String {
+    format!("{:03x}: {:04x}", emu.pc(), next_opcode(emu))
+}
+
+// Describe the registers, as shown by the r command.
+pub fn registers(emu: &Emu) -> String {
+    let mut out = format!("pc={:03x} opcode={:04x} i={:03x} dt={:02x} st={:02x} \
+                           sp={:x}\n", emu.pc(), emu.opcode(), emu.ram_idx(),
+                           emu.dt(), emu.st(), emu.sp());
+    for (i, v) in emu.v().iter().enumerate() {
+        let sep = if i % 8 == 7 {"\n"} else {" "};
+        write!(out, "v{:x}={:02x}{}", i, v, sep).unwrap();
+    }
+    out.trim_end().to_string()
+}
+
+fn watchpoints(emu: &Emu) -> String {
+    if emu.watchpoints().is_empty() {
+        return "No watchpoints".to_string();
+    }
+    emu.watchpoints().iter()
+        .map(|&(watchpoint, action)| match action {
+            WatchAction::Halt => watchpoint.to_string(),
+            WatchAction::Log => format!("{} log", watchpoint),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn stack(emu: &Emu) -> String {
+    if emu.sp() == 0 {
+        return "Stack is empty".to_string();
+    }
+    emu.stack()[..emu.sp()].iter().enumerate().rev()
+        .map(|(i, addr)| format!("#{} {:03x}", i, addr))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn memory(emu: &Emu, addr: usize, len: usize) -> String {
+    let ram = emu.ram();
+    let end = (addr + len).min(ram.len());
+    if addr >= end {
+        return format!("Address out of range: {:03x}", addr);
+    }
+    let mut out = String::new();
+    for (row, bytes) in ram[addr..end].chunks(16).enumerate() {
+        write!(out, "{:03x}:", addr + row * 16).unwrap();
+        for byte in bytes {
+            write!(out, " {:02x}", byte).unwrap();
+        }
+        out.push('\n');
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Command, Debugger};
+    use super::super::emu::Emu;
+    use super::super::quirks::Quirks;
+
+    // 200: call 206
+    // 202: v0 += 1
+    // 204: jump 202
+    // 206: v1 += 1
+    // 208: return
+    fn emu_with_program() -> Emu {
+        let mut emu = Emu::new(Quirks::default());
+        emu.load_rom(vec![0x22, 0x06, 0x70, 0x01, 0x12, 0x02,
+                          0x71, 0x01, 0x00, 0xee]).unwrap();
+        emu
+    }
+
+    // Run the emulator under control of the debugger until it stops, or
+    // the maximum number of cycles is exceeded. Returns the last report.
+    fn run(dbg: &mut Debugger, emu: &mut Emu, max_cycles: usize)
+        -> Option<String> {
+        let mut report = None;
+        for _ in 0..max_cycles {
+            if let Some(r) = dbg.before_cycle(emu) { report = Some(r); }
+            if !dbg.running() { break; }
+            emu.execute_cycle().unwrap();
+            if let Some(r) = dbg.after_cycle(emu) { report = Some(r); }
+            if !dbg.running() { break; }
+        }
+        report
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(Command::Break(Some(0x202))), Command::parse("b 202"));
+        assert_eq!(Ok(Command::Break(Some(0x202))), Command::parse("b 0x202"));
+        assert_eq!(Ok(Command::Break(None)), Command::parse("b"));
+        assert_eq!(Ok(Command::Step(10)), Command::parse(" s  10 "));
+        assert_eq!(Ok(Command::Memory(0x300, 0x20)), Command::parse("m 300 20"));
+        assert!(Command::parse("b xyz").is_err());
+        assert!(Command::parse("q").is_err());
+        assert!(Command::parse("").is_err());
+    }
+
+    #[test]
+    fn test_starts_stopped() {
+        let mut dbg = Debugger::new();
+        let mut emu = emu_with_program();
+        assert_eq!(None, run(&mut dbg, &mut emu, 10));
+        assert_eq!(0x200, emu.pc());
+    }
+
+    #[test]
+    fn test_step() {
+        let mut dbg = Debugger::new();
+        let mut emu = emu_with_program();
+        dbg.handle(Command::Step(2), &mut emu);
+        assert_eq!(Some("208: 00ee".to_string()), run(&mut dbg, &mut emu, 10));
+        assert!(!dbg.running());
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut dbg = Debugger::new();
+        let mut emu = emu_with_program();
+        dbg.handle(Command::Break(Some(0x204)), &mut emu);
+        dbg.handle(Command::Continue, &mut emu);
+        assert_eq!(Some("Breakpoint at 204\n204: 1202".to_string()),
+                   run(&mut dbg, &mut emu, 100));
+        assert_eq!(1, emu.v()[0]);
+        // Continuing must not stop at the same breakpoint straight away.
+        dbg.handle(Command::Continue, &mut emu);
+        run(&mut dbg, &mut emu, 100);
+        assert_eq!(0x204, emu.pc());
+        assert_eq!(2, emu.v()[0]);
+    }
+
+    #[test]
+    fn test_next_steps_over_call() {
+        let mut dbg = Debugger::new();
+        let mut emu = emu_with_program();
+        dbg.handle(Command::Next, &mut emu);
+        assert_eq!(Some("202: 7001".to_string()), run(&mut dbg, &mut emu, 10));
+        assert_eq!(1, emu.v()[1]);
+    }
+
+    #[test]
+    fn test_finish_runs_until_return() {
+        let mut dbg = Debugger::new();
+        let mut emu = emu_with_program();
+        dbg.handle(Command::Step(1), &mut emu);
+        run(&mut dbg, &mut emu, 10);
+        assert_eq!(0x206, emu.pc());
+        dbg.handle(Command::Finish, &mut emu);
+        assert_eq!(Some("202: 7001".to_string()), run(&mut dbg, &mut emu, 10));
+        assert_eq!(0, emu.sp());
+    }
+
+    #[test]
+    fn test_finish_outside_subroutine() {
+        let mut dbg = Debugger::new();
+        let mut emu = emu_with_program();
+        assert_eq!("Not in a subroutine", dbg.handle(Command::Finish, &mut emu));
+        assert!(!dbg.running());
+    }
+
+    #[test]
+    fn test_watch_write_halts() {
+        let mut dbg = Debugger::new();
+        let mut emu = Emu::new(Quirks::default());
+        // 200: i = 300
+        // 202: v0 = 7
+        // 204: store v0
+        // 206: jump 206
+        emu.load_rom(vec![0xa3, 0x00, 0x60, 0x07, 0xf0, 0x55, 0x12, 0x06])
+           .unwrap();
+        dbg.handle(Command::parse("w w:300").unwrap(), &mut emu);
+        dbg.handle(Command::Continue, &mut emu);
+        assert_eq!(Some("Watchpoint w:300 hit by f055 at 204, value 07\n\
+                         206: 1206".to_string()), 
+                   run(&mut dbg, &mut emu, 100));
+        assert!(!dbg.running());
+    }
+
+    #[test]
+    fn test_watch_register_logs() {
+        let mut dbg = Debugger::new();
+        let mut emu = emu_with_program();
+        dbg.handle(Command::parse("w v0=2 log").unwrap(), &mut emu);
+        dbg.handle(Command::Continue, &mut emu);
+        assert_eq!(Some("Watchpoint v0=2 hit by 7001 at 202, value 02"
+                        .to_string()), 
+                   run(&mut dbg, &mut emu, 6));
+        assert!(dbg.running());
+        assert_eq!("v0=2 log", dbg.handle(Command::Watch(None), &mut emu));
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut dbg = Debugger::new();
+        let mut emu = emu_with_program();
+        assert_eq!("200: 22 06 70 01", 
+                   dbg.handle(Command::Memory(0x200, 4), &mut emu));
+    }
+
+}
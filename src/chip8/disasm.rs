@@ -0,0 +1,307 @@
+use super::Mode;
+use super::emu::PROGRAM_START;
+use super::instruction::Instruction;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+// Names by which the syntaxes can be selected, e.g. from the command line.
+pub const SYNTAX_NAMES: [&str; 2] = ["octo", "cowgod"];
+
+// The flavour of mnemonics to print.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Syntax {
+    // As understood by the Octo assembler, e.g. "v0 += 0x01".
+    Octo,
+    // As in Cowgod's Chip-8 Technical Reference, e.g. "ADD V0, 0x01".
+    Cowgod,
+}
+
+impl Syntax {
+
+    // Look up a syntax by one of the names in SYNTAX_NAMES.
+    pub fn from_name(name: &str) -> Option<Syntax> {
+        match name {
+            "octo" => Some(Syntax::Octo),
+            "cowgod" => Some(Syntax::Cowgod),
+            _ => None,
+        }
+    }
+
+    fn comment(self) -> char {
+        match self {
+            Syntax::Octo => '#',
+            Syntax::Cowgod => ';',
+        }
+    }
+
+}
+
+// A stretch of the rom, decoded as an instruction where possible.
+struct Line<'a> {
+    addr: u16,
+    bytes: &'a [u8],
+    instruction: Option<Instruction>,
+}
+
+// Disassemble a rom as loaded at the start of program memory, one
+// instruction per line along with its address and raw bytes. Bytes that do
+// not decode to an instruction for the given mode are printed as data.
+// Jump and call targets within the rom are labelled.
+pub fn disassemble(rom: &[u8], mode: Mode, syntax: Syntax) -> String {
+    let lines = decode(rom, mode);
+    let addrs: BTreeSet<u16> = lines.iter().map(|line| line.addr).collect();
+    let labels: BTreeSet<u16> = lines.iter()
+        .filter_map(|line| line.instruction.and_then(|i| i.target()))
+        .filter(|target| addrs.contains(target))
+        .collect();
+    let mut out = String::new();
+    for line in &lines {
+        if labels.contains(&line.addr) {
+            match syntax {
+                Syntax::Octo => writeln!(out, ": {}", label(line.addr)),
+                Syntax::Cowgod => writeln!(out, "{}:", label(line.addr)),
+            }.unwrap();
+        }
+        let text = match line.instruction {
+            Some(instruction) => {
+                let target = match instruction.target() {
+                    Some(addr) if labels.contains(&addr) => label(addr),
+                    Some(addr) => format!("0x{:03x}", addr),
+                    None => String::new(),
+                };
+                let long = match line.bytes {
+                    [_, _, hbyte, lbyte] => (*hbyte as u16) << 8 | *lbyte as u16,
+                    _ => 0,
+                };
+                match syntax {
+                    Syntax::Octo => octo(instruction, &target, long),
+                    Syntax::Cowgod => cowgod(instruction, &target, long),
+                }
+            },
+            None => data(line.bytes, syntax),
+        };
+        let raw: Vec<String> = line.bytes.iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        writeln!(out, "    {:<24}{} {:03x}: {}", text, syntax.comment(),
+                 line.addr, raw.join(" ")).unwrap();
+    }
+    out
+}
+
+// Split the rom up into instructions and data, two bytes at a time.
+fn decode(rom: &[u8], mode: Mode) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let instruction = match rom.get(offset..offset + 2) {
+            Some(&[hbyte, lbyte]) =>
+                Instruction::decode((hbyte as u16) << 8 | lbyte as u16, mode)
+                    .filter(|i| offset + i.size() <= rom.len()),
+            _ => None,
+        };
+        let size = match instruction {
+            Some(instruction) => instruction.size(),
+            None => (rom.len() - offset).min(2),
+        };
+        lines.push(Line {
+            addr: (PROGRAM_START + offset) as u16,
+            bytes: &rom[offset..offset + size],
+            instruction
+        });
+        offset += size;
+    }
+    lines
+}
+
+fn label(addr: u16) -> String {
+    format!("L{:03x}", addr)
+}
+
+fn data(bytes: &[u8], syntax: Syntax) -> String {
+    let bytes: Vec<String> = bytes.iter()
+        .map(|byte| format!("0x{:02x}", byte))
+        .collect();
+    match syntax {
+        Syntax::Octo => bytes.join(" "),
+        Syntax::Cowgod => format!("DB {}", bytes.join(", ")),
+    }
+}
+
+fn octo(instruction: Instruction, target: &str, long: u16) -> String {
+    let v = |x: u8| format!("v{:x}", x);
+    match instruction {
+        Instruction::ScrollDown(n) => format!("scroll-down {}", n),
+        Instruction::ScrollUp(n) => format!("scroll-up {}", n),
+        Instruction::Clear => "clear".to_string(),
+        Instruction::Return => "return".to_string(),
+        Instruction::ScrollRight => "scroll-right".to_string(),
+        Instruction::ScrollLeft => "scroll-left".to_string(),
+        Instruction::Exit => "exit".to_string(),
+        Instruction::Lores => "lores".to_string(),
+        Instruction::Hires => "hires".to_string(),
+        Instruction::Jump(_) => format!("jump {}", target),
+        Instruction::Call(_) => format!(":call {}", target),
+        // Octo expresses skips as conditions under which the next
+        // instruction executes, hence the inverted comparisons.
+        Instruction::SkipIfEqual(x, nn) =>
+            format!("if {} != 0x{:02x} then", v(x), nn),
+        Instruction::SkipIfNotEqual(x, nn) =>
+            format!("if {} == 0x{:02x} then", v(x), nn),
+        Instruction::SkipIfRegEqual(x, y) =>
+            format!("if {} != {} then", v(x), v(y)),
+        Instruction::SaveRange(x, y) => format!("save {} - {}", v(x), v(y)),
+        Instruction::LoadRange(x, y) => format!("load {} - {}", v(x), v(y)),
+        Instruction::Set(x, nn) => format!("{} := 0x{:02x}", v(x), nn),
+        Instruction::Add(x, nn) => format!("{} += 0x{:02x}", v(x), nn),
+        Instruction::SetReg(x, y) => format!("{} := {}", v(x), v(y)),
+        Instruction::Or(x, y) => format!("{} |= {}", v(x), v(y)),
+        Instruction::And(x, y) => format!("{} &= {}", v(x), v(y)),
+        Instruction::Xor(x, y) => format!("{} ^= {}", v(x), v(y)),
+        Instruction::AddReg(x, y) => format!("{} += {}", v(x), v(y)),
+        Instruction::Sub(x, y) => format!("{} -= {}", v(x), v(y)),
+        Instruction::ShiftRight(x, y) => format!("{} >>= {}", v(x), v(y)),
+        Instruction::SubReversed(x, y) => format!("{} =- {}", v(x), v(y)),
+        Instruction::ShiftLeft(x, y) => format!("{} <<= {}", v(x), v(y)),
+        Instruction::SkipIfRegNotEqual(x, y) =>
+            format!("if {} == {} then", v(x), v(y)),
+        Instruction::SetIndex(nnn) => format!("i := 0x{:03x}", nnn),
+        Instruction::JumpOffset(nnn) => format!("jump0 0x{:03x}", nnn),
+        Instruction::Random(x, nn) =>
+            format!("{} := random 0x{:02x}", v(x), nn),
+        Instruction::Draw(x, y, n) =>
+            format!("sprite {} {} {}", v(x), v(y), n),
+        Instruction::SkipIfKey(x) => format!("if {} -key then", v(x)),
+        Instruction::SkipIfNotKey(x) => format!("if {} key then", v(x)),
+        Instruction::SetIndexLong => format!("i := long 0x{:04x}", long),
+        Instruction::Planes(n) => format!("plane {}", n),
+        Instruction::Audio => "audio".to_string(),
+        Instruction::GetDelay(x) => format!("{} := delay", v(x)),
+        Instruction::WaitKey(x) => format!("{} := key", v(x)),
+        Instruction::SetDelay(x) => format!("delay := {}", v(x)),
+        Instruction::SetSound(x) => format!("buzzer := {}", v(x)),
+        Instruction::AddIndex(x) => format!("i += {}", v(x)),
+        Instruction::Font(x) => format!("i := hex {}", v(x)),
+        Instruction::BigFont(x) => format!("i := bighex {}", v(x)),
+        Instruction::Bcd(x) => format!("bcd {}", v(x)),
+        Instruction::Pitch(x) => format!("pitch := {}", v(x)),
+        Instruction::Store(x) => format!("save {}", v(x)),
+        Instruction::Load(x) => format!("load {}", v(x)),
+        Instruction::SaveFlags(x) => format!("saveflags {}", v(x)),
+        Instruction::LoadFlags(x) => format!("loadflags {}", v(x)),
+    }
+}
+
+fn cowgod(instruction: Instruction, target: &str, long: u16) -> String {
+    let v = |x: u8| format!("V{:X}", x);
+    match instruction {
+        Instruction::ScrollDown(n) => format!("SCD {}", n),
+        Instruction::ScrollUp(n) => format!("SCU {}", n),
+        Instruction::Clear => "CLS".to_string(),
+        Instruction::Return => "RET".to_string(),
+        Instruction::ScrollRight => "SCR".to_string(),
+        Instruction::ScrollLeft => "SCL".to_string(),
+        Instruction::Exit => "EXIT".to_string(),
+        Instruction::Lores => "LOW".to_string(),
+        Instruction::Hires => "HIGH".to_string(),
+        Instruction::Jump(_) => format!("JP {}", target),
+        Instruction::Call(_) => format!("CALL {}", target),
+        Instruction::SkipIfEqual(x, nn) => format!("SE {}, 0x{:02x}", v(x), nn),
+        Instruction::SkipIfNotEqual(x, nn) =>
+            format!("SNE {}, 0x{:02x}", v(x), nn),
+        Instruction::SkipIfRegEqual(x, y) => format!("SE {}, {}", v(x), v(y)),
+        Instruction::SaveRange(x, y) => format!("LD [I], {}-{}", v(x), v(y)),
+        Instruction::LoadRange(x, y) => format!("LD {}-{}, [I]", v(x), v(y)),
+        Instruction::Set(x, nn) => format!("LD {}, 0x{:02x}", v(x), nn),
+        Instruction::Add(x, nn) => format!("ADD {}, 0x{:02x}", v(x), nn),
+        Instruction::SetReg(x, y) => format!("LD {}, {}", v(x), v(y)),
+        Instruction::Or(x, y) => format!("OR {}, {}", v(x), v(y)),
+        Instruction::And(x, y) => format!("AND {}, {}", v(x), v(y)),
+        Instruction::Xor(x, y) => format!("XOR {}, {}", v(x), v(y)),
+        Instruction::AddReg(x, y) => format!("ADD {}, {}", v(x), v(y)),
+        Instruction::Sub(x, y) => format!("SUB {}, {}", v(x), v(y)),
+        Instruction::ShiftRight(x, y) => format!("SHR {}, {}", v(x), v(y)),
+        Instruction::SubReversed(x, y) => format!("SUBN {}, {}", v(x), v(y)),
+        Instruction::ShiftLeft(x, y) => format!("SHL {}, {}", v(x), v(y)),
+        Instruction::SkipIfRegNotEqual(x, y) =>
+            format!("SNE {}, {}", v(x), v(y)),
+        Instruction::SetIndex(nnn) => format!("LD I, 0x{:03x}", nnn),
+        Instruction::JumpOffset(nnn) => format!("JP V0, 0x{:03x}", nnn),
+        Instruction::Random(x, nn) => format!("RND {}, 0x{:02x}", v(x), nn),
+        Instruction::Draw(x, y, n) =>
+            format!("DRW {}, {}, {}", v(x), v(y), n),
+        Instruction::SkipIfKey(x) => format!("SKP {}", v(x)),
+        Instruction::SkipIfNotKey(x) => format!("SKNP {}", v(x)),
+        Instruction::SetIndexLong => format!("LD I, LONG 0x{:04x}", long),
+        Instruction::Planes(n) => format!("PLANE {}", n),
+        Instruction::Audio => "AUDIO".to_string(),
+        Instruction::GetDelay(x) => format!("LD {}, DT", v(x)),
+        Instruction::WaitKey(x) => format!("LD {}, K", v(x)),
+        Instruction::SetDelay(x) => format!("LD DT, {}", v(x)),
+        Instruction::SetSound(x) => format!("LD ST, {}", v(x)),
+        Instruction::AddIndex(x) => format!("ADD I, {}", v(x)),
+        Instruction::Font(x) => format!("LD F, {}", v(x)),
+        Instruction::BigFont(x) => format!("LD HF, {}", v(x)),
+        Instruction::Bcd(x) => format!("LD B, {}", v(x)),
+        Instruction::Pitch(x) => format!("PITCH {}", v(x)),
+        Instruction::Store(x) => format!("LD [I], {}", v(x)),
+        Instruction::Load(x) => format!("LD {}, [I]", v(x)),
+        Instruction::SaveFlags(x) => format!("LD R, {}", v(x)),
+        Instruction::LoadFlags(x) => format!("LD {}, R", v(x)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{disassemble, Syntax};
+    use super::super::Mode;
+
+    // 200: call 206
+    // 202: v0 += 1
+    // 204: jump 202
+    // 206: return
+    // 208: one byte of data
+    const ROM: [u8; 9] = [0x22, 0x06, 0x70, 0x01, 0x12, 0x02, 0x00, 0xee, 0xff];
+
+    #[test]
+    fn test_octo() {
+        assert_eq!(
+"    :call L206              # 200: 22 06
+: L202
+    v0 += 0x01              # 202: 70 01
+    jump L202               # 204: 12 02
+: L206
+    return                  # 206: 00 ee
+    0xff                    # 208: ff
+", disassemble(&ROM, Mode::Standard, Syntax::Octo));
+    }
+
+    #[test]
+    fn test_cowgod() {
+        assert_eq!(
+"    CALL L206               ; 200: 22 06
+L202:
+    ADD V0, 0x01            ; 202: 70 01
+    JP L202                 ; 204: 12 02
+L206:
+    RET                     ; 206: 00 ee
+    DB 0xff                 ; 208: ff
+", disassemble(&ROM, Mode::Standard, Syntax::Cowgod));
+    }
+
+    #[test]
+    fn test_xo_chip_only_in_xo_chip_mode() {
+        let rom = [0xf0, 0x00, 0x12, 0x34, 0x10, 0x00];
+        assert_eq!(
+"    i := long 0x1234        # 200: f0 00 12 34
+    jump 0x000              # 204: 10 00
+", disassemble(&rom, Mode::XoChip, Syntax::Octo));
+        assert_eq!(
+"    0xf0 0x00               # 200: f0 00
+    jump 0x234              # 202: 12 34
+    jump 0x000              # 204: 10 00
+", disassemble(&rom, Mode::Standard, Syntax::Octo));
+    }
+
+}
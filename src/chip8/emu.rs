@@ -1,19 +1,35 @@
-extern crate rand;
-
-use super::{GFX_H,GFX_W,Mode};
+use super::{GFX_H,GFX_W,NUM_PLANES,Mode,Plane};
+use super::instruction::Instruction;
+use super::quirks::{LoadStoreIdx, Quirks};
+use super::rng::Rng;
+use super::romdb;
+use super::timing::{self, Timing};
+use super::state;
+use super::watch::{Register, WatchAction, WatchHit, Watchpoint};
 use std::default::Default;
 use std::cmp;
+use std::error::Error;
+use std::fmt;
 use std::mem;
+use std::ops::Range;
 
 const SMALL_GFX_W: usize = 64;
 const SMALL_GFX_H: usize = 32;
 
 const MAX_ROM_SIZE: usize = RAM_SIZE - PROGRAM_START;
+const XO_CHIP_MAX_ROM_SIZE: usize = XO_CHIP_RAM_SIZE - PROGRAM_START;
 const NUM_REGISTERS: usize = 16;
-const PROGRAM_START: usize = 512; 
+pub const PROGRAM_START: usize = 512; 
 const RAM_SIZE: usize = 4096;
+pub const XO_CHIP_RAM_SIZE: usize = 65536;
 const STACK_SIZE: usize = 16;
 const NUM_SUPER_MODE_RPL_FLAGS: usize = 8;
+const NUM_XO_CHIP_RPL_FLAGS: usize = 16;
+const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;
+// The rate at which the timers count down. A frame lasts from one timer 
+// update to the next.
+pub const FRAMES_PER_SECOND: u64 = 60;
 
 const FONT_MAP: [u8; 5 * 16] = [
     0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
@@ -53,6 +69,45 @@ const SUPER_MODE_FONT_MAP: [u8; 10 * 16] = [
     0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xc0, 0xc0  // F
 ];
 
+// Errors that may occur while loading or running a rom. Any of these leaves
+// the emulator in an undefined state and it should be reset before resuming.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmuError {
+    // The opcode at pc is not part of the instruction set.
+    UnknownOpcode { opcode: u16, pc: u16 },
+    // A subroutine call at pc exceeded the supported levels of nesting.
+    StackOverflow { pc: u16 },
+    // A return at pc was executed without a matching subroutine call.
+    StackUnderflow { pc: u16 },
+    // The rom does not fit into the memory available for programs.
+    RomTooLarge { size: usize, max: usize },
+    // The instruction at pc tried to access memory beyond the end of ram.
+    OutOfBoundsMemoryAccess { addr: usize, pc: u16 },
+}
+
+impl fmt::Display for EmuError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmuError::UnknownOpcode { opcode, pc } => 
+                write!(f, "Unknown opcode {:04x} at {:03x}", opcode, pc),
+            EmuError::StackOverflow { pc } => 
+                write!(f, "Stack overflow at {:03x}", pc),
+            EmuError::StackUnderflow { pc } => 
+                write!(f, "Stack underflow at {:03x}", pc),
+            EmuError::RomTooLarge { size, max } => 
+                write!(f, "Program too large to fit into memory ({} bytes, \
+                           at most {} allowed)", size, max),
+            EmuError::OutOfBoundsMemoryAccess { addr, pc } => 
+                write!(f, "Out of bounds memory access to {:04x} at {:03x}", 
+                       addr, pc),
+        }
+    }
+}
+
+impl Error for EmuError {}
+
+#[derive(Serialize, Deserialize)]
 pub struct Emu {
     
     // Unlike a typical Intel processor, that uses little endian order for multi byte data types,
@@ -62,10 +117,15 @@ pub struct Emu {
     //
     // For more on endiannes, see http://www.geeksforgeeks.org/little-and-big-endian-mystery/
     
-    // Can run in one of two modes: STANDARD or SUPER.
+    // Can run in one of three modes: STANDARD, SUPER or XO-CHIP. The first
+    // two switch between each other, XO-CHIP is chosen up front.
     pub mode: Mode,
-    // Graphics pixel is either set or not. 
-    pub gfx: [[bool; GFX_H]; GFX_W], 
+    // Graphics pixel is either set or not, for each of the bitplanes. Only
+    // XO-CHIP makes use of the second bitplane.
+    #[serde(with = "state::gfx_serde")]
+    pub gfx: [Plane; NUM_PLANES], 
+    // Set when the audio pattern or pitch has changed.
+    pub sound_changed: bool,
     // Maps state of keypresses. True means the key has been pressed.
     pub keys: [bool; 16],
     // Set when the graphics state has changed and requires a redraw.
@@ -74,6 +134,7 @@ pub struct Emu {
     // each 2 bytes long. 
     opcode: u16,
     // There are 4,096 8-bit memory locations making for a total of 4KB RAM. 
+    // XO-CHIP extends this to 65,536 locations, or 64KB RAM.
     // +---------------------+= 0xfff=4095 
     // |                     |
     // |                     |
@@ -97,12 +158,14 @@ pub struct Emu {
     // |                     | 
     // +---------------------+= 0x000=0000 
     //
-    ram: [u8; RAM_SIZE],  
+    #[serde(with = "state::ram_serde")]
+    ram: Vec<u8>,  
     // There are 16 8-bit registers, referred to as v0 to vf: v0 to vE are
     // general purpose while vf stores the carry flag.
     v: [u8; NUM_REGISTERS],            
     // The special purpose 16-bit index register is used to a memory address.
-    // Only the lowest (rightmost) 12 bits are usually used.
+    // Only the lowest (rightmost) 12 bits are usually used, except in XO-CHIP
+    // mode.
     ram_idx: u16,                
     // The program counter is used to store the currently executing address.
     // a 'pseudo register' not directly accessible from programs.
@@ -122,8 +185,42 @@ pub struct Emu {
     // stack[sp-1] <-- top of the stack (where last entry pushed resides)
     // a 'pseudo register' not directly accessible from programs.
     sp: usize,
-    // Super mode flags used by opcodes fx75 and fx85.
-    super_mode_rpl_flags: [u8; NUM_SUPER_MODE_RPL_FLAGS],
+    // Super mode flags used by opcodes fx75 and fx85. XO-CHIP mode extends
+    // the number of flags from 8 to 16.
+    super_mode_rpl_flags: [u8; NUM_XO_CHIP_RPL_FLAGS],
+    // XO-CHIP mode only. Bitmask of the bitplanes that drawing, clearing
+    // and scrolling apply to.
+    planes: u8,
+    // XO-CHIP mode only. Whether the 128x64 high resolution is enabled.
+    hires: bool,
+    // XO-CHIP mode only. A pattern of 128 1-bit samples that is played 
+    // while the sound timer is active. None plays the standard beep.
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    // XO-CHIP mode only. Determines the playback rate of the audio pattern.
+    pitch: u8,
+    // Interpreter specific behaviours that some roms depend on.
+    quirks: Quirks,
+    // Set on every 60Hz timer update and cleared when a sprite is drawn.
+    // Used to make dxyn wait for the vertical blank when required.
+    vblank: bool,
+    // Source of random numbers for cxnn.
+    rng: Rng,
+    // How long instructions take to execute.
+    timing: Timing,
+    // Cycles executed and frames completed since the emulator started. Kept
+    // on reset, so that they keep counting up, which movies rely on to time
+    // their input.
+    cycles: u64,
+    frames: u64,
+    // The share of the current frame used up: cycles given fixed timing,
+    // machine cycles given COSMAC VIP timing.
+    frame_cycles: u64,
+    // Watched ram addresses and registers, along with what to do when hit.
+    #[serde(skip)]
+    watchpoints: Vec<(Watchpoint, WatchAction)>,
+    // Watchpoints hit since last taken.
+    #[serde(skip)]
+    watch_hits: Vec<WatchHit>,
     // We cache a copy of the rom to allow for convenient reset.
     rom: Vec<u8>
 }
@@ -134,67 +231,191 @@ impl Default for Emu {
         let mut emu = Emu {
             mode: Mode::Standard,
             opcode: 0,
-            ram: [0; RAM_SIZE],  
+            ram: vec![0; XO_CHIP_RAM_SIZE],  
             v: [0; NUM_REGISTERS],
             ram_idx: 0,                
             pc: PROGRAM_START as u16,                
-            gfx: [[false; GFX_H]; GFX_W],
+            gfx: [[[false; GFX_H]; GFX_W]; NUM_PLANES],
+            sound_changed: false,
             dt: 0,
             st: 0,
             stack: [0; STACK_SIZE], 
             sp: 0, 
             keys: [false; 16],
             draw: false,
-            super_mode_rpl_flags: [0; NUM_SUPER_MODE_RPL_FLAGS],
+            super_mode_rpl_flags: [0; NUM_XO_CHIP_RPL_FLAGS],
+            planes: 0b01,
+            hires: false,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            quirks: Quirks::default(),
+            vblank: false,
+            rng: Rng::from_entropy(),
+            timing: Timing::Fixed(timing::DEFAULT_CYCLES_PER_FRAME),
+            cycles: 0,
+            frames: 0,
+            frame_cycles: 0,
+            watchpoints: Vec::new(),
+            watch_hits: Vec::new(),
             rom: Vec::with_capacity(MAX_ROM_SIZE),
         };
-        let mut i = 0;
-        for j in 0..FONT_MAP.len() {
-            emu.ram[i] = FONT_MAP[j];
-            i += 1;
-        }
-        for k in 0..SUPER_MODE_FONT_MAP.len() {
-            emu.ram[i] = SUPER_MODE_FONT_MAP[k];
-            i += 1;
-        }
+        let super_font_start = FONT_MAP.len();
+        let super_font_end = super_font_start + SUPER_MODE_FONT_MAP.len();
+        emu.ram[..super_font_start].copy_from_slice(&FONT_MAP);
+        emu.ram[super_font_start..super_font_end]
+            .copy_from_slice(&SUPER_MODE_FONT_MAP);
         emu 
     }
 }
 
 impl Emu {
 
-    // Create emulator.
-    pub fn new() -> Self { 
-        Default::default() 
+    // Create emulator that follows the given quirks.
+    pub fn new(quirks: Quirks) -> Self { 
+        Emu { quirks, ..Default::default() }
+    }
+
+    // Create emulator that starts in the given mode and follows the given
+    // quirks. Only XO-CHIP needs to be chosen up front, SUPER mode is 
+    // enabled by the rom itself.
+    pub fn with_mode(mode: Mode, quirks: Quirks) -> Self { 
+        Emu { mode, ..Emu::new(quirks) }
     }
     
     // Load rom into emulator, but does not start execution. 
-    pub fn load_rom(&mut self, rom: Vec<u8>) {
-        if rom.len() > MAX_ROM_SIZE {
-            panic!("Program too large to fit into memory");
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), EmuError> {
+        let max = match self.mode {
+            Mode::XoChip => XO_CHIP_MAX_ROM_SIZE,
+            _ => MAX_ROM_SIZE,
+        };
+        if rom.len() > max {
+            return Err(EmuError::RomTooLarge { size: rom.len(), max });
         }
-        self.rom = rom;
-        for i in 0..self.rom.len() {
-            self.ram[PROGRAM_START+i] = self.rom[i];
-        }  
+        self.install_rom(rom);
+        Ok(())
     }
 
     // Reset the program to the initial rom state.
     pub fn reset(&mut self) {
-        let stale = mem::replace(self, Emu::new());
-        self.load_rom(stale.rom);
+        let quirks = self.quirks;
+        let mode = match self.mode {
+            Mode::XoChip => Mode::XoChip,
+            _ => Mode::Standard,
+        };
+        let stale = mem::replace(self, Emu::with_mode(mode, quirks));
+        self.watchpoints = stale.watchpoints;
+        // Start the same random sequence over, so a reset run plays out
+        // like the first.
+        self.rng = Rng::new(stale.rng.seed());
+        self.timing = stale.timing;
+        self.cycles = stale.cycles;
+        self.frames = stale.frames;
+        self.frame_cycles = stale.frame_cycles;
+        // The cached rom was checked to fit when it was first loaded.
+        self.install_rom(stale.rom);
     }
 
     // Perform a single fetch-decode-execute cycle.
-    pub fn execute_cycle(&mut self) {
-        self.fetch_opcode();
-        self.decode_and_execute_opcode();
+    pub fn execute_cycle(&mut self) -> Result<(), EmuError> {
+        self.fetch_opcode()?;
+        let result = if self.watchpoints.is_empty() {
+            self.decode_and_execute_opcode()
+        } else {
+            let (pc, v, ram_idx) = (self.pc, self.v, self.ram_idx);
+            let result = self.decode_and_execute_opcode();
+            self.watch_registers(pc, v, ram_idx);
+            result
+        };
+        if result.is_ok() { self.cycles += 1; }
+        result
+    }
+
+    // Perform a cycle, then update the timers if it completes a frame. 
+    // Timing the timers by the cycles executed, rather than by the wall 
+    // clock, makes runs reproducible. Returns true at the end of a frame.
+    //
+    // Given COSMAC VIP timing, each instruction takes up its share of the
+    // machine cycles in a frame instead. On the VIP, dxyn waits for the 
+    // vertical blank, so given the display_wait quirk a waiting dxyn takes
+    // up the rest of the frame.
+    pub fn step(&mut self) -> Result<bool, EmuError> {
+        let (pc, v) = (self.pc, self.v);
+        self.execute_cycle()?;
+        let budget = match self.timing {
+            Timing::Fixed(cycles_per_frame) => {
+                self.frame_cycles += 1;
+                cycles_per_frame
+            },
+            Timing::CosmacVip => {
+                let instruction = Instruction::decode(self.opcode, self.mode);
+                let vx = v[(self.opcode as usize & 0x0f00) >> 8];
+                match instruction {
+                    Some(Instruction::Draw(..)) if self.pc == pc =>
+                        self.frame_cycles = timing::VIP_INTERPRETER_CYCLES,
+                    _ => self.frame_cycles += timing::vip_cycles(
+                        instruction, vx, self.pc.wrapping_sub(pc) > 2),
+                }
+                timing::VIP_INTERPRETER_CYCLES
+            },
+        };
+        if self.frame_cycles < budget {
+            return Ok(false);
+        }
+        self.frame_cycles -= budget;
+        self.frames += 1;
+        self.update_timers();
+        Ok(true)
+    }
+
+    // Choose how long instructions take to execute.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    // Cycles executed since the emulator started.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Frames completed since the emulator started.
+    pub fn frame(&self) -> u64 {
+        self.frames
+    }
+
+    // Watch a ram address or register, replacing the action of the same 
+    // watchpoint if already set.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint, 
+                          action: WatchAction) {
+        self.remove_watchpoint(watchpoint);
+        self.watchpoints.push((watchpoint, action));
+    }
+
+    // Stop watching. Returns false if the watchpoint was not set.
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|&(wp, _)| wp != watchpoint);
+        self.watchpoints.len() != len
+    }
+
+    // The watchpoints set, in the order they were added.
+    pub fn watchpoints(&self) -> &[(Watchpoint, WatchAction)] {
+        &self.watchpoints
+    }
+
+    // The watchpoints hit since last called, in the order they were hit.
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        mem::take(&mut self.watch_hits)
     }
 
     // Update the delay and sound timers.
     pub fn update_timers(&mut self) {
         if self.dt > 0 { self.dt -= 1; }
         if self.st > 0 { self.st -= 1; }
+        self.vblank = true;
     }
 
     // Indicates whether the state justifies a beep at this
@@ -202,68 +423,271 @@ impl Emu {
     pub fn beeping(&self) -> bool {
         self.st > 0
     }
-    
-    // Return the gfx width.
-    fn width(&self) -> usize {
+
+    // Whether the 128x64 high resolution is in use, as opposed to 64x32.
+    pub fn hires(&self) -> bool {
         match self.mode {
-            Mode::Standard => SMALL_GFX_W,
-            Mode::Super => GFX_W
+            Mode::Standard => false,
+            Mode::Super => true,
+            Mode::XoChip => self.hires,
         }
     }
 
-    // Return the gfx height.
-    fn height(&self) -> usize {
+    // The program counter, pointing at the next instruction to execute.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    // The most recently fetched opcode.
+    pub fn opcode(&self) -> u16 {
+        self.opcode
+    }
+
+    // The general purpose registers v0 to vf.
+    pub fn v(&self) -> &[u8; NUM_REGISTERS] {
+        &self.v
+    }
+
+    // The index register.
+    pub fn ram_idx(&self) -> u16 {
+        self.ram_idx
+    }
+
+    // The delay timer.
+    pub fn dt(&self) -> u8 {
+        self.dt
+    }
+
+    // The sound timer.
+    pub fn st(&self) -> u8 {
+        self.st
+    }
+
+    // The return addresses of the subroutines, of which the first sp are
+    // in use.
+    pub fn stack(&self) -> &[u16; STACK_SIZE] {
+        &self.stack
+    }
+
+    // The stack pointer, or number of nested subroutine calls.
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    // The addressable memory for the current mode.
+    pub fn ram(&self) -> &[u8] {
+        &self.ram[..self.addr_mask() as usize + 1]
+    }
+
+    // Seed the random number generator, making runs reproducible.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    // SHA-1 hash of the loaded rom, in hex. Identifies the game.
+    pub fn rom_hash(&self) -> String {
+        romdb::rom_hash(&self.rom)
+    }
+
+    // Take over the state of a saved emulator, such as one restored from a
+    // save state. Watchpoints are kept, and the screen and sound refreshed.
+    pub fn restore(&mut self, saved: Emu) {
+        let watchpoints = mem::take(&mut self.watchpoints);
+        *self = saved;
+        self.watchpoints = watchpoints;
+        self.draw = true;
+        self.sound_changed = true;
+    }
+
+    // The XO-CHIP audio pattern, if any, and the pitch to play it at.
+    pub fn sound(&self) -> (Option<[u8; AUDIO_PATTERN_SIZE]>, u8) {
+        (self.audio_pattern, self.pitch)
+    }
+    
+    // Record hits on the watchpoints of the given kind for ram accessed by 
+    // the current instruction.
+    fn watch_ram(&mut self, kind: fn(u16) -> Watchpoint, range: Range<usize>) {
+        if self.watchpoints.is_empty() {
+            return;
+        }
+        for addr in range {
+            let watchpoint = kind(addr as u16);
+            let found = self.watchpoints.iter()
+                                        .find(|&&(wp, _)| wp == watchpoint);
+            if let Some(&(_, action)) = found {
+                self.watch_hits.push(WatchHit { 
+                    watchpoint, action, pc: self.pc, opcode: self.opcode, 
+                    value: self.ram[addr] as u16 
+                });
+            }
+        }
+    }
+
+    // Record hits on the register watchpoints, given the register values 
+    // from before the instruction at pc executed.
+    fn watch_registers(&mut self, pc: u16, v: [u8; NUM_REGISTERS], 
+                       ram_idx: u16) {
+        for &(watchpoint, action) in &self.watchpoints {
+            let (old, new, wanted) = match watchpoint {
+                Watchpoint::Register(Register::V(x), wanted) => 
+                    (v[x] as u16, self.v[x] as u16, wanted),
+                Watchpoint::Register(Register::I, wanted) => 
+                    (ram_idx, self.ram_idx, wanted),
+                _ => continue,
+            };
+            if old != new && wanted.is_none_or(|value| value == new) {
+                self.watch_hits.push(WatchHit { 
+                    watchpoint, action, pc, opcode: self.opcode, value: new
+                });
+            }
+        }
+    }
+
+    // Cache the rom and copy it into program memory.
+    fn install_rom(&mut self, rom: Vec<u8>) {
+        self.rom = rom;
+        let program_end = PROGRAM_START + self.rom.len();
+        self.ram[PROGRAM_START..program_end].copy_from_slice(&self.rom);
+    }
+
+    // Return the ram addresses start to start + len (exclusive), provided
+    // they are all within bounds.
+    fn ram_range(&self, start: usize, len: usize) 
+        -> Result<Range<usize>, EmuError> {
+        let ram_size = self.addr_mask() as usize + 1;
+        if start + len > ram_size {
+            return Err(EmuError::OutOfBoundsMemoryAccess { 
+                addr: cmp::max(start, ram_size), 
+                pc: self.pc 
+            });
+        }
+        Ok(start..start + len)
+    }
+
+    // Return the mask that keeps addresses within ram. 
+    fn addr_mask(&self) -> u16 {
         match self.mode {
-            Mode::Standard => SMALL_GFX_H,
-            Mode::Super => GFX_H
+            Mode::XoChip => (XO_CHIP_RAM_SIZE - 1) as u16,
+            _ => (RAM_SIZE - 1) as u16,
+        }
+    }
+
+    // Advance the program counter to the next instruction.
+    fn next_instruction(&mut self) {
+        self.pc = self.pc.wrapping_add(2) & self.addr_mask(); 
+    }
+
+    // Advance the program counter past the next instruction if cond holds,
+    // or to the next instruction otherwise. The XO-CHIP f000 nnnn 
+    // instruction is twice as long and skipped in its entirety.
+    fn skip_next_instruction_if(&mut self, cond: bool) {
+        self.next_instruction();
+        if cond {
+            let next = (self.ram[self.pc as usize] as u16) << 8 | 
+                self.ram[self.pc.wrapping_add(1) as usize] as u16;
+            let long = Instruction::decode(next, self.mode)
+                .is_some_and(|instruction| instruction.size() == 4);
+            self.next_instruction();
+            if long { self.next_instruction(); }
         }
     }
+
+    // Whether drawing, clearing and scrolling apply to bitplane p.
+    fn plane_selected(&self, p: usize) -> bool {
+        self.planes & (1 << p) != 0
+    }
+
+    // Return the gfx width.
+    pub fn width(&self) -> usize {
+        if self.hires() { GFX_W } else { SMALL_GFX_W }
+    }
+
+    // Return the gfx height.
+    pub fn height(&self) -> usize {
+        if self.hires() { GFX_H } else { SMALL_GFX_H }
+    }
    
     // Scroll screen n lines down.
     fn execute_opcode_00cn(&mut self) {
         let n = (self.opcode & 0x000f) as usize; 
-        for y in (n..GFX_H).rev() {
-            for x in 0..GFX_W { self.gfx[x][y] = self.gfx[x][y-n]; }
-        } 
-        for y in 0..n {
-            for x in 0..GFX_W { self.gfx[x][y] = false; }
-        } 
+        for p in 0..NUM_PLANES {
+            if !self.plane_selected(p) { continue; }
+            let gfx = &mut self.gfx[p];
+            for y in (n..GFX_H).rev() {
+                for col in gfx.iter_mut() { col[y] = col[y-n]; }
+            } 
+            for y in 0..n {
+                for col in gfx.iter_mut() { col[y] = false; }
+            } 
+        }
+        self.draw = true;
+        self.next_instruction();
+    }  
+
+    // Scroll screen n lines up. XO-CHIP mode only.
+    fn execute_opcode_00dn(&mut self) {
+        let n = (self.opcode & 0x000f) as usize; 
+        for p in 0..NUM_PLANES {
+            if !self.plane_selected(p) { continue; }
+            let gfx = &mut self.gfx[p];
+            for y in 0..(GFX_H - n) {
+                for col in gfx.iter_mut() { col[y] = col[y+n]; }
+            } 
+            for y in (GFX_H - n)..GFX_H {
+                for col in gfx.iter_mut() { col[y] = false; }
+            } 
+        }
         self.draw = true;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }  
     
     // Clear screen.
     fn execute_opcode_00e0(&mut self) {
-        for x in 0..GFX_W { for y in 0..GFX_H { self.gfx[x][y] = false; } }
+        for p in 0..NUM_PLANES {
+            if !self.plane_selected(p) { continue; }
+            self.gfx[p] = [[false; GFX_H]; GFX_W];
+        }
         self.draw = true;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }  
     
     // Return from last subroutine.
-    fn execute_opcode_00ee(&mut self) {
-        self.sp = (self.sp - 1) & (STACK_SIZE - 1); 
-        self.pc = self.stack[self.sp] as u16; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+    fn execute_opcode_00ee(&mut self) -> Result<(), EmuError> {
+        if self.sp == 0 {
+            return Err(EmuError::StackUnderflow { pc: self.pc });
+        }
+        self.sp -= 1; 
+        self.pc = self.stack[self.sp]; 
+        self.next_instruction();
+        Ok(())
     } 
 
     // Scroll screen 4 pixels right.
     fn execute_opcode_00fb(&mut self) {
-        for y in 0..GFX_H {
-            for x in (4..GFX_W).rev() { self.gfx[x][y] = self.gfx[x-4][y] }
-            for x in 0..4 { self.gfx[x][y] = false; }
+        for p in 0..NUM_PLANES {
+            if !self.plane_selected(p) { continue; }
+            let gfx = &mut self.gfx[p];
+            for x in (4..GFX_W).rev() { gfx[x] = gfx[x-4]; }
+            for col in gfx.iter_mut().take(4) { *col = [false; GFX_H]; }
         }
         self.draw = true;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Scroll screen 4 pixels left. 
     fn execute_opcode_00fc(&mut self) {
-        for y in 0..GFX_H {
-            for x in 0..(GFX_W - 4) { self.gfx[x][y] = self.gfx[x+4][y] }
-            for x in (GFX_W-4)..GFX_W { self.gfx[x][y] = false; }
+        for p in 0..NUM_PLANES {
+            if !self.plane_selected(p) { continue; }
+            let gfx = &mut self.gfx[p];
+            for x in 0..(GFX_W - 4) { gfx[x] = gfx[x+4]; }
+            for col in gfx.iter_mut().skip(GFX_W - 4) { *col = [false; GFX_H]; }
         }
         self.draw = true;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Meant to exit, but we will reset instead.
@@ -271,17 +695,30 @@ impl Emu {
         self.reset();
     } 
     
-    // Disable SUPER mode. 
+    // Disable SUPER mode. In XO-CHIP mode, switch to low resolution and 
+    // clear the screen instead.
     fn execute_opcode_00fe(&mut self) {
-        self.mode = Mode::Standard;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.set_hires(false);
+        self.next_instruction();
     } 
     
-    // Enable SUPER mode. 
+    // Enable SUPER mode. In XO-CHIP mode, switch to high resolution and 
+    // clear the screen instead.
     fn execute_opcode_00ff(&mut self) {
-        self.mode = Mode::Super;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.set_hires(true);
+        self.next_instruction();
     } 
+
+    fn set_hires(&mut self, hires: bool) {
+        match self.mode {
+            Mode::XoChip => {
+                self.hires = hires;
+                self.gfx = [[[false; GFX_H]; GFX_W]; NUM_PLANES];
+                self.draw = true;
+            },
+            _ => self.mode = if hires {Mode::Super} else {Mode::Standard},
+        }
+    }
     
     // Jump to address nnn.
     fn execute_opcode_1nnn(&mut self) {
@@ -290,32 +727,66 @@ impl Emu {
     }
 
     // Call subroutine at nnn.
-    fn execute_opcode_2nnn(&mut self) {
+    fn execute_opcode_2nnn(&mut self) -> Result<(), EmuError> {
+        if self.sp == STACK_SIZE {
+            return Err(EmuError::StackOverflow { pc: self.pc });
+        }
         let nnn = self.opcode & 0x0fff;
-        self.stack[self.sp] = self.pc as u16; 
-        self.sp = (self.sp + 1) & (STACK_SIZE - 1); 
+        self.stack[self.sp] = self.pc; 
+        self.sp += 1; 
         self.pc = nnn;
+        Ok(())
     }
 
     // Skip the next instruction if vx equals nn.
     fn execute_opcode_3xnn(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
         let nn = self.opcode & 0x00ff; 
-        self.pc += if self.v[x as usize] == nn as u8 {4} else {2}; 
+        self.skip_next_instruction_if(self.v[x as usize] == nn as u8);
     }
 
     // Skip the next instruction if vx does not equal nn.
     fn execute_opcode_4xnn(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
         let nn = self.opcode & 0x00ff; 
-        self.pc += if self.v[x as usize] != nn as u8 {4} else {2}; 
+        self.skip_next_instruction_if(self.v[x as usize] != nn as u8);
     }
 
     // Skip the next instruction if vx equals vy.
     fn execute_opcode_5xy0(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
         let y = (self.opcode & 0x00f0) >> 4; 
-        self.pc += if self.v[x as usize] == self.v[y as usize] {4} else {2};
+        self.skip_next_instruction_if(self.v[x as usize] == self.v[y as usize]);
+    }
+
+    // Save vx to vy (or vy down to vx when x > y) in memory starting at 
+    // address ram_idx. Does not change ram_idx. XO-CHIP mode only.
+    fn execute_opcode_5xy2(&mut self) -> Result<(), EmuError> {
+        let x = ((self.opcode & 0x0f00) >> 8) as usize; 
+        let y = ((self.opcode & 0x00f0) >> 4) as usize; 
+        let len = if x > y {x - y + 1} else {y - x + 1};
+        let dst = self.ram_range(self.ram_idx as usize, len)?;
+        for (i, addr) in dst.clone().enumerate() {
+            self.ram[addr] = self.v[if x > y {x - i} else {x + i}];
+        }
+        self.watch_ram(Watchpoint::Write, dst);
+        self.next_instruction();
+        Ok(())
+    }
+
+    // Load vx to vy (or vy down to vx when x > y) from memory starting at 
+    // address ram_idx. Does not change ram_idx. XO-CHIP mode only.
+    fn execute_opcode_5xy3(&mut self) -> Result<(), EmuError> {
+        let x = ((self.opcode & 0x0f00) >> 8) as usize; 
+        let y = ((self.opcode & 0x00f0) >> 4) as usize; 
+        let len = if x > y {x - y + 1} else {y - x + 1};
+        let src = self.ram_range(self.ram_idx as usize, len)?;
+        for (i, addr) in src.clone().enumerate() {
+            self.v[if x > y {x - i} else {x + i}] = self.ram[addr];
+        }
+        self.watch_ram(Watchpoint::Read, src);
+        self.next_instruction();
+        Ok(())
     }
 
     // Set vx to nn.
@@ -323,7 +794,7 @@ impl Emu {
         let x = (self.opcode & 0x0f00) >> 8; 
         let nn = self.opcode & 0x00ff; 
         self.v[x as usize] = nn as u8; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Add nn to vx.
@@ -331,7 +802,7 @@ impl Emu {
         let x = (self.opcode & 0x0f00) >> 8; 
         let nn = self.opcode & 0x00ff; 
         self.v[x as usize] = self.v[x as usize].wrapping_add(nn as u8);
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Set vx to the value of vy.
@@ -339,7 +810,7 @@ impl Emu {
         let x = (self.opcode & 0x0f00) >> 8; 
         let y = (self.opcode & 0x00f0) >> 4; 
         self.v[x as usize] = self.v[y as usize]; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Set vx to vx OR vy.
@@ -347,7 +818,8 @@ impl Emu {
         let x = (self.opcode & 0x0f00) >> 8; 
         let y = (self.opcode & 0x00f0) >> 4; 
         self.v[x as usize] |= self.v[y as usize]; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        if self.quirks.logic_resets_vf { self.v[0x0f] = 0; }
+        self.next_instruction();
     }
 
     // Set vx to vx AND vy.
@@ -355,7 +827,8 @@ impl Emu {
         let x = (self.opcode & 0x0f00) >> 8; 
         let y = (self.opcode & 0x00f0) >> 4; 
         self.v[x as usize] &= self.v[y as usize]; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        if self.quirks.logic_resets_vf { self.v[0x0f] = 0; }
+        self.next_instruction();
     }
 
     // Set vx to vx XOR vy.
@@ -363,7 +836,8 @@ impl Emu {
         let x = (self.opcode & 0x0f00) >> 8; 
         let y = (self.opcode & 0x00f0) >> 4; 
         self.v[x as usize] ^= self.v[y as usize]; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        if self.quirks.logic_resets_vf { self.v[0x0f] = 0; }
+        self.next_instruction();
     }
 
     // Add vy to vx and set vf to 1 if there was a carry, 0 otherwise. 
@@ -375,7 +849,7 @@ impl Emu {
         self.v[x as usize] = vx.wrapping_add(vy); 
         let carried = (vx as u16 + vy as u16) > 0xff;
         self.v[0x0f] = if carried {1} else {0}; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Subtract vy from vx. Set vf to 0 if there was a borrow, 1 otherwise.
@@ -387,42 +861,28 @@ impl Emu {
         self.v[x as usize] = vx.wrapping_sub(vy); 
         let borrowed = vy > vx;
         self.v[0x0f] = if borrowed {0} else {1}; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // There is some difference in opinion on how this opcode should
     // be implemented. See http://mattmik.com/emu.html
     //
-    // This implementation mirrors the behavior of the original interpreter.
-    //
-    // Store the value of register vy shifted right one bit in register vx.
-    // Set register vf to the least significant bit prior to the shift.
-    #[allow(dead_code)]
-    fn execute_opcode_8xy6_orig_not_used(&mut self) {
-        let x = (self.opcode & 0x0f00) >> 8; 
-        let y = (self.opcode & 0x00f0) >> 4; 
-        self.v[0x0f] = self.v[y as usize] & 0x01;
-        self.v[x as usize] = self.v[y as usize] >> 1; 
-        self.pc = (self.pc + 2) & 0x0fff; 
-    }
-
-    // There is some difference in opinion on how this opcode should
-    // be implemented. See http://mattmik.com/emu.html
+    // The original interpreter stores the value of register vy shifted right
+    // one bit in register vx. The most recent descriptions of the instruction
+    // set instead shift vx in place and ignore vy completely. The latter
+    // (perhaps erroneous) is what a majority of programmers had in mind and
+    // a significant number of the more complex roms, e.g. Space Invaders, 
+    // will ONLY work with it. The shift_uses_vy quirk selects between them.
     //
-    // This implementation follows the most recent descriptions of the 
-    // instruction set. This implementation (perhaps erroneous) were
-    // what a majority of programmers had in mind. As a result, it seems
-    // to work with a majority of roms. A significant number of the more
-    // complex roms, e.g. Space Invaders, will ONLY work with this 
-    // implementation.
-    //
-    // Shifts vx right by one. Set vf to the value of the least significant
-    // bit of vx before the shift. 
+    // Set vf to the value of the least significant bit before the shift.
     fn execute_opcode_8xy6(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
-        self.v[0x0f] = self.v[x as usize] & 0b00000001;
-        self.v[x as usize] >>= 1;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        let y = (self.opcode & 0x00f0) >> 4; 
+        let src = if self.quirks.shift_uses_vy {y} else {x};
+        let val = self.v[src as usize];
+        self.v[x as usize] = val >> 1;
+        self.v[0x0f] = val & 0b00000001;
+        self.next_instruction();
     }
 
     // Set vx to vy minus vx. Set vf to 0 if there was a borrow, 1 otherwise.
@@ -434,132 +894,148 @@ impl Emu {
         self.v[x as usize] = vy.wrapping_sub(vx); 
         let borrowed = vx > vy; 
         self.v[0x0f] = if borrowed {0} else {1}; 
-        self.pc = (self.pc + 2) & 0x0fff; 
-    }
-
-    // There is some difference in opinion on how this opcode should
-    // be implemented. See http://mattmik.com/emu.html
-    //
-    // This implementation mirrors the behavior of the original interpreter.
-    // 
-    // Store the value of register vy shifted left one bit in register vx.
-    // Set register vf to the most significant bit prior to the shift.
-    #[allow(dead_code)]
-    fn execute_opcode_8xye_orig_not_used(&mut self) {
-        let x = (self.opcode & 0x0f00) >> 8; 
-        let y = (self.opcode & 0x00f0) >> 4; 
-        self.v[0x0f] = (self.v[y as usize] >> 7) & 0b00000001;
-        self.v[x as usize] = self.v[y as usize] << 1; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // There is some difference in opinion on how this opcode should
-    // be implemented. See http://mattmik.com/emu.html
+    // be implemented. See the notes on execute_opcode_8xy6.
     //
-    // This implementation follows the most recent descriptions of the 
-    // instruction set. This implementation (perhaps erroneous) were
-    // what a majority of programmers had in mind. As a result, it seems
-    // to work with a majority of roms. A significant number of the more
-    // complex roms, e.g. Space Invaders, will ONLY work with this 
-    // implementation.
-    //
-    // Shift vx left by one. Set vf to the value of the most significant bit
-    // of vx before the shift. Notice that vy is completely ignored. 
+    // Shift vx (or vy, given the shift_uses_vy quirk) left by one and store
+    // the result in vx. Set vf to the value of the most significant bit
+    // before the shift.
     fn execute_opcode_8xye(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
-        self.v[0x0f] = (self.v[x as usize] >> 7) & 0b00000001; 
-        self.v[x as usize] <<= 1; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        let y = (self.opcode & 0x00f0) >> 4; 
+        let src = if self.quirks.shift_uses_vy {y} else {x};
+        let val = self.v[src as usize];
+        self.v[x as usize] = val << 1; 
+        self.v[0x0f] = (val >> 7) & 0b00000001; 
+        self.next_instruction();
     }
 
     // Skip the next instruction if vx does not equal vy.
     fn execute_opcode_9xy0(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
         let y = (self.opcode & 0x00f0) >> 4; 
-        self.pc += if self.v[x as usize] != self.v[y as usize] {4} else {2};
-        self.pc &= 0x0fff;
+        self.skip_next_instruction_if(self.v[x as usize] != self.v[y as usize]);
     }
 
     // Set ram_idx to the address nnn.
     fn execute_opcode_annn(&mut self) {
         let nnn = self.opcode & 0x0fff; 
         self.ram_idx = nnn; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     } 
 
-    // Jump to the address nnn plus v0.
+    // Jump to the address nnn plus v0. Given the jump_uses_vx quirk, this 
+    // becomes bxnn instead: jump to the address xnn plus vx.
     fn execute_opcode_bnnn(&mut self) {
         let nnn = self.opcode & 0x0fff; 
-        self.pc = (nnn + (self.v[0] as u16)) & 0x0fff; 
+        let x = if self.quirks.jump_uses_vx {(nnn & 0x0f00) >> 8} else {0};
+        self.pc = (nnn + (self.v[x as usize] as u16)) & self.addr_mask(); 
     } 
 
     // Set vx to a random number and nn.
     fn execute_opcode_cxnn(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
         let nn = self.opcode & 0x00ff; 
-        self.v[x as usize] = rand::random::<u8>() & (nn as u8); 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.v[x as usize] = self.rng.next_u8() & (nn as u8); 
+        self.next_instruction();
     }
 
     // Draw 8xn sprite from ram[ram_idx] at gfx[vx][vy]. Set vf to 1 if
     // any set pixels are changed to unset, and 0 otherwise. If n is 0 and
-    // in SUPER mode, then show 16x16 sprite instead.
-    fn execute_opcode_dxyn(&mut self) {
-        let gfx_start_x = self.v[(self.opcode as usize & 0x0f00) >> 8] as usize;
-        let gfx_start_y = self.v[(self.opcode as usize & 0x00f0) >> 4] as usize;
+    // in SUPER or XO-CHIP mode, then show 16x16 sprite instead. In XO-CHIP
+    // mode the sprite is drawn to each selected bitplane in turn, with the 
+    // data for each bitplane following that of the previous one.
+    //
+    // Given the display_wait quirk, the program counter is only advanced 
+    // once the vertical blank has been reached. In other words, this opcode
+    // will execute over and over until the next timer update.
+    fn execute_opcode_dxyn(&mut self) -> Result<(), EmuError> {
+        if self.quirks.display_wait && !self.vblank {
+            return Ok(());
+        }
+        self.vblank = false;
+        // The start position always wraps, the sprite itself may be clipped.
+        let gfx_start_x = 
+            self.v[(self.opcode as usize & 0x0f00) >> 8] as usize % self.width();
+        let gfx_start_y = 
+            self.v[(self.opcode as usize & 0x00f0) >> 4] as usize % self.height();
         let n = (self.opcode & 0x000f) as usize; 
-        let sprt_w = if n == 0 && self.mode == Mode::Super {16} else {8};
+        let sprt_w = if n == 0 && self.mode != Mode::Standard {16} else {8};
         let sprt_h = if n == 0 {16} else {n};
         let sprt_bytes_per_row = sprt_w / 8; 
+        let sprt_size = sprt_h * sprt_bytes_per_row;
+        let num_planes = (0..NUM_PLANES).filter(|&p| self.plane_selected(p))
+                                        .count();
+        let sprts = self.ram_range(self.ram_idx as usize, 
+                                   sprt_size * num_planes)?;
         self.v[0x0f] = 0x00;
-        for y_offset in 0..sprt_h {
-            for sprt_byte_col_idx in 0..sprt_bytes_per_row {
-                let sprt_byte_ram_idx = self.ram_idx as usize + 
-                    y_offset * sprt_bytes_per_row;
-                let sprt_byte: u8 = self.ram[sprt_byte_ram_idx]; 
-                for sprt_byte_bit_idx in 0..8 as usize {
-                    let x_offset = sprt_byte_col_idx * 8 + sprt_byte_bit_idx;
-                    // Drawing beyond max width and height will wrap.
-                    let gfx_x = (gfx_start_x + x_offset) % self.width();
-                    let gfx_y = (gfx_start_y + y_offset) % self.height(); 
-                    // Mask to obtain single bit from byte. 
-                    let mask = 0b_1000_0000_u8 >> sprt_byte_bit_idx; 
-                    let sprt_pix = sprt_byte & mask != 0;
-                    if sprt_pix == true {
-                        let gfx_pix = &mut self.gfx[gfx_x][gfx_y];
-                        *gfx_pix ^= true;
-                        if *gfx_pix == true {
-                            // Reduce flicker and draw only when pix switched on. 
-                            self.draw = true;
-                        } else {
-                            self.v[0x0f] = 0x01;
-                        } 
+        let mut sprt_start = sprts.start;
+        for p in 0..NUM_PLANES {
+            if !self.plane_selected(p) { continue; }
+            for y_offset in 0..sprt_h {
+                for sprt_byte_col_idx in 0..sprt_bytes_per_row {
+                    let sprt_byte_ram_idx = sprt_start + 
+                        y_offset * sprt_bytes_per_row + sprt_byte_col_idx;
+                    let sprt_byte: u8 = self.ram[sprt_byte_ram_idx]; 
+                    for sprt_byte_bit_idx in 0..8_usize {
+                        let x_offset = sprt_byte_col_idx * 8 + sprt_byte_bit_idx;
+                        let gfx_x = gfx_start_x + x_offset;
+                        let gfx_y = gfx_start_y + y_offset; 
+                        if self.quirks.clip_sprites && 
+                            (gfx_x >= self.width() || gfx_y >= self.height()) {
+                            continue;
+                        }
+                        // Drawing beyond max width and height will wrap.
+                        let gfx_x = gfx_x % self.width();
+                        let gfx_y = gfx_y % self.height(); 
+                        // Mask to obtain single bit from byte. 
+                        let mask = 0b_1000_0000_u8 >> sprt_byte_bit_idx; 
+                        let sprt_pix = sprt_byte & mask != 0;
+                        if sprt_pix {
+                            let gfx_pix = &mut self.gfx[p][gfx_x][gfx_y];
+                            *gfx_pix ^= true;
+                            if *gfx_pix {
+                                // Reduce flicker and draw only when pix 
+                                // switched on. 
+                                self.draw = true;
+                            } else {
+                                self.v[0x0f] = 0x01;
+                            } 
+                        }
                     }
-                }
-            } 
+                } 
+            }
+            sprt_start += sprt_size;
         }
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.watch_ram(Watchpoint::Read, sprts);
+        self.next_instruction();
+        Ok(())
     }
     
-    // Skip the next instruction if the key stored in vx is pressed.
+    // Skip the next instruction if the key stored in vx is pressed. Only the
+    // low nibble of vx counts, there being 16 keys.
     fn execute_opcode_ex9e(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
-        let key_pressed = self.keys[self.v[x as usize] as usize];
-        self.pc = (self.pc + if key_pressed {4} else {2}) & 0x0fff;
+        let key_pressed = self.keys[(self.v[x as usize] & 0xf) as usize];
+        self.skip_next_instruction_if(key_pressed);
     }
 
-    // Skips the next instruction if the key stored in vx is not pressed.
+    // Skips the next instruction if the key stored in vx is not pressed. As
+    // with ex9e, only the low nibble of vx counts.
     fn execute_opcode_exa1(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
-        let key_pressed = self.keys[self.v[x as usize] as usize];
-        self.pc = (self.pc + if !key_pressed {4} else {2}) & 0x0fff;
+        let key_pressed = self.keys[(self.v[x as usize] & 0xf) as usize];
+        self.skip_next_instruction_if(!key_pressed);
     }
 
     // Set vx to the value of the delay timer.
     fn execute_opcode_fx07(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8; 
         self.v[x as usize] = self.dt;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Wait for a keypress then store it in vx.
@@ -572,7 +1048,8 @@ impl Emu {
         for i in 0..self.keys.len() {
             if self.keys[i] {
                 self.v[x as usize] = i as u8;
-                self.pc = (self.pc + 2) & 0x0fff; 
+                self.next_instruction();
+                break;
             }
         }
     }
@@ -581,25 +1058,25 @@ impl Emu {
     fn execute_opcode_fx15(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8;
         self.dt = self.v[x as usize];
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Set the sound timer to vx.
     fn execute_opcode_fx18(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8;
         self.st = self.v[x as usize];
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
     // Add vx to ram_idx. Set vf to 1 if there was a range overflow,
-    // ram_idx + vx > 0x0fff, 0 otherwise.
+    // ram_idx + vx > 0x0fff (0xffff in XO-CHIP mode), 0 otherwise.
     fn execute_opcode_fx1e(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8;
-        let sum  = self.ram_idx + self.v[x as usize] as u16;
-        let overflowed = sum > 0x0fff;
-        self.v[0xf as usize] = if overflowed {1} else {0};
-        self.ram_idx = sum % (0x0fff + 1);
-        self.pc = (self.pc + 2) & 0x0fff; 
+        let sum = self.ram_idx as u32 + self.v[x as usize] as u32;
+        let overflowed = sum > self.addr_mask() as u32;
+        self.v[0x0f] = if overflowed {1} else {0};
+        self.ram_idx = sum as u16 & self.addr_mask();
+        self.next_instruction();
     }
 
     // Set ram_idx to the location of the sprite for the character in vx. 
@@ -607,8 +1084,8 @@ impl Emu {
     fn execute_opcode_fx29(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8;
         let fchar = self.v[x as usize];
-        self.ram_idx = 0x0000 + (fchar as u16) * 5; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.ram_idx = (fchar as u16) * 5; 
+        self.next_instruction();
     } 
 
     // Set ram_idx to the location of the sprite for the character in vx, where
@@ -617,8 +1094,8 @@ impl Emu {
     fn execute_opcode_fx30(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8;
         let fchar = self.v[x as usize];
-        self.ram_idx = 0x0000 + (fchar as u16) * 10; 
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.ram_idx = (fchar as u16) * 10; 
+        self.next_instruction();
     } 
 
     // Store the binary-coded decimal (BCD) representation of vx, with the
@@ -627,7 +1104,8 @@ impl Emu {
     // plus 2. In other words, take the decimal representation of vx, place 
     // the hundreds digit in memory at location in ram_idx, the tens digits 
     // at location ram_idx+1, and the ones digit at location ram_idx+2.
-    fn execute_opcode_fx33(&mut self) {
+    fn execute_opcode_fx33(&mut self) -> Result<(), EmuError> {
+        let bcd = self.ram_range(self.ram_idx as usize, 3)?;
         let x = (self.opcode & 0x0f00) >> 8;
         let mut vx = self.v[x as usize];
         let ones = vx % 10;
@@ -635,212 +1113,376 @@ impl Emu {
         let tens = vx % 10;
         vx /= 10;
         let hundreds = vx % 10;
-        self.ram[(self.ram_idx+0) as usize] = hundreds as u8;
-        self.ram[(self.ram_idx+1) as usize] = tens as u8;
-        self.ram[(self.ram_idx+2) as usize] = ones as u8;
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.ram[bcd.clone()].copy_from_slice(&[hundreds, tens, ones]);
+        self.watch_ram(Watchpoint::Write, bcd);
+        self.next_instruction();
+        Ok(())
+    }
+
+    // Move ram_idx on past v0 to vx, as stored or loaded, following the
+    // load_store_idx quirk.
+    fn advance_load_store_idx(&mut self, x: usize) {
+        let step = match self.quirks.load_store_idx {
+            LoadStoreIdx::Unchanged => return,
+            LoadStoreIdx::PlusX => x as u16,
+            LoadStoreIdx::PlusXPlusOne => x as u16 + 1,
+        };
+        self.ram_idx = self.ram_idx.wrapping_add(step) & self.addr_mask();
     }
 
-    // Store v0 to vx in memory starting at address ram_idx.
-    fn execute_opcode_fx55(&mut self) {
-        let x = (self.opcode & 0x0f00) >> 8;
-        for i in 0..(x as u16) + 1 {
-            self.ram[(self.ram_idx+i) as usize] = self.v[i as usize];
-        }
-        self.pc = (self.pc + 2) & 0x0fff; 
+    // Store v0 to vx in memory starting at address ram_idx. Where ram_idx
+    // is left depends on the load_store_idx quirk.
+    fn execute_opcode_fx55(&mut self) -> Result<(), EmuError> {
+        let x = ((self.opcode & 0x0f00) >> 8) as usize;
+        let dst = self.ram_range(self.ram_idx as usize, x + 1)?;
+        self.ram[dst.clone()].copy_from_slice(&self.v[..x + 1]);
+        self.watch_ram(Watchpoint::Write, dst);
+        self.advance_load_store_idx(x);
+        self.next_instruction();
+        Ok(())
     }
 
     // Fill v0 to vx with values from memory starting at address ram_idx.
-    fn execute_opcode_fx65(&mut self) {
-        let x = (self.opcode & 0x0f00) >> 8;
-        for i in 0..(x as u16) + 1 {
-            self.v[i as usize] = self.ram[(self.ram_idx+i) as usize];
-        }
-        self.pc = (self.pc + 2) & 0x0fff; 
-    }
-
-    // Store v0 to vx in super_mode_rpl_flags user flags (x <= 7).
+    // Where ram_idx is left depends on the load_store_idx quirk.
+    fn execute_opcode_fx65(&mut self) -> Result<(), EmuError> {
+        let x = ((self.opcode & 0x0f00) >> 8) as usize;
+        let src = self.ram_range(self.ram_idx as usize, x + 1)?;
+        self.v[..x + 1].copy_from_slice(&self.ram[src.clone()]);
+        self.watch_ram(Watchpoint::Read, src);
+        self.advance_load_store_idx(x);
+        self.next_instruction();
+        Ok(())
+    }
+
+    // Store v0 to vx in super_mode_rpl_flags user flags (x <= 7, or any x
+    // in XO-CHIP mode).
     fn execute_opcode_fx75(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8;
-        for i in 0..(cmp::min(x,7) as u16) + 1 {
+        for i in 0..cmp::min(x, self.max_rpl_flag()) + 1 {
             self.super_mode_rpl_flags[i as usize] = self.v[i as usize];
         }
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
     }
 
-    // Fill v0 to vx with values from super_mode_rpl_flags (x <= 7).
+    // Fill v0 to vx with values from super_mode_rpl_flags (x <= 7, or any x
+    // in XO-CHIP mode).
     fn execute_opcode_fx85(&mut self) {
         let x = (self.opcode & 0x0f00) >> 8;
-        for i in 0..(cmp::min(x,7) as u16) + 1 {
+        for i in 0..cmp::min(x, self.max_rpl_flag()) + 1 {
             self.v[i as usize] = self.super_mode_rpl_flags[i as usize];
         }
-        self.pc = (self.pc + 2) & 0x0fff; 
+        self.next_instruction();
+    }
+    
+    // The highest index of super_mode_rpl_flags available to fx75 and fx85.
+    fn max_rpl_flag(&self) -> u16 {
+        match self.mode {
+            Mode::XoChip => (NUM_XO_CHIP_RPL_FLAGS - 1) as u16,
+            _ => (NUM_SUPER_MODE_RPL_FLAGS - 1) as u16,
+        }
+    }
+
+    // Set ram_idx to the 16-bit address nnnn stored in the two bytes 
+    // following the opcode, then skip over them. XO-CHIP mode only.
+    fn execute_opcode_f000(&mut self) -> Result<(), EmuError> {
+        let next = self.pc.wrapping_add(2) & self.addr_mask();
+        let nnnn = self.ram_range(next as usize, 2)?;
+        let hbyte = self.ram[nnnn.start];
+        let lbyte = self.ram[nnnn.start + 1];
+        self.ram_idx = (hbyte as u16) << 8 | lbyte as u16; 
+        self.next_instruction();
+        self.next_instruction();
+        Ok(())
+    }
+
+    // Select the bitplanes n (a bitmask) that drawing, clearing and 
+    // scrolling apply to. XO-CHIP mode only.
+    fn execute_opcode_fn01(&mut self) {
+        let n = (self.opcode & 0x0f00) >> 8;
+        self.planes = (n & 0b11) as u8;
+        self.next_instruction();
+    }
+
+    // Load the 16 byte audio pattern from memory starting at address 
+    // ram_idx. XO-CHIP mode only.
+    fn execute_opcode_f002(&mut self) -> Result<(), EmuError> {
+        let src = self.ram_range(self.ram_idx as usize, AUDIO_PATTERN_SIZE)?;
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        pattern.copy_from_slice(&self.ram[src]);
+        self.audio_pattern = Some(pattern);
+        self.sound_changed = true;
+        self.next_instruction();
+        Ok(())
+    }
+
+    // Set the pitch of the audio pattern to vx. XO-CHIP mode only.
+    fn execute_opcode_fx3a(&mut self) {
+        let x = (self.opcode & 0x0f00) >> 8;
+        self.pitch = self.v[x as usize];
+        self.sound_changed = true;
+        self.next_instruction();
     }
     
     // Fetch the opcode to which the program counter is pointing.
-    fn fetch_opcode(&mut self) {
-        let hbyte = self.ram[self.pc as usize];
-        let lbyte = self.ram[self.pc as usize + 1];
+    fn fetch_opcode(&mut self) -> Result<(), EmuError> {
+        let addr = self.ram_range(self.pc as usize, 2)?;
+        let hbyte = self.ram[addr.start];
+        let lbyte = self.ram[addr.start + 1];
         // Uses big-endiannes for multi byte data types.
         self.opcode = (hbyte as u16) << 8 | lbyte as u16; 
+        Ok(())
     }
                 
-    fn decode_and_execute_opcode(&mut self) {
-        match self.opcode & 0xf000 {
-            0x0000 => 
-                match self.opcode & 0x00f0 {
-                    0x00c0 => self.execute_opcode_00cn(),
-                    _ =>  match self.opcode & 0x00ff {
-                        0x00e0 => self.execute_opcode_00e0(),
-                        0x00ee => self.execute_opcode_00ee(),
-                        0x00fb => self.execute_opcode_00fb(),
-                        0x00fc => self.execute_opcode_00fc(),
-                        0x00fd => self.execute_opcode_00fd(),
-                        0x00fe => self.execute_opcode_00fe(),
-                        0x00ff => self.execute_opcode_00ff(),
-                        _ => self.unknown_opcode()
-                }, 
-            }, 
-            0x1000 => self.execute_opcode_1nnn(), 
-            0x2000 => self.execute_opcode_2nnn(), 
-            0x3000 => self.execute_opcode_3xnn(), 
-            0x4000 => self.execute_opcode_4xnn(), 
-            0x5000 => match self.opcode & 0x000f {
-                0x0000 => self.execute_opcode_5xy0(),   
-                _ => self.unknown_opcode()
-            }, 
-            0x6000 => self.execute_opcode_6xnn(), 
-            0x7000 => self.execute_opcode_7xnn(), 
-            0x8000 => match self.opcode & 0x000f {
-                0x0000 => self.execute_opcode_8xy0(),
-                0x0001 => self.execute_opcode_8xy1(),
-                0x0002 => self.execute_opcode_8xy2(),
-                0x0003 => self.execute_opcode_8xy3(),
-                0x0004 => self.execute_opcode_8xy4(),
-                0x0005 => self.execute_opcode_8xy5(),
-                0x0006 => self.execute_opcode_8xy6(),
-                0x0007 => self.execute_opcode_8xy7(),
-                0x000e => self.execute_opcode_8xye(),
-                _ => self.unknown_opcode()
-            }, 
-            0x9000 => self.execute_opcode_9xy0(), 
-            0xa000 => self.execute_opcode_annn(), 
-            0xb000 => self.execute_opcode_bnnn(), 
-            0xc000 => self.execute_opcode_cxnn(), 
-            0xd000 => self.execute_opcode_dxyn(), 
-            0xe000 => match self.opcode & 0x000f {
-                0x000E => self.execute_opcode_ex9e(),
-                0x0001 => self.execute_opcode_exa1(),
-                _ => self.unknown_opcode()
-            }, 
-            0xf000 => match self.opcode & 0x00ff {
-               0x0007 => self.execute_opcode_fx07(),
-               0x000a => self.execute_opcode_fx0a(),
-               0x0015 => self.execute_opcode_fx15(),
-               0x0018 => self.execute_opcode_fx18(),
-               0x001e => self.execute_opcode_fx1e(),
-               0x0029 => self.execute_opcode_fx29(),
-               0x0030 => self.execute_opcode_fx30(),
-               0x0033 => self.execute_opcode_fx33(),
-               0x0055 => self.execute_opcode_fx55(),
-               0x0065 => self.execute_opcode_fx65(),
-               0x0075 => self.execute_opcode_fx75(),
-               0x0085 => self.execute_opcode_fx85(),
-               _ => self.unknown_opcode()
-            },
-            _ => self.unknown_opcode()
+    // Execute the opcode as decoded by Instruction::decode, the same decoder
+    // the disassembler uses.
+    fn decode_and_execute_opcode(&mut self) -> Result<(), EmuError> {
+        let instruction = match Instruction::decode(self.opcode, self.mode) {
+            Some(instruction) => instruction,
+            None => return Err(self.unknown_opcode()),
+        };
+        match instruction {
+            Instruction::ScrollDown(_) => self.execute_opcode_00cn(),
+            Instruction::ScrollUp(_) => self.execute_opcode_00dn(),
+            Instruction::Clear => self.execute_opcode_00e0(),
+            Instruction::Return => self.execute_opcode_00ee()?,
+            Instruction::ScrollRight => self.execute_opcode_00fb(),
+            Instruction::ScrollLeft => self.execute_opcode_00fc(),
+            Instruction::Exit => self.execute_opcode_00fd(),
+            Instruction::Lores => self.execute_opcode_00fe(),
+            Instruction::Hires => self.execute_opcode_00ff(),
+            Instruction::Jump(_) => self.execute_opcode_1nnn(), 
+            Instruction::Call(_) => self.execute_opcode_2nnn()?, 
+            Instruction::SkipIfEqual(..) => self.execute_opcode_3xnn(), 
+            Instruction::SkipIfNotEqual(..) => self.execute_opcode_4xnn(), 
+            Instruction::SkipIfRegEqual(..) => self.execute_opcode_5xy0(),   
+            Instruction::SaveRange(..) => self.execute_opcode_5xy2()?,
+            Instruction::LoadRange(..) => self.execute_opcode_5xy3()?,
+            Instruction::Set(..) => self.execute_opcode_6xnn(), 
+            Instruction::Add(..) => self.execute_opcode_7xnn(), 
+            Instruction::SetReg(..) => self.execute_opcode_8xy0(),
+            Instruction::Or(..) => self.execute_opcode_8xy1(),
+            Instruction::And(..) => self.execute_opcode_8xy2(),
+            Instruction::Xor(..) => self.execute_opcode_8xy3(),
+            Instruction::AddReg(..) => self.execute_opcode_8xy4(),
+            Instruction::Sub(..) => self.execute_opcode_8xy5(),
+            Instruction::ShiftRight(..) => self.execute_opcode_8xy6(),
+            Instruction::SubReversed(..) => self.execute_opcode_8xy7(),
+            Instruction::ShiftLeft(..) => self.execute_opcode_8xye(),
+            Instruction::SkipIfRegNotEqual(..) => self.execute_opcode_9xy0(), 
+            Instruction::SetIndex(_) => self.execute_opcode_annn(), 
+            Instruction::JumpOffset(_) => self.execute_opcode_bnnn(), 
+            Instruction::Random(..) => self.execute_opcode_cxnn(), 
+            Instruction::Draw(..) => self.execute_opcode_dxyn()?, 
+            Instruction::SkipIfKey(_) => self.execute_opcode_ex9e(),
+            Instruction::SkipIfNotKey(_) => self.execute_opcode_exa1(),
+            Instruction::SetIndexLong => self.execute_opcode_f000()?,
+            Instruction::Planes(_) => self.execute_opcode_fn01(),
+            Instruction::Audio => self.execute_opcode_f002()?,
+            Instruction::GetDelay(_) => self.execute_opcode_fx07(),
+            Instruction::WaitKey(_) => self.execute_opcode_fx0a(),
+            Instruction::SetDelay(_) => self.execute_opcode_fx15(),
+            Instruction::SetSound(_) => self.execute_opcode_fx18(),
+            Instruction::AddIndex(_) => self.execute_opcode_fx1e(),
+            Instruction::Font(_) => self.execute_opcode_fx29(),
+            Instruction::BigFont(_) => self.execute_opcode_fx30(),
+            Instruction::Bcd(_) => self.execute_opcode_fx33()?,
+            Instruction::Pitch(_) => self.execute_opcode_fx3a(),
+            Instruction::Store(_) => self.execute_opcode_fx55()?,
+            Instruction::Load(_) => self.execute_opcode_fx65()?,
+            Instruction::SaveFlags(_) => self.execute_opcode_fx75(),
+            Instruction::LoadFlags(_) => self.execute_opcode_fx85(),
         }
+        Ok(())
     }
     
-    fn unknown_opcode(&self) -> ! {
-        panic!("Unknown opcode: {:x}", self.opcode);    
+    fn unknown_opcode(&self) -> EmuError {
+        EmuError::UnknownOpcode { opcode: self.opcode, pc: self.pc }
     }
 
 }
 
 #[cfg(test)]
+#[allow(non_snake_case, clippy::bool_assert_comparison, clippy::identity_op,
+        clippy::unnecessary_cast)]
 mod tests {
 
-    use super::Emu;
+    use super::{Emu, EmuError};
+    use super::{MAX_ROM_SIZE, RAM_SIZE, STACK_SIZE, XO_CHIP_MAX_ROM_SIZE};
+    use super::super::quirks::{LoadStoreIdx, Quirks};
+    use super::super::timing::Timing;
+    use super::super::watch::{Register, WatchAction, WatchHit, Watchpoint};
     use super::{SMALL_GFX_H, SMALL_GFX_W};
     use super::super::{Mode, GFX_H, GFX_W};
 
+    #[test]
+    fn test_load_rom_too_large() {
+        let mut emu = Emu::new(Quirks::default());
+        //when
+        let result = emu.load_rom(vec![0; MAX_ROM_SIZE + 1]);
+        //then
+        assert_eq!(Err(EmuError::RomTooLarge { 
+            size: MAX_ROM_SIZE + 1, 
+            max: MAX_ROM_SIZE 
+        }), result);
+    }
+
+    #[test]
+    fn test_reset_restores_rom() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.load_rom(vec![0x12, 0x34]).unwrap();
+        emu.ram[0x200] = 0x00;
+        emu.pc = 0x0aaa;
+        //when
+        emu.reset();
+        //then
+        assert_eq!(0x12, emu.ram[0x200]);
+        assert_eq!(0x34, emu.ram[0x201]);
+        assert_eq!(0x200, emu.pc);
+    }
+
+    #[test]
+    fn test_execute_cycle_fetch_out_of_bounds() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0fff;
+        //when
+        let result = emu.execute_cycle();
+        //then
+        assert_eq!(Err(EmuError::OutOfBoundsMemoryAccess { 
+            addr: RAM_SIZE, 
+            pc: 0x0fff 
+        }), result);
+    }
+
+    #[test]
+    fn test_watch_ram_read_by_dxyn() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        emu.load_rom(vec![0xd0, 0x12]).unwrap();
+        emu.add_watchpoint(Watchpoint::Read(0x301), WatchAction::Log);
+        //when
+        emu.reset();
+        emu.ram_idx = 0x300;
+        emu.ram[0x301] = 0x81;
+        emu.execute_cycle().unwrap();
+        //then
+        assert_eq!(vec![WatchHit { 
+            watchpoint: Watchpoint::Read(0x301), action: WatchAction::Log,
+            pc: 0x200, opcode: 0xd012, value: 0x81 
+        }], emu.take_watch_hits());
+        assert!(emu.take_watch_hits().is_empty());
+    }
+
+    #[test]
+    fn test_watch_register_value() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        emu.load_rom(vec![0x70, 0x01, 0x70, 0x01]).unwrap();
+        let watchpoint = Watchpoint::Register(Register::V(0), Some(2));
+        emu.add_watchpoint(watchpoint, WatchAction::Halt);
+        //when
+        emu.execute_cycle().unwrap();
+        let first = emu.take_watch_hits();
+        emu.execute_cycle().unwrap();
+        let second = emu.take_watch_hits();
+        //then
+        assert!(first.is_empty());
+        assert_eq!(1, second.len());
+        assert_eq!(0x202, second[0].pc);
+        assert!(emu.remove_watchpoint(watchpoint));
+        assert!(!emu.remove_watchpoint(watchpoint));
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0aaa;
+        //when
+        emu.opcode = 0x5ab1;
+        let result = emu.decode_and_execute_opcode();
+        //then
+        assert_eq!(Err(EmuError::UnknownOpcode { opcode: 0x5ab1, pc: 0x0aaa }),
+                   result);
+    }
+
     #[test]
     pub fn test_opcode_00cn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
-        emu.gfx[0][0] = true;
-        emu.gfx[1][0] = true;
+        emu.gfx[0][0][0] = true;
+        emu.gfx[0][1][0] = true;
         //when
         emu.opcode = 0x00c2;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(false, emu.gfx[0][0]);
-        assert_eq!(false, emu.gfx[1][0]);
-        assert_eq!(true, emu.gfx[0][2]);
-        assert_eq!(true, emu.gfx[1][2]);
+        assert_eq!(false, emu.gfx[0][0][0]);
+        assert_eq!(false, emu.gfx[0][1][0]);
+        assert_eq!(true, emu.gfx[0][0][2]);
+        assert_eq!(true, emu.gfx[0][1][2]);
         assert_eq!(true, emu.draw);
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_00e0() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
         emu.draw = false;
-        for x in 0..GFX_W { for y in 0..GFX_H { emu.gfx[x][y] = true; } }
+        for x in 0..GFX_W { for y in 0..GFX_H { emu.gfx[0][x][y] = true; } }
         //when
         emu.opcode = 0x00e0;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        for x in 0..GFX_W { for y in 0..GFX_H { assert_eq!(false, emu.gfx[x][y]); } }
+        for x in 0..GFX_W { for y in 0..GFX_H { assert_eq!(false, emu.gfx[0][x][y]); } }
         assert_eq!(true, emu.draw);
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_00fb() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
-        emu.gfx[0][0] = true;
-        emu.gfx[1][0] = true;
+        emu.gfx[0][0][0] = true;
+        emu.gfx[0][1][0] = true;
         //when
         emu.opcode = 0x00fb;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(false, emu.gfx[0][0]);
-        assert_eq!(false, emu.gfx[1][0]);
-        assert_eq!(true, emu.gfx[4][0]);
-        assert_eq!(true, emu.gfx[5][0]);
+        assert_eq!(false, emu.gfx[0][0][0]);
+        assert_eq!(false, emu.gfx[0][1][0]);
+        assert_eq!(true, emu.gfx[0][4][0]);
+        assert_eq!(true, emu.gfx[0][5][0]);
         assert_eq!(true, emu.draw);
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_00fc() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
-        emu.gfx[4][0] = true;
-        emu.gfx[5][0] = true;
+        emu.gfx[0][4][0] = true;
+        emu.gfx[0][5][0] = true;
         //when
         emu.opcode = 0x00fc;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(false, emu.gfx[4][0]);
-        assert_eq!(false, emu.gfx[5][0]);
-        assert_eq!(true, emu.gfx[0][0]);
-        assert_eq!(true, emu.gfx[1][0]);
+        assert_eq!(false, emu.gfx[0][4][0]);
+        assert_eq!(false, emu.gfx[0][5][0]);
+        assert_eq!(true, emu.gfx[0][0][0]);
+        assert_eq!(true, emu.gfx[0][1][0]);
         assert_eq!(true, emu.draw);
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_00ee() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0ccc; 
         emu.stack[0] = 0x0aaa;
@@ -848,21 +1490,35 @@ mod tests {
         emu.sp = 0x01;
         //when
         emu.opcode = 0x00ee;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x00, emu.sp);
         assert_eq!(0x0aaa+2, emu.pc);
     }
 
+    #[test]
+    fn test_opcode_00ee_stack_underflow() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0ccc; 
+        emu.sp = 0x00;
+        //when
+        emu.opcode = 0x00ee;
+        let result = emu.decode_and_execute_opcode();
+        //then
+        assert_eq!(Err(EmuError::StackUnderflow { pc: 0x0ccc }), result);
+        assert_eq!(0x00, emu.sp);
+    }
+
     #[test]
     pub fn test_opcode_00fe() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0aaa; 
         emu.mode = Mode::Super;
         //when
         emu.opcode = 0x00fe;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(Mode::Standard, emu.mode);
         assert_eq!(0x0aaa+2, emu.pc);
@@ -870,13 +1526,13 @@ mod tests {
 
     #[test]
     pub fn test_opcode_00ff() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0aaa; 
         emu.mode = Mode::Standard;
         //when
         emu.opcode = 0x00ff;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(Mode::Super, emu.mode);
         assert_eq!(0x0aaa+2, emu.pc);
@@ -884,117 +1540,145 @@ mod tests {
 
     #[test]
     pub fn test_opcode_1nnn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0aaa; 
         //when
         emu.opcode = 0x1bcd;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0bcd, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_2nnn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
         //when
         emu.opcode = 0x1234;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0234, emu.pc);
     }
 
+    #[test]
+    fn test_opcode_2nnn_call() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0aaa; 
+        //when
+        emu.opcode = 0x2234;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x0234, emu.pc);
+        assert_eq!(0x0aaa, emu.stack[0]);
+        assert_eq!(0x01, emu.sp);
+    }
+
+    #[test]
+    fn test_opcode_2nnn_stack_overflow() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0aaa; 
+        emu.sp = STACK_SIZE;
+        //when
+        emu.opcode = 0x2234;
+        let result = emu.decode_and_execute_opcode();
+        //then
+        assert_eq!(Err(EmuError::StackOverflow { pc: 0x0aaa }), result);
+        assert_eq!(0x0aaa, emu.pc);
+    }
+
     #[test]
     pub fn test_opcode_3xnn_given_vx_equals_nn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         //when
         emu.opcode = 0x3a23;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+4, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_3xnn_given_vx_not_equals_nn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         //when
         emu.opcode = 0x3a24;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_4xnn_given_vx_equals_nn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         //when
         emu.opcode = 0x4a23;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_4xnn_given_vx_not_equals_nn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         //when
         emu.opcode = 0x4a24;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+4, emu.pc);
     }
     
     #[test]
     pub fn test_opcode_5xy0_given_vx_equals_vy() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         emu.v[0x0b] = 0x23;
         //when
         emu.opcode = 0x5ab0;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+4, emu.pc);
     }
 
     #[test]
     pub fn test_opcode_5xy0_given_vx_does_not_equal_vy() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         emu.v[0x0b] = 0x24;
         //when
         emu.opcode = 0x5ab0;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+2, emu.pc);
     }
     
     #[test]
     fn test_opcode_6xnn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         //when
         emu.opcode = 0x6a24;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0024, emu.v[0x0a]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1002,13 +1686,13 @@ mod tests {
 
     #[test]
     fn test_opcode_7xnn_without_overflow() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x03;
         //when
         emu.opcode = 0x7afb;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0xfe, emu.v[0x0a]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1016,13 +1700,13 @@ mod tests {
 
     #[test]
     fn test_opcode_7xnn_with_overflow() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x03;
         //when
         emu.opcode = 0x7aff;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         let wrap_mod = (0x0003u16 + 0x00ffu16) % (0x00ffu16 + 0x00001u16);
         assert_eq!(wrap_mod, (emu.v[0x0a] as u16));
@@ -1031,14 +1715,14 @@ mod tests {
     
     #[test]
     fn test_opcode_8xy0() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         emu.v[0x0b] = 0x24;
         //when
         emu.opcode = 0x8ab0;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x24, emu.v[0x0a]);
         assert_eq!(0x24, emu.v[0x0b]);
@@ -1047,14 +1731,14 @@ mod tests {
     
     #[test]
     fn test_opcode_8xy1() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         emu.v[0x0b] = 0x24;
         //when
         emu.opcode = 0x8ab1;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x23|0x24, emu.v[0x0a]);
         assert_eq!(0x24, emu.v[0x0b]);
@@ -1063,14 +1747,14 @@ mod tests {
     
     #[test]
     fn test_opcode_8xy2() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         emu.v[0x0b] = 0x24;
         //when
         emu.opcode = 0x8ab2;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x23&0x24, emu.v[0x0a]);
         assert_eq!(0x24, emu.v[0x0b]);
@@ -1079,14 +1763,14 @@ mod tests {
 
     #[test]
     fn test_opcode_8xy3() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x23;
         emu.v[0x0b] = 0x24;
         //when
         emu.opcode = 0x8ab3;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x23^0x24, emu.v[0x0a]);
         assert_eq!(0x24, emu.v[0x0b]);
@@ -1095,14 +1779,14 @@ mod tests {
     
     #[test]
     fn test_opcode_8xy4_without_carry() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0xf0;
         emu.v[0x0b] = 0x03;
         //when
         emu.opcode = 0x8ab4;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0xf3, 0xf0 + 0x03);
         assert_eq!(0xf3, emu.v[0x0a]);
@@ -1113,14 +1797,14 @@ mod tests {
 
     #[test]
     fn test_opcode_8xy4_with_carry() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0xff;
         emu.v[0x0b] = 0x03;
         //when
         emu.opcode = 0x8ab4;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         let wrap_mod = (0x00ffu16 + 0x0003u16) % (0x00ffu16 + 0x00001u16);
         assert_eq!(0x02u16, wrap_mod);
@@ -1132,14 +1816,14 @@ mod tests {
     
     #[test]
     fn test_opcode_8xy5_without_borrow() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x09;
         emu.v[0x0b] = 0x08;
         //when
         emu.opcode = 0x8ab5;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x01, 0x09 - 0x08);
         assert_eq!(0x01, emu.v[0x0a]);
@@ -1150,14 +1834,14 @@ mod tests {
 
     #[test]
     fn test_opcode_8xy5_with_borrow() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x08;
         emu.v[0x0b] = 0x09;
         //when
         emu.opcode = 0x8ab5;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0xff, emu.v[0x0a]);
         assert_eq!(0x09, emu.v[0x0b]);
@@ -1166,15 +1850,16 @@ mod tests {
     }
 
     #[test]
-    fn test_opcode_8xy6_orig_not_used_least_significant_bit_not_set() {
-        let mut emu = Emu::new();
+    fn test_opcode_8xy6_shift_uses_vy_least_significant_bit_not_set() {
+        let mut emu = Emu::new(
+            Quirks { shift_uses_vy: true, ..Quirks::default() });
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x07;
         emu.v[0x0b] = 0x04;
         //when
         emu.opcode = 0x8ab6;
-        emu.execute_opcode_8xy6_orig_not_used();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x02, 0x04 >> 1);
         assert_eq!(0x02, emu.v[0x0a]);
@@ -1185,15 +1870,16 @@ mod tests {
     }
 
     #[test]
-    fn test_opcode_8xy6_orig_not_used_least_significant_bit_set() {
-        let mut emu = Emu::new();
+    fn test_opcode_8xy6_shift_uses_vy_least_significant_bit_set() {
+        let mut emu = Emu::new(
+            Quirks { shift_uses_vy: true, ..Quirks::default() });
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x04;
         emu.v[0x0b] = 0x05;
         //when
         emu.opcode = 0x8ab6;
-        emu.execute_opcode_8xy6_orig_not_used();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x02, 0x05 >> 1);
         assert_eq!(0x02, emu.v[0x0a]);
@@ -1205,14 +1891,14 @@ mod tests {
     
     #[test]
     fn test_opcode_8xy6_least_significant_bit_not_set() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x04;
         emu.v[0x0b] = 0x07;
         //when
         emu.opcode = 0x8ab6;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x02, 0x04 >> 1);
         assert_eq!(0x02, emu.v[0x0a]);
@@ -1224,14 +1910,14 @@ mod tests {
 
     #[test]
     fn test_opcode_8xy6_least_significant_bit_set() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x05;
         emu.v[0x0b] = 0x04;
         //when
         emu.opcode = 0x8ab6;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x02, 0x05 >> 1);
         assert_eq!(0x02, emu.v[0x0a]);
@@ -1243,14 +1929,14 @@ mod tests {
     
     #[test]
     fn test_opcode_8xy7_without_borrow() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x08;
         emu.v[0x0b] = 0x09;
         //when
         emu.opcode = 0x8ab7;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x01, 0x09 - 0x08);
         assert_eq!(0x01, emu.v[0x0a]);
@@ -1261,14 +1947,14 @@ mod tests {
 
     #[test]
     fn test_opcode_8xy7_with_borrow() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x09;
         emu.v[0x0b] = 0x08;
         //when
         emu.opcode = 0x8ab7;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0xff, emu.v[0x0a]);
         assert_eq!(0x08, emu.v[0x0b]);
@@ -1278,14 +1964,14 @@ mod tests {
 
     #[test]
     fn test_opcode_8xye_most_significant_bit_not_set() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0b01111111_u8;
         emu.v[0x0b] = 0b11111111_u8;
         //when
         emu.opcode = 0x8abe;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0b11111110_u8, emu.v[0x0a]);
         assert_eq!(0x00, emu.v[0x0f]);
@@ -1294,14 +1980,14 @@ mod tests {
 
     #[test]
     fn test_opcode_8xye_most_significant_bit_set() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0b11111111_u8;
         emu.v[0x0b] = 0b01111111_u8;
         //when
         emu.opcode = 0x8abe;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0b11111110_u8, emu.v[0x0a]);
         assert_eq!(0x01, emu.v[0x0f]);
@@ -1309,15 +1995,16 @@ mod tests {
    }
 
     #[test]
-    fn test_opcode_8xye_orig_not_used_most_significant_bit_not_set() {
-        let mut emu = Emu::new();
+    fn test_opcode_8xye_shift_uses_vy_most_significant_bit_not_set() {
+        let mut emu = Emu::new(
+            Quirks { shift_uses_vy: true, ..Quirks::default() });
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0b11111111_u8;
         emu.v[0x0b] = 0b01111111_u8;
         //when
         emu.opcode = 0x8abe;
-        emu.execute_opcode_8xye_orig_not_used();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0b11111110_u8, emu.v[0x0a]);
         assert_eq!(0x00, emu.v[0x0f]);
@@ -1325,58 +2012,91 @@ mod tests {
    }
 
     #[test]
-    fn test_opcode_8xye_orig_not_used_most_significant_bit_set() {
-        let mut emu = Emu::new();
+    fn test_opcode_8xye_shift_uses_vy_most_significant_bit_set() {
+        let mut emu = Emu::new(
+            Quirks { shift_uses_vy: true, ..Quirks::default() });
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0b01111111_u8;
         emu.v[0x0b] = 0b11111111_u8;
         //when
         emu.opcode = 0x8abe;
-        emu.execute_opcode_8xye_orig_not_used();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0b11111110_u8, emu.v[0x0a]);
         assert_eq!(0x01, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
    }
 
+    #[test]
+    fn test_opcode_8xy1_logic_resets_vf() {
+        let mut emu = Emu::new(
+            Quirks { logic_resets_vf: true, ..Quirks::default() });
+        //given
+        emu.pc = 0x0000;
+        emu.v[0x0a] = 0x23;
+        emu.v[0x0b] = 0x24;
+        emu.v[0x0f] = 0x01;
+        //when
+        emu.opcode = 0x8ab1;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x23|0x24, emu.v[0x0a]);
+        assert_eq!(0x00, emu.v[0x0f]);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_opcode_8xy6_vf_wins_over_result() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0000;
+        emu.v[0x0f] = 0x05;
+        //when
+        emu.opcode = 0x8f06;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x01, emu.v[0x0f]);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
     #[test]
     fn test_opcode_9xy0_vx_does_not_match_vy() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x07;
         emu.v[0x0b] = 0x05;
         //when
         emu.opcode = 0x9ab0;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+4, emu.pc);
     }
 
     #[test]
     fn test_opcode_9xy0_vx_matches_vy() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x0a] = 0x07;
         emu.v[0x0b] = 0x07;
         //when
         emu.opcode = 0x9ab0;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     fn test_opcode_annn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.ram_idx = 0xacc;
         //when
         emu.opcode = 0xadef;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0def, emu.ram_idx);
         assert_eq!(0x0000+2, emu.pc);
@@ -1384,20 +2104,122 @@ mod tests {
 
     #[test]
     fn test_opcode_bnnn() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0] = 0x23;
         //when
         emu.opcode = 0xb345;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0368, emu.pc);
     }
 
+    #[test]
+    fn test_opcode_bnnn_jump_uses_vx() {
+        let mut emu = Emu::new(
+            Quirks { jump_uses_vx: true, ..Quirks::default() });
+        //given
+        emu.pc = 0x0000;
+        emu.v[0] = 0x11;
+        emu.v[3] = 0x23;
+        //when
+        emu.opcode = 0xb345;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x0368, emu.pc);
+    }
+
+    #[test]
+    fn test_opcode_cxnn_seeded() {
+        //given
+        let mut emu1 = Emu::new(Quirks::default());
+        let mut emu2 = Emu::new(Quirks::default());
+        emu1.set_seed(1234);
+        emu2.set_seed(1234);
+        //when
+        let mut values = Vec::new();
+        for _ in 0..16 {
+            emu1.opcode = 0xc3f0;
+            emu1.decode_and_execute_opcode().unwrap();
+            emu2.opcode = 0xc3f0;
+            emu2.decode_and_execute_opcode().unwrap();
+            values.push(emu1.v[3]);
+            //then
+            assert_eq!(emu1.v[3], emu2.v[3]);
+            assert_eq!(0, emu1.v[3] & 0x0f);
+        }
+        assert!(values.iter().any(|&v| v != values[0]));
+    }
+
+    #[test]
+    fn test_step_fixed_timing() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        // 200: jump 200
+        emu.load_rom(vec![0x12, 0x00]).unwrap();
+        //when
+        emu.set_timing(Timing::Fixed(10));
+        let frame_ends = (0..500).filter(|_| emu.step().unwrap()).count();
+        //then
+        assert_eq!(50, frame_ends);
+        assert_eq!(50, emu.frame());
+        assert_eq!(500, emu.cycles());
+    }
+
+    #[test]
+    fn test_step_vip_timing() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        emu.set_timing(Timing::CosmacVip);
+        // 200: v0 = 1
+        // 202: jump 200
+        emu.load_rom(vec![0x60, 0x01, 0x12, 0x00]).unwrap();
+        //when
+        let mut steps = 1;
+        while !emu.step().unwrap() { steps += 1; }
+        //then
+        // Alternating 46 and 52 machine cycles, out of 2598 in a frame.
+        assert_eq!(54, steps);
+        assert_eq!(1, emu.frame());
+    }
+
+    #[test]
+    fn test_step_vip_timing_waits_for_vblank() {
+        //given
+        let mut emu = Emu::new(Quirks::cosmac_vip());
+        emu.set_timing(Timing::CosmacVip);
+        // 200: sprite v0 v0 5
+        emu.load_rom(vec![0xd0, 0x05]).unwrap();
+        //when
+        let waited = emu.step().unwrap();
+        let drawn = emu.step().unwrap();
+        //then
+        assert!(waited);
+        assert!(!drawn);
+        assert_eq!(0x202, emu.pc);
+    }
+
+    #[test]
+    fn test_reset_restarts_random_sequence() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        // 200: v0 = random byte
+        emu.load_rom(vec![0xc0, 0xff]).unwrap();
+        emu.set_seed(99);
+        emu.execute_cycle().unwrap();
+        let first = emu.v[0];
+        //when
+        emu.reset();
+        emu.execute_cycle().unwrap();
+        //then
+        assert_eq!(first, emu.v[0]);
+        assert_eq!(99, emu.seed());
+    }
+
     #[test]
     fn test_opcode_dxyn_simple_draw() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
         emu.draw = false;
@@ -1409,26 +2231,26 @@ mod tests {
 
         //when
         emu.opcode = 0xd122;
-        emu.decode_and_execute_opcode();
-
-        //then
-        assert_eq!(false, emu.gfx[0x0005+0][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+1][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+2][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+3][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+4][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+5][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+6][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+7][0x0006+0]);
-
-        assert_eq!(true,  emu.gfx[0x0005+0][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+1][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+2][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+3][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+4][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+5][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+6][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+7][0x0006+1]);
+        emu.decode_and_execute_opcode().unwrap();
+
+        //then
+        assert_eq!(false, emu.gfx[0][0x0005+0][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+1][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+2][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+3][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+4][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+5][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+6][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+7][0x0006+0]);
+
+        assert_eq!(true,  emu.gfx[0][0x0005+0][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+1][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+2][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+3][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+4][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+5][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+6][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+7][0x0006+1]);
         
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
@@ -1437,28 +2259,28 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_simple_undraw() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
         emu.draw = false;
 
-        emu.gfx[0x0005+0][0x006+0] = false;
-        emu.gfx[0x0005+1][0x006+0] = true;
-        emu.gfx[0x0005+2][0x006+0] = false;
-        emu.gfx[0x0005+3][0x006+0] = true;
-        emu.gfx[0x0005+4][0x006+0] = false;
-        emu.gfx[0x0005+5][0x006+0] = true;
-        emu.gfx[0x0005+6][0x006+0] = false;
-        emu.gfx[0x0005+7][0x006+0] = true;
-
-        emu.gfx[0x0005+0][0x006+1] = true;
-        emu.gfx[0x0005+1][0x006+1] = true;
-        emu.gfx[0x0005+2][0x006+1] = true;
-        emu.gfx[0x0005+3][0x006+1] = true;
-        emu.gfx[0x0005+4][0x006+1] = true;
-        emu.gfx[0x0005+5][0x006+1] = true;
-        emu.gfx[0x0005+6][0x006+1] = true;
-        emu.gfx[0x0005+7][0x006+1] = true;
+        emu.gfx[0][0x0005+0][0x006+0] = false;
+        emu.gfx[0][0x0005+1][0x006+0] = true;
+        emu.gfx[0][0x0005+2][0x006+0] = false;
+        emu.gfx[0][0x0005+3][0x006+0] = true;
+        emu.gfx[0][0x0005+4][0x006+0] = false;
+        emu.gfx[0][0x0005+5][0x006+0] = true;
+        emu.gfx[0][0x0005+6][0x006+0] = false;
+        emu.gfx[0][0x0005+7][0x006+0] = true;
+
+        emu.gfx[0][0x0005+0][0x006+1] = true;
+        emu.gfx[0][0x0005+1][0x006+1] = true;
+        emu.gfx[0][0x0005+2][0x006+1] = true;
+        emu.gfx[0][0x0005+3][0x006+1] = true;
+        emu.gfx[0][0x0005+4][0x006+1] = true;
+        emu.gfx[0][0x0005+5][0x006+1] = true;
+        emu.gfx[0][0x0005+6][0x006+1] = true;
+        emu.gfx[0][0x0005+7][0x006+1] = true;
 
         emu.v[1] = 0x0005;
         emu.v[2] = 0x0006;
@@ -1468,26 +2290,26 @@ mod tests {
         
         //when
         emu.opcode = 0xd122;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         
         //then
-        assert_eq!(false, emu.gfx[0x0005+0][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+1][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+2][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+3][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+4][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+5][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+6][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+7][0x0006+0]);
-
-        assert_eq!(false, emu.gfx[0x0005+0][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+1][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+2][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+3][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+4][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+5][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+6][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+7][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+0][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+1][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+2][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+3][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+4][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+5][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+6][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+7][0x0006+0]);
+
+        assert_eq!(false, emu.gfx[0][0x0005+0][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+1][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+2][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+3][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+4][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+5][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+6][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+7][0x0006+1]);
         
         assert_eq!(false, emu.draw);
         assert_eq!(0x01, emu.v[0x0f]);
@@ -1496,28 +2318,28 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_simple_partial_redraw() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
         emu.draw = false;
 
-        emu.gfx[0x0005+0][0x006+0] = false;
-        emu.gfx[0x0005+1][0x006+0] = true;
-        emu.gfx[0x0005+2][0x006+0] = false;
-        emu.gfx[0x0005+3][0x006+0] = true;
-        emu.gfx[0x0005+4][0x006+0] = false;
-        emu.gfx[0x0005+5][0x006+0] = false;
-        emu.gfx[0x0005+6][0x006+0] = false;
-        emu.gfx[0x0005+7][0x006+0] = false;
-
-        emu.gfx[0x0005+0][0x006+1] = true;
-        emu.gfx[0x0005+1][0x006+1] = true;
-        emu.gfx[0x0005+2][0x006+1] = true;
-        emu.gfx[0x0005+3][0x006+1] = true;
-        emu.gfx[0x0005+4][0x006+1] = true;
-        emu.gfx[0x0005+5][0x006+1] = true;
-        emu.gfx[0x0005+6][0x006+1] = true;
-        emu.gfx[0x0005+7][0x006+1] = true;
+        emu.gfx[0][0x0005+0][0x006+0] = false;
+        emu.gfx[0][0x0005+1][0x006+0] = true;
+        emu.gfx[0][0x0005+2][0x006+0] = false;
+        emu.gfx[0][0x0005+3][0x006+0] = true;
+        emu.gfx[0][0x0005+4][0x006+0] = false;
+        emu.gfx[0][0x0005+5][0x006+0] = false;
+        emu.gfx[0][0x0005+6][0x006+0] = false;
+        emu.gfx[0][0x0005+7][0x006+0] = false;
+
+        emu.gfx[0][0x0005+0][0x006+1] = true;
+        emu.gfx[0][0x0005+1][0x006+1] = true;
+        emu.gfx[0][0x0005+2][0x006+1] = true;
+        emu.gfx[0][0x0005+3][0x006+1] = true;
+        emu.gfx[0][0x0005+4][0x006+1] = true;
+        emu.gfx[0][0x0005+5][0x006+1] = true;
+        emu.gfx[0][0x0005+6][0x006+1] = true;
+        emu.gfx[0][0x0005+7][0x006+1] = true;
 
         emu.v[1] = 0x0005;
         emu.v[2] = 0x0006;
@@ -1527,26 +2349,26 @@ mod tests {
         
         //when
         emu.opcode = 0xd122;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         
         //then
-        assert_eq!(true,  emu.gfx[0x0005+0][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+1][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+2][0x0006+0]);
-        assert_eq!(false, emu.gfx[0x0005+3][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+4][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+5][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+6][0x0006+0]);
-        assert_eq!(true,  emu.gfx[0x0005+7][0x0006+0]);
-
-        assert_eq!(false, emu.gfx[0x0005+0][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+1][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+2][0x0006+1]);
-        assert_eq!(false, emu.gfx[0x0005+3][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+4][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+5][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+6][0x0006+1]);
-        assert_eq!(true,  emu.gfx[0x0005+7][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+0][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+1][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+2][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0x0005+3][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+4][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+5][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+6][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+7][0x0006+0]);
+
+        assert_eq!(false, emu.gfx[0][0x0005+0][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+1][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+2][0x0006+1]);
+        assert_eq!(false, emu.gfx[0][0x0005+3][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+4][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+5][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+6][0x0006+1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+7][0x0006+1]);
         
         assert_eq!(true, emu.draw);
         assert_eq!(0x01, emu.v[0x0f]);
@@ -1555,7 +2377,7 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_overflow_width() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
         emu.draw = false;
@@ -1567,26 +2389,26 @@ mod tests {
 
         //when
         emu.opcode = 0xd122;
-        emu.decode_and_execute_opcode();
-
-        //then
-        assert_eq!(false, emu.gfx[SMALL_GFX_W-4+0][0x0006+0]);
-        assert_eq!(true,  emu.gfx[SMALL_GFX_W-4+1][0x0006+0]);
-        assert_eq!(false, emu.gfx[SMALL_GFX_W-4+2][0x0006+0]);
-        assert_eq!(true,  emu.gfx[SMALL_GFX_W-4+3][0x0006+0]);
-        assert_eq!(false, emu.gfx[0][0x0006+0]);
-        assert_eq!(true,  emu.gfx[1][0x0006+0]);
-        assert_eq!(false, emu.gfx[2][0x0006+0]);
-        assert_eq!(true,  emu.gfx[3][0x0006+0]);
-
-        assert_eq!(true, emu.gfx[SMALL_GFX_W-4+0][0x0006+1]);
-        assert_eq!(true, emu.gfx[SMALL_GFX_W-4+1][0x0006+1]);
-        assert_eq!(true, emu.gfx[SMALL_GFX_W-4+2][0x0006+1]);
-        assert_eq!(true, emu.gfx[SMALL_GFX_W-4+3][0x0006+1]);
-        assert_eq!(true, emu.gfx[0][0x0006+1]);
-        assert_eq!(true, emu.gfx[1][0x0006+1]);
-        assert_eq!(true, emu.gfx[2][0x0006+1]);
-        assert_eq!(true, emu.gfx[3][0x0006+1]);
+        emu.decode_and_execute_opcode().unwrap();
+
+        //then
+        assert_eq!(false, emu.gfx[0][SMALL_GFX_W-4+0][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][SMALL_GFX_W-4+1][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][SMALL_GFX_W-4+2][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][SMALL_GFX_W-4+3][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][0][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][1][0x0006+0]);
+        assert_eq!(false, emu.gfx[0][2][0x0006+0]);
+        assert_eq!(true,  emu.gfx[0][3][0x0006+0]);
+
+        assert_eq!(true, emu.gfx[0][SMALL_GFX_W-4+0][0x0006+1]);
+        assert_eq!(true, emu.gfx[0][SMALL_GFX_W-4+1][0x0006+1]);
+        assert_eq!(true, emu.gfx[0][SMALL_GFX_W-4+2][0x0006+1]);
+        assert_eq!(true, emu.gfx[0][SMALL_GFX_W-4+3][0x0006+1]);
+        assert_eq!(true, emu.gfx[0][0][0x0006+1]);
+        assert_eq!(true, emu.gfx[0][1][0x0006+1]);
+        assert_eq!(true, emu.gfx[0][2][0x0006+1]);
+        assert_eq!(true, emu.gfx[0][3][0x0006+1]);
         
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
@@ -1595,7 +2417,7 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_overflow_height() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000; 
         emu.draw = false;
@@ -1607,48 +2429,137 @@ mod tests {
 
         //when
         emu.opcode = 0xd122;
-        emu.decode_and_execute_opcode();
-
-        //then
-        assert_eq!(false, emu.gfx[0x0005+0][SMALL_GFX_H-1]);
-        assert_eq!(true,  emu.gfx[0x0005+1][SMALL_GFX_H-1]);
-        assert_eq!(false, emu.gfx[0x0005+2][SMALL_GFX_H-1]);
-        assert_eq!(true,  emu.gfx[0x0005+3][SMALL_GFX_H-1]);
-        assert_eq!(false, emu.gfx[0x0005+4][SMALL_GFX_H-1]);
-        assert_eq!(true,  emu.gfx[0x0005+5][SMALL_GFX_H-1]);
-        assert_eq!(false, emu.gfx[0x0005+6][SMALL_GFX_H-1]);
-        assert_eq!(true,  emu.gfx[0x0005+7][SMALL_GFX_H-1]);
-
-        assert_eq!(true,  emu.gfx[0x0005+0][0]);
-        assert_eq!(true,  emu.gfx[0x0005+1][0]);
-        assert_eq!(true,  emu.gfx[0x0005+2][0]);
-        assert_eq!(true,  emu.gfx[0x0005+3][0]);
-        assert_eq!(true,  emu.gfx[0x0005+4][0]);
-        assert_eq!(true,  emu.gfx[0x0005+5][0]);
-        assert_eq!(true,  emu.gfx[0x0005+6][0]);
-        assert_eq!(true,  emu.gfx[0x0005+7][0]);
+        emu.decode_and_execute_opcode().unwrap();
+
+        //then
+        assert_eq!(false, emu.gfx[0][0x0005+0][SMALL_GFX_H-1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+1][SMALL_GFX_H-1]);
+        assert_eq!(false, emu.gfx[0][0x0005+2][SMALL_GFX_H-1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+3][SMALL_GFX_H-1]);
+        assert_eq!(false, emu.gfx[0][0x0005+4][SMALL_GFX_H-1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+5][SMALL_GFX_H-1]);
+        assert_eq!(false, emu.gfx[0][0x0005+6][SMALL_GFX_H-1]);
+        assert_eq!(true,  emu.gfx[0][0x0005+7][SMALL_GFX_H-1]);
+
+        assert_eq!(true,  emu.gfx[0][0x0005+0][0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+1][0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+2][0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+3][0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+4][0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+5][0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+6][0]);
+        assert_eq!(true,  emu.gfx[0][0x0005+7][0]);
         
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
     }
     
+    #[test]
+    fn test_opcode_dxyn_clip_sprites() {
+        let mut emu = Emu::new(
+            Quirks { clip_sprites: true, ..Quirks::default() });
+        //given
+        emu.pc = 0x0000; 
+        emu.v[1] = (SMALL_GFX_W - 4) as u8;
+        emu.v[2] = (SMALL_GFX_H - 1) as u8;
+        emu.ram_idx = 0x222;
+        emu.ram[(emu.ram_idx+0) as usize] = 0b11111111 as u8;
+        emu.ram[(emu.ram_idx+1) as usize] = 0b11111111 as u8;
+
+        //when
+        emu.opcode = 0xd122;
+        emu.decode_and_execute_opcode().unwrap();
+
+        //then
+        for x in 0..4 {
+            assert_eq!(true, emu.gfx[0][SMALL_GFX_W-4+x][SMALL_GFX_H-1]);
+            assert_eq!(false, emu.gfx[0][x][SMALL_GFX_H-1]);
+            assert_eq!(false, emu.gfx[0][x][0]);
+            assert_eq!(false, emu.gfx[0][SMALL_GFX_W-4+x][0]);
+        }
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_opcode_dxyn_clip_sprites_wraps_start_position() {
+        let mut emu = Emu::new(
+            Quirks { clip_sprites: true, ..Quirks::default() });
+        //given
+        emu.pc = 0x0000; 
+        emu.v[1] = (SMALL_GFX_W + 1) as u8;
+        emu.v[2] = (SMALL_GFX_H + 2) as u8;
+        emu.ram_idx = 0x222;
+        emu.ram[emu.ram_idx as usize] = 0b10000000 as u8;
+
+        //when
+        emu.opcode = 0xd121;
+        emu.decode_and_execute_opcode().unwrap();
+
+        //then
+        assert_eq!(true, emu.gfx[0][1][2]);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_opcode_dxyn_display_wait() {
+        let mut emu = Emu::new(
+            Quirks { display_wait: true, ..Quirks::default() });
+        //given
+        emu.pc = 0x0000; 
+        emu.ram_idx = 0x222;
+        emu.ram[emu.ram_idx as usize] = 0b10000000 as u8;
+
+        //when
+        emu.opcode = 0xd001;
+        emu.decode_and_execute_opcode().unwrap();
+
+        //then
+        assert_eq!(false, emu.gfx[0][0][0]);
+        assert_eq!(0x0000, emu.pc);
+
+        //when
+        emu.update_timers();
+        emu.decode_and_execute_opcode().unwrap();
+
+        //then
+        assert_eq!(true, emu.gfx[0][0][0]);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_opcode_dxyn_out_of_bounds() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0000; 
+        emu.ram_idx = 0x0ffe;
+        //when
+        emu.opcode = 0xd123;
+        let result = emu.decode_and_execute_opcode();
+        //then
+        assert_eq!(Err(EmuError::OutOfBoundsMemoryAccess { 
+            addr: RAM_SIZE, 
+            pc: 0x0000 
+        }), result);
+        assert_eq!(0x0000, emu.pc);
+    }
+
     #[test]
     fn test_opcode_dxyn_draw_font_0() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x0; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1656,20 +2567,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_1() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x1; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("  # "), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte(" ## "), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("  # "), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("  # "), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte(" ###"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("  # "), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte(" ## "), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("  # "), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("  # "), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte(" ###"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1677,20 +2588,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_2() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x2; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1698,20 +2609,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_3() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x3; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1719,20 +2630,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_4() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x4; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1740,20 +2651,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_5() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x5; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1761,20 +2672,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_6() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x6; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1782,20 +2693,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_7() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x7; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("  # "), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte(" #  "), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte(" #  "), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("  # "), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte(" #  "), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte(" #  "), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1803,20 +2714,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_8() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x8; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1824,20 +2735,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_9() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0x9; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("   #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1845,20 +2756,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_A() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0xA; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1866,20 +2777,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_B() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0xB; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1887,20 +2798,20 @@ mod tests {
     
     #[test]
     fn test_opcode_dxyn_draw_font_C() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0xC; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1908,20 +2819,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_D() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0xD; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#  #"), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("### "), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1929,20 +2840,20 @@ mod tests {
 
     #[test]
     fn test_opcode_dxyn_draw_font_E() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0xE; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1950,20 +2861,20 @@ mod tests {
     
     #[test]
     fn test_opcode_dxyn_draw_font_F() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         let fchar = 0xF; 
         emu.ram_idx = 0x0000 + (fchar as u16) * 5; 
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xd005;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 0));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 1));
-        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx, 0, 2));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 3));
-        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx, 0, 4));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 0));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 1));
+        assert_eq!(txt_to_byte("####"), booleans_to_byte(&emu.gfx[0], 0, 2));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 3));
+        assert_eq!(txt_to_byte("#   "), booleans_to_byte(&emu.gfx[0], 0, 4));
         assert_eq!(true, emu.draw);
         assert_eq!(0x00, emu.v[0x0f]);
         assert_eq!(0x0000+2, emu.pc);
@@ -1988,69 +2899,85 @@ mod tests {
 
     #[test]
     fn test_opcode_ex9e_key_not_pressed() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[2] = 0x0a;
         emu.keys[0x0a] = false;
         //when
         emu.opcode = 0xe29e;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     fn test_opcode_ex9e_key_pressed() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[2] = 0x0a;
         emu.keys[0x0a] = true;
         //when
         emu.opcode = 0xe29e;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+4, emu.pc);
     }
 
+    #[test]
+    fn test_opcode_ex9e_key_out_of_range() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0000;
+        emu.v[2] = 0x10;
+        emu.keys[0x00] = true;
+        //when
+        emu.opcode = 0xe29e;
+        emu.decode_and_execute_opcode().unwrap();
+        emu.opcode = 0xe2a1;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x0000+4+2, emu.pc);
+    }
+
     #[test]
     fn test_opcode_exa1_key_not_pressed() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[2] = 0x0a;
         emu.keys[0x0a] = false;
         //when
         emu.opcode = 0xe2a1;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+4, emu.pc);
     }
 
     #[test]
     fn test_opcode_exa1_key_pressed() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[2] = 0x0a;
         emu.keys[0x0a] = true;
         //when
         emu.opcode = 0xe2a1;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+2, emu.pc);
     }
 
     #[test]
     fn test_opcode_fx07() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.dt = 0x9a;
         //when
         emu.opcode = 0xf207;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x9a, emu.v[0x02]);
         assert_eq!(0x9a, emu.dt);
@@ -2059,39 +2986,54 @@ mod tests {
 
     #[test]
     fn test_opcode_fx0a_with_keypress() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.keys[0x0f] = true;
         //when
         emu.opcode = 0xf20a;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0f, emu.v[0x02]);
         assert_eq!(0x0000+2, emu.pc);
     }
 
+    #[test]
+    fn test_opcode_fx0a_with_several_keypresses() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0000;
+        emu.keys[0x03] = true;
+        emu.keys[0x0c] = true;
+        //when
+        emu.opcode = 0xf20a;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x03, emu.v[2]);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
     #[test]
     fn test_opcode_fx0a_without_keypress() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         //when
         emu.opcode = 0xf20a;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+0, emu.pc);
     }
 
     #[test]
     fn test_opcode_fx15() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x02] = 0x9a;
         //when
         emu.opcode = 0xf215;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x9a, emu.v[0x02]);
         assert_eq!(0x9a, emu.dt);
@@ -2100,13 +3042,13 @@ mod tests {
 
     #[test]
     fn test_opcode_fx18() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0x02] = 0x9a;
         //when
         emu.opcode = 0xf218;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x9a, emu.v[0x02]);
         assert_eq!(0x9a, emu.st);
@@ -2115,14 +3057,14 @@ mod tests {
 
     #[test]
     fn test_opcode_fx1e_without_overflow() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.ram_idx = 0x222;
         emu.v[0x02] = 0xab;
         //when
         emu.opcode = 0xf21e;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x2cd, 0x222 + 0xab);
         assert_eq!(0x2cd, emu.ram_idx);
@@ -2133,14 +3075,14 @@ mod tests {
 
     #[test]
     fn test_opcode_fx1e_with_overflow() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.ram_idx = 0xfff;
         emu.v[0x02] = 0xab;
         //when
         emu.opcode = 0xf21e;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         let wrap_mod = (0xfff + 0xab) % (0xfff + 0x001);
         assert_eq!(0x0aa, wrap_mod);
@@ -2152,14 +3094,14 @@ mod tests {
 
     #[test]
     fn test_opcode_fx29() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.ram_idx = 0xfff;
         emu.v[0x03] = 0x0a;
         //when
         emu.opcode = 0xf329;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+(0x0a*5), emu.ram_idx);
         assert_eq!(0x0a, emu.v[0x03]);
@@ -2168,14 +3110,14 @@ mod tests {
 
     #[test]
     fn test_opcode_fx30() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.ram_idx = 0xfff;
         emu.v[0x03] = 0x0a;
         //when
         emu.opcode = 0xf330;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0000+(0x0a*10), emu.ram_idx);
         assert_eq!(0x0a, emu.v[0x03]);
@@ -2184,14 +3126,14 @@ mod tests {
 
     #[test]
     fn test_opcode_fx33() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.ram_idx = 0xbbb;
         emu.v[0x02] = 0x7b;
         //when
         emu.opcode = 0xf233;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x7b, 123);
         assert_eq!(0x7b, emu.v[0x02]);
@@ -2201,9 +3143,26 @@ mod tests {
         assert_eq!(0x0000+2, emu.pc);
     }
 
+    #[test]
+    fn test_opcode_fx33_out_of_bounds() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x0ffe;
+        //when
+        emu.opcode = 0xf233;
+        let result = emu.decode_and_execute_opcode();
+        //then
+        assert_eq!(Err(EmuError::OutOfBoundsMemoryAccess { 
+            addr: RAM_SIZE, 
+            pc: 0x0000 
+        }), result);
+        assert_eq!(0x00, emu.ram[0x0ffe]);
+    }
+
     #[test]
     fn test_opcode_fx55() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.ram_idx = 0x333;
@@ -2212,7 +3171,7 @@ mod tests {
         emu.v[0x02] = 0x0c;
         //when
         emu.opcode = 0xf355;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0a, emu.ram[(emu.ram_idx+0) as usize]);
         assert_eq!(0x0b, emu.ram[(emu.ram_idx+1) as usize]);
@@ -2220,9 +3179,59 @@ mod tests {
         assert_eq!(0x0000+2, emu.pc);
     }
 
+    #[test]
+    fn test_opcode_fx55_out_of_bounds() {
+        let mut emu = Emu::new(Quirks::default());
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x0ffd;
+        //when
+        emu.opcode = 0xf355;
+        let result = emu.decode_and_execute_opcode();
+        //then
+        assert_eq!(Err(EmuError::OutOfBoundsMemoryAccess { 
+            addr: RAM_SIZE, 
+            pc: 0x0000 
+        }), result);
+    }
+
+    #[test]
+    fn test_opcode_fx55_load_store_idx() {
+        let mut emu = Emu::new(
+            Quirks { load_store_idx: LoadStoreIdx::PlusXPlusOne,
+                     ..Quirks::default() });
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x333;
+        //when
+        emu.opcode = 0xf255;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x333+3, emu.ram_idx);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_opcode_fx55_load_store_idx_presets() {
+        let presets = [("vip", 0x333+3), ("chip48", 0x333+2),
+                       ("schip1.0", 0x333+2), ("schip1.1", 0x333),
+                       ("xochip", 0x333+3), ("modern", 0x333)];
+        for &(name, expected) in presets.iter() {
+            let mut emu = Emu::new(Quirks::preset(name).unwrap());
+            //given
+            emu.pc = 0x0000;
+            emu.ram_idx = 0x333;
+            //when
+            emu.opcode = 0xf255;
+            emu.decode_and_execute_opcode().unwrap();
+            //then
+            assert_eq!(expected, emu.ram_idx, "{}", name);
+        }
+    }
+
     #[test]
     fn test_opcode_fx65() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.ram_idx = 0x333;
@@ -2231,7 +3240,7 @@ mod tests {
         emu.ram[(emu.ram_idx + 2) as usize] = 0x0c;
         //when
         emu.opcode = 0xf365;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x0a, emu.v[0]);
         assert_eq!(0x0b, emu.v[1]);
@@ -2239,9 +3248,25 @@ mod tests {
         assert_eq!(0x0000+2, emu.pc);
     }
 
+    #[test]
+    fn test_opcode_fx65_load_store_idx() {
+        let mut emu = Emu::new(
+            Quirks { load_store_idx: LoadStoreIdx::PlusXPlusOne,
+                     ..Quirks::default() });
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x333;
+        //when
+        emu.opcode = 0xf365;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x333+4, emu.ram_idx);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
     #[test]
     fn test_opcode_fx75() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0] = 0x03;
@@ -2255,7 +3280,7 @@ mod tests {
         emu.v[8] = 0x0B;
         //when
         emu.opcode = 0xf375;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x03, emu.super_mode_rpl_flags[0]);
         assert_eq!(0x04, emu.super_mode_rpl_flags[1]);
@@ -2270,7 +3295,7 @@ mod tests {
 
     #[test]
     fn test_opcode_fx75_safe_against_x_greater_than_7() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::new(Quirks::default());
         //given
         emu.pc = 0x0000;
         emu.v[0] = 0x03;
@@ -2284,7 +3309,7 @@ mod tests {
         emu.v[8] = 0x0B;
         //when
         emu.opcode = 0xf875;
-        emu.decode_and_execute_opcode();
+        emu.decode_and_execute_opcode().unwrap();
         //then
         assert_eq!(0x03, emu.super_mode_rpl_flags[0]);
         assert_eq!(0x04, emu.super_mode_rpl_flags[1]);
@@ -2297,5 +3322,259 @@ mod tests {
         assert_eq!(0x0000+2, emu.pc);
     }
 
-}
+    fn xo_chip_emu() -> Emu {
+        Emu::with_mode(Mode::XoChip, Quirks::xo_chip())
+    }
+
+    #[test]
+    fn test_xo_chip_load_rom_beyond_4k() {
+        let mut emu = xo_chip_emu();
+        //when
+        emu.load_rom(vec![0xaa; XO_CHIP_MAX_ROM_SIZE]).unwrap();
+        //then
+        assert_eq!(0xaa, emu.ram[0xffff]);
+    }
 
+    #[test]
+    fn test_xo_chip_reset_stays_in_xo_chip_mode() {
+        let mut emu = xo_chip_emu();
+        //when
+        emu.reset();
+        //then
+        assert_eq!(Mode::XoChip, emu.mode);
+    }
+
+    #[test]
+    fn test_xo_chip_opcodes_unknown_in_standard_mode() {
+        let mut emu = Emu::new(Quirks::default());
+        for &opcode in [0x00d1, 0x5ab2, 0x5ab3, 0xf000, 0xf101, 0xf002, 
+                        0xf13a].iter() {
+            //given
+            emu.pc = 0x0aaa;
+            //when
+            emu.opcode = opcode;
+            let result = emu.decode_and_execute_opcode();
+            //then
+            assert_eq!(Err(EmuError::UnknownOpcode { opcode, pc: 0x0aaa }), 
+                       result);
+        }
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_00dn() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000; 
+        emu.gfx[0][0][2] = true;
+        emu.gfx[0][1][2] = true;
+        //when
+        emu.opcode = 0x00d2;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(false, emu.gfx[0][0][2]);
+        assert_eq!(false, emu.gfx[0][1][2]);
+        assert_eq!(true, emu.gfx[0][0][0]);
+        assert_eq!(true, emu.gfx[0][1][0]);
+        assert_eq!(true, emu.draw);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_00ff_and_00fe() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000; 
+        emu.gfx[0][0][0] = true;
+        //when
+        emu.opcode = 0x00ff;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(Mode::XoChip, emu.mode);
+        assert_eq!(true, emu.hires());
+        assert_eq!(false, emu.gfx[0][0][0]);
+        //when
+        emu.opcode = 0x00fe;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(Mode::XoChip, emu.mode);
+        assert_eq!(false, emu.hires());
+        assert_eq!(0x0000+4, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_5xy2() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x333;
+        emu.v[0x02] = 0x0a;
+        emu.v[0x03] = 0x0b;
+        emu.v[0x04] = 0x0c;
+        //when
+        emu.opcode = 0x5242;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x0a, emu.ram[0x333]);
+        assert_eq!(0x0b, emu.ram[0x334]);
+        assert_eq!(0x0c, emu.ram[0x335]);
+        assert_eq!(0x333, emu.ram_idx);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_5xy2_reversed() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x333;
+        emu.v[0x02] = 0x0a;
+        emu.v[0x03] = 0x0b;
+        emu.v[0x04] = 0x0c;
+        //when
+        emu.opcode = 0x5422;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x0c, emu.ram[0x333]);
+        assert_eq!(0x0b, emu.ram[0x334]);
+        assert_eq!(0x0a, emu.ram[0x335]);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_5xy3() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x333;
+        emu.ram[0x333] = 0x0a;
+        emu.ram[0x334] = 0x0b;
+        //when
+        emu.opcode = 0x5783;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x0a, emu.v[0x07]);
+        assert_eq!(0x0b, emu.v[0x08]);
+        assert_eq!(0x333, emu.ram_idx);
+        assert_eq!(0x0000+2, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_f000() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0200;
+        emu.ram[0x0202] = 0xbe;
+        emu.ram[0x0203] = 0xef;
+        //when
+        emu.opcode = 0xf000;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0xbeef, emu.ram_idx);
+        assert_eq!(0x0200+4, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_skip_over_f000() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0200;
+        emu.ram[0x0202] = 0xf0;
+        emu.ram[0x0203] = 0x00;
+        emu.v[0x0a] = 0x23;
+        //when
+        emu.opcode = 0x3a23;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x0200+6, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_fn01_and_dxyn_on_both_planes() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x222;
+        emu.ram[0x222] = 0b10000000;
+        emu.ram[0x223] = 0b01000000;
+        //when
+        emu.opcode = 0xf301;
+        emu.decode_and_execute_opcode().unwrap();
+        emu.opcode = 0xd001;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(true, emu.gfx[0][0][0]);
+        assert_eq!(false, emu.gfx[0][1][0]);
+        assert_eq!(false, emu.gfx[1][0][0]);
+        assert_eq!(true, emu.gfx[1][1][0]);
+        assert_eq!(0x0000+4, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_00e0_clears_selected_planes_only() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000;
+        emu.gfx[0][0][0] = true;
+        emu.gfx[1][0][0] = true;
+        //when
+        emu.opcode = 0xf201;
+        emu.decode_and_execute_opcode().unwrap();
+        emu.opcode = 0x00e0;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(true, emu.gfx[0][0][0]);
+        assert_eq!(false, emu.gfx[1][0][0]);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_dxy0_draws_16x16_in_lores() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x222;
+        emu.ram[0x222] = 0b00000000;
+        emu.ram[0x223] = 0b00000001;
+        //when
+        emu.opcode = 0xd000;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(true, emu.gfx[0][15][0]);
+        assert_eq!(false, emu.gfx[0][7][0]);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_f002_and_fx3a() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000;
+        emu.ram_idx = 0x222;
+        for i in 0..16 { emu.ram[0x222 + i] = i as u8; }
+        emu.v[0x05] = 0x70;
+        //when
+        emu.opcode = 0xf002;
+        emu.decode_and_execute_opcode().unwrap();
+        emu.opcode = 0xf53a;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        let (pattern, pitch) = emu.sound();
+        assert_eq!(Some([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]),
+                   pattern);
+        assert_eq!(0x70, pitch);
+        assert_eq!(true, emu.sound_changed);
+        assert_eq!(0x0000+4, emu.pc);
+    }
+
+    #[test]
+    fn test_xo_chip_opcode_fx75_uses_16_flags() {
+        let mut emu = xo_chip_emu();
+        //given
+        emu.pc = 0x0000;
+        emu.v[0x0f] = 0x0b;
+        //when
+        emu.opcode = 0xff75;
+        emu.decode_and_execute_opcode().unwrap();
+        //then
+        assert_eq!(0x0b, emu.super_mode_rpl_flags[0x0f]);
+    }
+
+}
@@ -0,0 +1,273 @@
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use std::collections::{HashMap, HashSet};
+use super::keymap::{Action, Binding};
+
+// Controller buttons that can be bound, by name.
+const BUTTON_NAMES: [(&str, Button); 15] = [
+    ("a", Button::A),
+    ("b", Button::B),
+    ("x", Button::X),
+    ("y", Button::Y),
+    ("back", Button::Back),
+    ("guide", Button::Guide),
+    ("start", Button::Start),
+    ("left stick", Button::LeftStick),
+    ("right stick", Button::RightStick),
+    ("left shoulder", Button::LeftShoulder),
+    ("right shoulder", Button::RightShoulder),
+    ("up", Button::DPadUp),
+    ("down", Button::DPadDown),
+    ("left", Button::DPadLeft),
+    ("right", Button::DPadRight),
+];
+
+// The button with the given name, ignoring case.
+pub fn button(name: &str) -> Option<Button> {
+    let name = name.to_lowercase();
+    BUTTON_NAMES.iter()
+        .find(|&&(button_name, _)| button_name == name)
+        .map(|&(_, button)| button)
+}
+
+// Controller buttons to the keypad keys and actions they are bound to. By
+// default the d-pad presses 5, 8, 7 and 9, which many games move with, and
+// the face buttons and shoulders the keys games most often act on besides.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Buttons {
+    bindings: HashMap<Button, Binding>,
+}
+
+impl Default for Buttons {
+
+    fn default() -> Buttons {
+        let bindings = [
+            (Button::DPadUp, Binding::Key(5)),
+            (Button::DPadDown, Binding::Key(8)),
+            (Button::DPadLeft, Binding::Key(7)),
+            (Button::DPadRight, Binding::Key(9)),
+            (Button::A, Binding::Key(6)),
+            (Button::B, Binding::Key(4)),
+            (Button::X, Binding::Key(1)),
+            (Button::Y, Binding::Key(0xc)),
+            (Button::LeftShoulder, Binding::Key(0xa)),
+            (Button::RightShoulder, Binding::Key(0xb)),
+            (Button::Start, Binding::Action(Action::Pause)),
+            (Button::Back, Binding::Action(Action::Reset)),
+        ];
+        Buttons { bindings: bindings.iter().cloned().collect() }
+    }
+
+}
+
+impl Buttons {
+
+    // Bind buttons, by name, to keypad keys in hex, to actions, or to
+    // "none". For instance:
+    //
+    //     { "a": "5", "x": "rewind", "back": "none" }
+    pub fn bind(&mut self, bindings: &HashMap<String, String>)
+        -> Result<(), String> {
+        for (name, binding) in bindings.iter() {
+            let button = button(name)
+                .ok_or_else(|| format!("unknown button {}", name))?;
+            match Binding::from_name(binding) {
+                Some(Some(binding)) => self.bindings.insert(button, binding),
+                Some(None) => self.bindings.remove(&button),
+                None => return Err(format!(
+                    "expected keypad key 0 to f, an action or none, got {}",
+                    binding)),
+            };
+        }
+        Ok(())
+    }
+
+}
+
+// What a controller event comes to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PadEvent {
+    // The keypad changed, see Gamepad::keys.
+    Keys,
+    Pressed(Action),
+    Released(Action),
+}
+
+// The state of the buttons of every controller plugged in, kept up to date
+// from controller events.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gamepad {
+    buttons: Buttons,
+    // Buttons held, by the id of the controller they are held on.
+    held: HashMap<u32, HashSet<Button>>,
+}
+
+impl Gamepad {
+
+    pub fn new(buttons: Buttons) -> Gamepad {
+        Gamepad { buttons, held: HashMap::new() }
+    }
+
+    // Take in a controller event. A controller unplugged releases whatever
+    // was held on it.
+    pub fn handle(&mut self, event: &Event) -> Vec<PadEvent> {
+        match *event {
+            Event::ControllerButtonDown { which, button, .. } => {
+                if !self.held.entry(which).or_default().insert(button) {
+                    return vec![];
+                }
+                match self.buttons.bindings.get(&button) {
+                    Some(&Binding::Key(_)) => vec![PadEvent::Keys],
+                    Some(&Binding::Action(action)) =>
+                        vec![PadEvent::Pressed(action)],
+                    None => vec![],
+                }
+            },
+            Event::ControllerButtonUp { which, button, .. } => {
+                let held = self.held.get_mut(&which)
+                    .is_some_and(|held| held.remove(&button));
+                match held {
+                    true => self.released(&[button]),
+                    false => vec![],
+                }
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                match self.held.remove(&which) {
+                    Some(held) => {
+                        let held: Vec<Button> = held.into_iter().collect();
+                        self.released(&held)
+                    },
+                    None => vec![],
+                }
+            },
+            _ => vec![],
+        }
+    }
+
+    fn released(&self, buttons: &[Button]) -> Vec<PadEvent> {
+        let mut events = vec![];
+        for button in buttons.iter() {
+            match self.buttons.bindings.get(button) {
+                Some(&Binding::Key(_)) if !events.contains(&PadEvent::Keys) =>
+                    events.push(PadEvent::Keys),
+                Some(&Binding::Action(action)) =>
+                    events.push(PadEvent::Released(action)),
+                _ => {},
+            }
+        }
+        events
+    }
+
+    // The state of the keypad, given the buttons held on any controller.
+    pub fn keys(&self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for button in self.held.values().flat_map(|held| held.iter()) {
+            let binding = self.buttons.bindings.get(button);
+            if let Some(&Binding::Key(key)) = binding {
+                keys[key] = true;
+            }
+        }
+        keys
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{button, Buttons, Gamepad, PadEvent};
+    use super::super::keymap::Action;
+    use sdl2::controller::Button;
+    use sdl2::event::Event;
+    use std::collections::HashMap;
+
+    fn down(which: u32, button: Button) -> Event {
+        Event::ControllerButtonDown { timestamp: 0, which, button }
+    }
+
+    fn up(which: u32, button: Button) -> Event {
+        Event::ControllerButtonUp { timestamp: 0, which, button }
+    }
+
+    fn pressed(keys: [bool; 16]) -> Vec<usize> {
+        (0..16).filter(|&key| keys[key]).collect()
+    }
+
+    #[test]
+    fn test_button() {
+        assert_eq!(Some(Button::A), button("a"));
+        assert_eq!(Some(Button::DPadUp), button("Up"));
+        assert_eq!(Some(Button::LeftShoulder), button("left shoulder"));
+        assert_eq!(None, button("z"));
+    }
+
+    #[test]
+    fn test_buttons_press_keys() {
+        //given
+        let mut gamepad = Gamepad::new(Buttons::default());
+        //when
+        let events = gamepad.handle(&down(0, Button::DPadUp));
+        gamepad.handle(&down(1, Button::A));
+        //then
+        assert_eq!(vec![PadEvent::Keys], events);
+        assert_eq!(vec![5, 6], pressed(gamepad.keys()));
+        let events = gamepad.handle(&up(0, Button::DPadUp));
+        assert_eq!(vec![PadEvent::Keys], events);
+        assert_eq!(vec![6], pressed(gamepad.keys()));
+        // Released on another controller than the one it is held on.
+        assert!(gamepad.handle(&up(0, Button::A)).is_empty());
+    }
+
+    #[test]
+    fn test_buttons_take_actions() {
+        //given
+        let mut gamepad = Gamepad::new(Buttons::default());
+        //when
+        let pressed = gamepad.handle(&down(0, Button::Start));
+        let released = gamepad.handle(&up(0, Button::Start));
+        //then
+        assert_eq!(vec![PadEvent::Pressed(Action::Pause)], pressed);
+        assert_eq!(vec![PadEvent::Released(Action::Pause)], released);
+        assert!(gamepad.handle(&down(0, Button::Guide)).is_empty());
+    }
+
+    #[test]
+    fn test_unplugging_releases_buttons() {
+        //given
+        let mut buttons = Buttons::default();
+        let mut bindings = HashMap::new();
+        bindings.insert("x".to_string(), "rewind".to_string());
+        buttons.bind(&bindings).unwrap();
+        let mut gamepad = Gamepad::new(buttons);
+        gamepad.handle(&down(3, Button::X));
+        gamepad.handle(&down(3, Button::DPadLeft));
+        gamepad.handle(&down(3, Button::DPadRight));
+        //when
+        let mut events = gamepad.handle(&Event::ControllerDeviceRemoved {
+            timestamp: 0, which: 3,
+        });
+        //then
+        events.sort_by_key(|&event| event == PadEvent::Keys);
+        assert_eq!(vec![PadEvent::Released(Action::Rewind), PadEvent::Keys],
+                   events);
+        assert_eq!(Vec::<usize>::new(), pressed(gamepad.keys()));
+    }
+
+    #[test]
+    fn test_bind() {
+        let mut buttons = Buttons::default();
+        let bindings = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter()
+                .map(|&(name, binding)| (name.to_string(), binding.to_string()))
+                .collect()
+        };
+        buttons.bind(&bindings(&[("a", "5"), ("start", "none")])).unwrap();
+        let mut gamepad = Gamepad::new(buttons.clone());
+        gamepad.handle(&down(0, Button::A));
+        assert_eq!(vec![5], pressed(gamepad.keys()));
+        assert!(gamepad.handle(&down(0, Button::Start)).is_empty());
+        assert!(buttons.bind(&bindings(&[("a", "10")])).is_err());
+        assert!(buttons.bind(&bindings(&[("z", "1")])).is_err());
+    }
+
+}
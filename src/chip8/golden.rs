@@ -0,0 +1,206 @@
+// Whole-game regression tests. Every rom in roms/ is run headless with a
+// fixed seed and scripted input, and the screen is compared at a number of
+// checkpoints against golden hashes and images checked in under golden/.
+//
+// For each checkpoint that no longer matches, the actual image and a diff
+// image are written to target/golden/. In the diff, pixels set in both are
+// white, pixels only set in the golden image red, and pixels only set now
+// green. Once a change in output is known to be right, run the tests with
+// CHIP8_BLESS=1 to update the golden files.
+
+use super::Mode;
+use super::emu::Emu;
+use super::headless::{self, Limit, Script};
+use super::quirks::Quirks;
+use sha1_smol::Sha1;
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The frames at which the screen is checked.
+const CHECKPOINTS: [u64; 4] = [30, 120, 300, 600];
+const SEED: u64 = 0xc8;
+
+fn dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(name)
+}
+
+// Input for roms without a script of their own in golden/. Presses each of
+// the keys commonly used by games in turn, for a few frames at a time, to
+// get past title screens and move things about.
+fn default_script() -> Script {
+    let keys = [0x5, 0x4, 0x6, 0x8, 0x2, 0xf, 0x7, 0x9, 0x1];
+    let mut source = String::new();
+    for (idx, key) in keys.iter().cycle().take(40).enumerate() {
+        let frame = 15 + idx * 15;
+        writeln!(source, "{} press {:x}", frame, key).unwrap();
+        writeln!(source, "{} release {:x}", frame + 5, key).unwrap();
+    }
+    Script::parse(&source).unwrap()
+}
+
+// The screen as a plain PBM image, a pixel being set when set in any of the
+// bitplanes.
+fn pbm(emu: &Emu) -> String {
+    let mut out = format!("P1\n{} {}\n", emu.width(), emu.height());
+    for y in 0..emu.height() {
+        for x in 0..emu.width() {
+            let set = emu.gfx.iter().any(|plane| plane[x][y]);
+            out.push(if set {'1'} else {'0'});
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// The width, height and pixels of a plain PBM image.
+fn parse_pbm(pbm: &str) -> Option<(usize, usize, Vec<bool>)> {
+    let mut tokens = pbm.split_whitespace();
+    if tokens.next() != Some("P1") {
+        return None;
+    }
+    let width = tokens.next()?.parse().ok()?;
+    let height = tokens.next()?.parse().ok()?;
+    let pixels: Vec<bool> = tokens.flat_map(|token| token.chars())
+        .map(|c| c == '1')
+        .collect();
+    if pixels.len() != width * height {
+        return None;
+    }
+    Some((width, height, pixels))
+}
+
+// Compare two PBM images as a binary PPM image, big enough to hold both.
+fn diff_ppm(golden: &str, actual: &str) -> Vec<u8> {
+    let (gw, gh, gpix) = parse_pbm(golden).unwrap_or((0, 0, Vec::new()));
+    let (aw, ah, apix) = parse_pbm(actual).expect("generated pbm is valid");
+    let (width, height) = (gw.max(aw), gh.max(ah));
+    let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for y in 0..height {
+        for x in 0..width {
+            let g = x < gw && y < gh && gpix[y * gw + x];
+            let a = x < aw && y < ah && apix[y * aw + x];
+            out.extend_from_slice(match (g, a) {
+                (true, true) => &[0xff, 0xff, 0xff],
+                (true, false) => &[0xff, 0x00, 0x00],
+                (false, true) => &[0x00, 0xff, 0x00],
+                (false, false) => &[0x00, 0x00, 0x00],
+            });
+        }
+    }
+    out
+}
+
+// Hash of the screen, covering all the bitplanes and the resolution.
+fn gfx_hash(emu: &Emu) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(format!("{}x{}", emu.width(), emu.height()).as_bytes());
+    for plane in emu.gfx.iter() {
+        for col in plane.iter().take(emu.width()) {
+            let col: Vec<u8> = col.iter().take(emu.height())
+                .map(|&pix| pix as u8)
+                .collect();
+            sha1.update(&col);
+        }
+    }
+    sha1.digest().to_string()
+}
+
+// Run the rom, returning a line with the hash for each checkpoint and the
+// image at each checkpoint. Stops at the first error, noting it instead.
+fn run_rom(path_to_rom: &Path) -> (String, Vec<(u64, String)>) {
+    let mode = match path_to_rom.extension() {
+        Some(ext) if ext == OsStr::new("xo8") => Mode::XoChip,
+        _ => Mode::Standard,
+    };
+    let mut emu = Emu::with_mode(mode, Quirks::default());
+    emu.set_seed(SEED);
+    emu.load_rom(fs::read(path_to_rom).unwrap()).unwrap();
+    let keys = dir("golden").join(path_to_rom.file_stem().unwrap())
+        .with_extension("keys");
+    let script = match fs::read_to_string(&keys) {
+        Ok(source) => Script::parse(&source)
+            .unwrap_or_else(|err| panic!("{}: {}", keys.display(), err)),
+        Err(_) => default_script(),
+    };
+    let mut player = script.player();
+    let mut hashes = String::new();
+    let mut images = Vec::new();
+    for &frame in CHECKPOINTS.iter() {
+        if let Err(err) = headless::run(&mut emu, Limit::Frames(frame),
+                                        &mut player) {
+            writeln!(hashes, "{} error: {}", frame, err).unwrap();
+            break;
+        }
+        writeln!(hashes, "{} {}", frame, gfx_hash(&emu)).unwrap();
+        images.push((frame, pbm(&emu)));
+    }
+    (hashes, images)
+}
+
+#[test]
+fn test_golden_images() {
+    let bless = env::var_os("CHIP8_BLESS").is_some();
+    let golden = dir("golden");
+    let output = dir("target").join("golden");
+    let mut roms: Vec<PathBuf> = fs::read_dir(dir("roms")).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty());
+    let mut failures = Vec::new();
+    for rom in roms.iter() {
+        let name = rom.file_stem().unwrap().to_string_lossy().into_owned();
+        let (hashes, images) = run_rom(rom);
+        let hashes_path = golden.join(format!("{}.txt", name));
+        if bless {
+            fs::create_dir_all(&golden).unwrap();
+            fs::write(&hashes_path, &hashes).unwrap();
+            for (frame, image) in images {
+                fs::write(golden.join(format!("{}.{}.pbm", name, frame)),
+                          image).unwrap();
+            }
+            continue;
+        }
+        let expected = fs::read_to_string(&hashes_path).unwrap_or_default();
+        if expected == hashes {
+            continue;
+        }
+        fs::create_dir_all(&output).unwrap();
+        let expected: Vec<&str> = expected.lines().collect();
+        for (idx, line) in hashes.lines().enumerate() {
+            if expected.get(idx) == Some(&line) {
+                continue;
+            }
+            failures.push(format!("{}: expected {}, got {}", name,
+                                  expected.get(idx).unwrap_or(&"nothing"),
+                                  line));
+            if let Some(&(frame, ref image)) = images.get(idx) {
+                let golden_image = fs::read_to_string(
+                    golden.join(format!("{}.{}.pbm", name, frame)))
+                    .unwrap_or_default();
+                fs::write(output.join(format!("{}.{}.pbm", name, frame)),
+                          image).unwrap();
+                fs::write(output.join(format!("{}.{}.diff.ppm", name, frame)),
+                          diff_ppm(&golden_image, image)).unwrap();
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{} checkpoints differ, see {}:\n{}",
+            failures.len(), output.display(), failures.join("\n"));
+}
+
+#[test]
+fn test_diff_ppm() {
+    let golden = "P1\n2 2\n10\n01\n";
+    let actual = "P1\n2 1\n11\n";
+    let diff = diff_ppm(golden, actual);
+    let header = b"P6\n2 2\n255\n";
+    assert_eq!(&header[..], &diff[..header.len()]);
+    assert_eq!(&[0xff, 0xff, 0xff, 0x00, 0xff, 0x00,
+                 0x00, 0x00, 0x00, 0xff, 0x00, 0x00][..],
+               &diff[header.len()..]);
+}
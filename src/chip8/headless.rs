@@ -0,0 +1,216 @@
+use super::debugger;
+use super::emu::{Emu, EmuError};
+use std::fmt;
+use std::fmt::Write;
+
+// How long to run for. A frame is one update of the timers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Limit {
+    Cycles(u64),
+    Frames(u64),
+}
+
+// A key pressed or released at the start of a frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: usize,
+    pub pressed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+
+}
+
+// Scripted input for a headless run. Each line holds a frame number, press
+// or release, and a key in hex. Blank lines and anything following a # are
+// ignored. For instance:
+//
+//     # hold 5 for half a second
+//     30 press 5
+//     60 release 5
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script {
+    // Ordered by frame.
+    events: Vec<KeyEvent>,
+}
+
+impl Script {
+
+    pub fn parse(source: &str) -> Result<Script, ScriptError> {
+        let mut events = Vec::new();
+        for (idx, line) in source.lines().enumerate() {
+            let err = |message: &str| ScriptError {
+                line: idx + 1,
+                message: message.to_string()
+            };
+            let line = line.split('#').next().unwrap_or("");
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            if words.len() != 3 {
+                return Err(err("expected FRAME press|release KEY"));
+            }
+            let frame = words[0].parse()
+                .map_err(|_| err("expected frame number"))?;
+            let pressed = match words[1] {
+                "press" => true,
+                "release" => false,
+                _ => return Err(err("expected press or release")),
+            };
+            let key = match usize::from_str_radix(words[2], 16) {
+                Ok(key) if key < 16 => key,
+                _ => return Err(err("expected key 0 to f")),
+            };
+            events.push(KeyEvent { frame, key, pressed });
+        }
+        // Stable, so events on the same frame keep their order.
+        events.sort_by_key(|event| event.frame);
+        Ok(Script { events })
+    }
+
+    // Input for run, which presses and releases the keys as scripted.
+    pub fn player(&self) -> impl FnMut(&mut Emu) + '_ {
+        let mut events = self.events.iter().peekable();
+        move |emu| {
+            while let Some(event) = 
+                events.next_if(|event| event.frame <= emu.frame()) {
+                emu.keys[event.key] = event.pressed;
+            }
+        }
+    }
+
+}
+
+// Run the emulator as fast as possible and without a ui, until it has run
+// for as long as the limit since it started, or an error occurs. The input
+// is given the chance to update the emulator before every cycle. Returns the
+// number of cycles executed.
+pub fn run<F>(emu: &mut Emu, limit: Limit, mut input: F) 
+    -> Result<u64, EmuError> where F: FnMut(&mut Emu) {
+    let done = |emu: &Emu| match limit {
+        Limit::Cycles(cycles) => emu.cycles() >= cycles,
+        Limit::Frames(frames) => emu.frame() >= frames,
+    };
+    while !done(emu) {
+        input(emu);
+        emu.step()?;
+    }
+    Ok(emu.cycles())
+}
+
+// Describe the random seed, the registers and the framebuffer, one row of
+// pixels per line. Pixels are shown as . when off, or by the bitplanes they
+// are set in: # for the first, + for the second and % for both.
+pub fn dump(emu: &Emu) -> String {
+    let mut out = format!("seed={}\n", emu.seed());
+    out.push_str(&debugger::registers(emu));
+    out.push('\n');
+    for y in 0..emu.height() {
+        out.push('\n');
+        for x in 0..emu.width() {
+            let color = (0..emu.gfx.len())
+                .filter(|&p| emu.gfx[p][x][y])
+                .fold(0, |color, p| color | (1 << p));
+            out.push(['.', '#', '+', '%'][color]);
+        }
+    }
+    writeln!(out).unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{dump, run, KeyEvent, Limit, Script};
+    use super::super::emu::Emu;
+    use super::super::quirks::Quirks;
+
+    #[test]
+    fn test_parse_script() {
+        let script = Script::parse("# comment\n\n30 release a\n10 press A # x\n")
+            .unwrap();
+        assert_eq!(vec![KeyEvent { frame: 10, key: 0xa, pressed: true },
+                        KeyEvent { frame: 30, key: 0xa, pressed: false }],
+                   script.events);
+        let err = Script::parse("1 press 5\n2 push 5").unwrap_err();
+        assert_eq!("line 2: expected press or release", err.to_string());
+        assert!(Script::parse("1 press 10").is_err());
+        assert!(Script::parse("x press 1").is_err());
+    }
+
+    #[test]
+    fn test_run_cycles() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        // 200: v0 += 1
+        // 202: jump 200
+        emu.load_rom(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        //when
+        let cycles = run(&mut emu, Limit::Cycles(21), |_: &mut Emu| {});
+        //then
+        assert_eq!(Ok(21), cycles);
+        assert_eq!(11, emu.v()[0]);
+    }
+
+    #[test]
+    fn test_run_frames_updates_timers() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        // 200: v0 = 60
+        // 202: delay = v0
+        // 204: jump 204
+        emu.load_rom(vec![0x60, 0x3c, 0xf0, 0x15, 0x12, 0x04]).unwrap();
+        //when
+        let cycles = run(&mut emu, Limit::Frames(60), |_: &mut Emu| {});
+        //then
+        assert_eq!(Ok(60 * 8), cycles);
+        assert_eq!(0, emu.dt());
+    }
+
+    #[test]
+    fn test_run_scripted_input() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        // 200: v0 = key
+        // 202: jump 202
+        emu.load_rom(vec![0xf0, 0x0a, 0x12, 0x02]).unwrap();
+        let script = Script::parse("5 press 7\n6 release 7").unwrap();
+        //when
+        run(&mut emu, Limit::Frames(10), script.player()).unwrap();
+        //then
+        assert_eq!(7, emu.v()[0]);
+        assert_eq!(0x202, emu.pc());
+    }
+
+    #[test]
+    fn test_dump() {
+        //given
+        let mut emu = Emu::new(Quirks::default());
+        // 200: sprite v0 v0 5 (the font for 0)
+        emu.load_rom(vec![0xd0, 0x05]).unwrap();
+        emu.execute_cycle().unwrap();
+        //when
+        let out = dump(&emu);
+        //then
+        let rows: Vec<&str> = out.lines().skip(5).collect();
+        assert!(out.lines().nth(1).unwrap().starts_with("pc=202"));
+        assert_eq!(32, rows.len());
+        assert_eq!(64, rows[0].len());
+        assert!(rows[0].starts_with("####...."));
+        assert!(rows[1].starts_with("#..#...."));
+        assert!(rows[5].starts_with("........"));
+    }
+
+}
@@ -0,0 +1,244 @@
+use super::Mode;
+
+// A decoded instruction along with its operands. Registers are given by
+// their index, so V(3) is v3. Shared by the emulator, which executes the
+// instructions, and the disassembler, which prints them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Instruction {
+    // 00cn: scroll down n pixels.
+    ScrollDown(u8),
+    // 00dn: scroll up n pixels. XO-CHIP only.
+    ScrollUp(u8),
+    // 00e0: clear the screen.
+    Clear,
+    // 00ee: return from subroutine.
+    Return,
+    // 00fb: scroll right 4 pixels.
+    ScrollRight,
+    // 00fc: scroll left 4 pixels.
+    ScrollLeft,
+    // 00fd: exit the interpreter.
+    Exit,
+    // 00fe: disable high resolution.
+    Lores,
+    // 00ff: enable high resolution.
+    Hires,
+    // 1nnn: jump to nnn.
+    Jump(u16),
+    // 2nnn: call subroutine at nnn.
+    Call(u16),
+    // 3xnn: skip if vx equals nn.
+    SkipIfEqual(u8, u8),
+    // 4xnn: skip if vx does not equal nn.
+    SkipIfNotEqual(u8, u8),
+    // 5xy0: skip if vx equals vy.
+    SkipIfRegEqual(u8, u8),
+    // 5xy2: save vx to vy in memory. XO-CHIP only.
+    SaveRange(u8, u8),
+    // 5xy3: load vx to vy from memory. XO-CHIP only.
+    LoadRange(u8, u8),
+    // 6xnn: vx = nn.
+    Set(u8, u8),
+    // 7xnn: vx += nn.
+    Add(u8, u8),
+    // 8xy0: vx = vy.
+    SetReg(u8, u8),
+    // 8xy1: vx |= vy.
+    Or(u8, u8),
+    // 8xy2: vx &= vy.
+    And(u8, u8),
+    // 8xy3: vx ^= vy.
+    Xor(u8, u8),
+    // 8xy4: vx += vy, vf is the carry.
+    AddReg(u8, u8),
+    // 8xy5: vx -= vy, vf is the inverted borrow.
+    Sub(u8, u8),
+    // 8xy6: shift right.
+    ShiftRight(u8, u8),
+    // 8xy7: vx = vy - vx, vf is the inverted borrow.
+    SubReversed(u8, u8),
+    // 8xye: shift left.
+    ShiftLeft(u8, u8),
+    // 9xy0: skip if vx does not equal vy.
+    SkipIfRegNotEqual(u8, u8),
+    // annn: ram_idx = nnn.
+    SetIndex(u16),
+    // bnnn: jump to nnn plus v0 (or vx, given the jump_uses_vx quirk).
+    JumpOffset(u16),
+    // cxnn: vx = random byte & nn.
+    Random(u8, u8),
+    // dxyn: draw a sprite n rows high at vx, vy.
+    Draw(u8, u8, u8),
+    // ex9e: skip if the key in vx is pressed.
+    SkipIfKey(u8),
+    // exa1: skip if the key in vx is not pressed.
+    SkipIfNotKey(u8),
+    // f000 nnnn: ram_idx = the 16-bit word that follows. XO-CHIP only.
+    SetIndexLong,
+    // fn01: select the bitplanes in n. XO-CHIP only.
+    Planes(u8),
+    // f002: load the audio pattern. XO-CHIP only.
+    Audio,
+    // fx07: vx = delay timer.
+    GetDelay(u8),
+    // fx0a: wait for a key press and store it in vx.
+    WaitKey(u8),
+    // fx15: delay timer = vx.
+    SetDelay(u8),
+    // fx18: sound timer = vx.
+    SetSound(u8),
+    // fx1e: ram_idx += vx.
+    AddIndex(u8),
+    // fx29: point ram_idx to the font sprite for vx.
+    Font(u8),
+    // fx30: point ram_idx to the big font sprite for vx.
+    BigFont(u8),
+    // fx33: store the decimal digits of vx.
+    Bcd(u8),
+    // fx3a: pitch = vx. XO-CHIP only.
+    Pitch(u8),
+    // fx55: store v0 to vx in memory.
+    Store(u8),
+    // fx65: load v0 to vx from memory.
+    Load(u8),
+    // fx75: store v0 to vx in the user flags.
+    SaveFlags(u8),
+    // fx85: load v0 to vx from the user flags.
+    LoadFlags(u8),
+}
+
+impl Instruction {
+
+    // Decode an opcode, given the mode the emulator runs in. None if the
+    // opcode is not part of the instruction set for that mode.
+    pub fn decode(opcode: u16, mode: Mode) -> Option<Instruction> {
+        let xo_chip = mode == Mode::XoChip;
+        let x = ((opcode & 0x0f00) >> 8) as u8;
+        let y = ((opcode & 0x00f0) >> 4) as u8;
+        let n = (opcode & 0x000f) as u8;
+        let nn = (opcode & 0x00ff) as u8;
+        let nnn = opcode & 0x0fff;
+        let instruction = match opcode & 0xf000 {
+            0x0000 => match opcode & 0xfff0 {
+                0x00c0 => Instruction::ScrollDown(n),
+                0x00d0 if xo_chip => Instruction::ScrollUp(n),
+                _ => match opcode {
+                    0x00e0 => Instruction::Clear,
+                    0x00ee => Instruction::Return,
+                    0x00fb => Instruction::ScrollRight,
+                    0x00fc => Instruction::ScrollLeft,
+                    0x00fd => Instruction::Exit,
+                    0x00fe => Instruction::Lores,
+                    0x00ff => Instruction::Hires,
+                    _ => return None,
+                },
+            },
+            0x1000 => Instruction::Jump(nnn),
+            0x2000 => Instruction::Call(nnn),
+            0x3000 => Instruction::SkipIfEqual(x, nn),
+            0x4000 => Instruction::SkipIfNotEqual(x, nn),
+            0x5000 => match n {
+                0x0 => Instruction::SkipIfRegEqual(x, y),
+                0x2 if xo_chip => Instruction::SaveRange(x, y),
+                0x3 if xo_chip => Instruction::LoadRange(x, y),
+                _ => return None,
+            },
+            0x6000 => Instruction::Set(x, nn),
+            0x7000 => Instruction::Add(x, nn),
+            0x8000 => match n {
+                0x0 => Instruction::SetReg(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::AddReg(x, y),
+                0x5 => Instruction::Sub(x, y),
+                0x6 => Instruction::ShiftRight(x, y),
+                0x7 => Instruction::SubReversed(x, y),
+                0xe => Instruction::ShiftLeft(x, y),
+                _ => return None,
+            },
+            0x9000 if n == 0 => Instruction::SkipIfRegNotEqual(x, y),
+            0xa000 => Instruction::SetIndex(nnn),
+            0xb000 => Instruction::JumpOffset(nnn),
+            0xc000 => Instruction::Random(x, nn),
+            0xd000 => Instruction::Draw(x, y, n),
+            0xe000 => match nn {
+                0x9e => Instruction::SkipIfKey(x),
+                0xa1 => Instruction::SkipIfNotKey(x),
+                _ => return None,
+            },
+            0xf000 => match nn {
+                0x00 if xo_chip && x == 0 => Instruction::SetIndexLong,
+                0x01 if xo_chip => Instruction::Planes(x),
+                0x02 if xo_chip && x == 0 => Instruction::Audio,
+                0x07 => Instruction::GetDelay(x),
+                0x0a => Instruction::WaitKey(x),
+                0x15 => Instruction::SetDelay(x),
+                0x18 => Instruction::SetSound(x),
+                0x1e => Instruction::AddIndex(x),
+                0x29 => Instruction::Font(x),
+                0x30 => Instruction::BigFont(x),
+                0x33 => Instruction::Bcd(x),
+                0x3a if xo_chip => Instruction::Pitch(x),
+                0x55 => Instruction::Store(x),
+                0x65 => Instruction::Load(x),
+                0x75 => Instruction::SaveFlags(x),
+                0x85 => Instruction::LoadFlags(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    // The number of bytes the instruction takes up in memory.
+    pub fn size(&self) -> usize {
+        match *self {
+            Instruction::SetIndexLong => 4,
+            _ => 2,
+        }
+    }
+
+    // The address a jump or call transfers control to, if known up front.
+    pub fn target(&self) -> Option<u16> {
+        match *self {
+            Instruction::Jump(nnn) | Instruction::Call(nnn) => Some(nnn),
+            _ => None,
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Instruction;
+    use super::super::Mode;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Some(Instruction::Clear),
+                   Instruction::decode(0x00e0, Mode::Standard));
+        assert_eq!(Some(Instruction::Draw(0xa, 0xb, 0x5)),
+                   Instruction::decode(0xdab5, Mode::Standard));
+        assert_eq!(Some(Instruction::SkipIfNotKey(0x3)),
+                   Instruction::decode(0xe3a1, Mode::Standard));
+        assert_eq!(Some(Instruction::ShiftLeft(0x1, 0x2)),
+                   Instruction::decode(0x812e, Mode::Super));
+        assert_eq!(None, Instruction::decode(0x8128, Mode::Standard));
+        assert_eq!(None, Instruction::decode(0xe39f, Mode::Standard));
+        assert_eq!(None, Instruction::decode(0x0123, Mode::Standard));
+    }
+
+    #[test]
+    fn test_decode_xo_chip_only() {
+        for &opcode in &[0x00d4, 0x5122, 0x5123, 0xf000, 0xf201, 0xf002,
+                         0xf13a] {
+            assert_eq!(None, Instruction::decode(opcode, Mode::Standard));
+            assert!(Instruction::decode(opcode, Mode::XoChip).is_some());
+        }
+        assert_eq!(None, Instruction::decode(0xf100, Mode::XoChip));
+        assert_eq!(4, Instruction::decode(0xf000, Mode::XoChip).unwrap().size());
+    }
+
+}
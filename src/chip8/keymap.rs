@@ -0,0 +1,455 @@
+use sdl2::keyboard::Scancode;
+use std::collections::HashMap;
+
+// Keys that can be bound, by name. Letters and digits stand for themselves.
+const NAMED_KEYS: [(&str, Scancode); 52] = [
+    ("escape", Scancode::Escape),
+    ("return", Scancode::Return),
+    ("backspace", Scancode::Backspace),
+    ("delete", Scancode::Delete),
+    ("tab", Scancode::Tab),
+    ("space", Scancode::Space),
+    ("up", Scancode::Up),
+    ("down", Scancode::Down),
+    ("left", Scancode::Left),
+    ("right", Scancode::Right),
+    ("`", Scancode::Grave),
+    ("-", Scancode::Minus),
+    ("=", Scancode::Equals),
+    (",", Scancode::Comma),
+    (".", Scancode::Period),
+    (";", Scancode::Semicolon),
+    ("'", Scancode::Apostrophe),
+    ("/", Scancode::Slash),
+    ("\\", Scancode::Backslash),
+    ("[", Scancode::LeftBracket),
+    ("]", Scancode::RightBracket),
+    ("f1", Scancode::F1),
+    ("f2", Scancode::F2),
+    ("f3", Scancode::F3),
+    ("f4", Scancode::F4),
+    ("f5", Scancode::F5),
+    ("f6", Scancode::F6),
+    ("f7", Scancode::F7),
+    ("f8", Scancode::F8),
+    ("f9", Scancode::F9),
+    ("f10", Scancode::F10),
+    ("f11", Scancode::F11),
+    ("f12", Scancode::F12),
+    ("keypad 0", Scancode::Kp0),
+    ("keypad 1", Scancode::Kp1),
+    ("keypad 2", Scancode::Kp2),
+    ("keypad 3", Scancode::Kp3),
+    ("keypad 4", Scancode::Kp4),
+    ("keypad 5", Scancode::Kp5),
+    ("keypad 6", Scancode::Kp6),
+    ("keypad 7", Scancode::Kp7),
+    ("keypad 8", Scancode::Kp8),
+    ("keypad 9", Scancode::Kp9),
+    ("keypad /", Scancode::KpDivide),
+    ("keypad *", Scancode::KpMultiply),
+    ("keypad -", Scancode::KpMinus),
+    ("keypad +", Scancode::KpPlus),
+    ("keypad enter", Scancode::KpEnter),
+    ("keypad .", Scancode::KpPeriod),
+    ("home", Scancode::Home),
+    ("end", Scancode::End),
+    ("insert", Scancode::Insert),
+];
+
+const DIGITS: [Scancode; 10] = [
+    Scancode::Num0, Scancode::Num1, Scancode::Num2, Scancode::Num3,
+    Scancode::Num4, Scancode::Num5, Scancode::Num6, Scancode::Num7,
+    Scancode::Num8, Scancode::Num9,
+];
+
+// Names by which the layouts can be selected, e.g. from the config file.
+pub const LAYOUT_NAMES: [&str; 3] = ["qwerty", "azerty", "dvorak"];
+
+// The keyboard layout by which letters are named. Keys are found by their
+// position, so the layout decides which key carries each letter.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Dvorak,
+}
+
+impl Layout {
+
+    // Look up a layout by one of the names in LAYOUT_NAMES.
+    pub fn from_name(name: &str) -> Option<Layout> {
+        match name {
+            "qwerty" => Some(Layout::Qwerty),
+            "azerty" => Some(Layout::Azerty),
+            "dvorak" => Some(Layout::Dvorak),
+            _ => None,
+        }
+    }
+
+    // The key carrying each of the letters a to z.
+    fn letters(self) -> [Scancode; 26] {
+        use sdl2::keyboard::Scancode::*;
+        match self {
+            Layout::Qwerty => [
+                A, B, C, D, E, F, G, H, I, J, K, L, M,
+                N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+            ],
+            Layout::Azerty => [
+                Q, B, C, D, E, F, G, H, I, J, K, L, Semicolon,
+                N, O, P, A, R, S, T, U, V, Z, X, Y, W,
+            ],
+            Layout::Dvorak => [
+                A, N, I, H, D, Y, U, J, G, C, V, P, M,
+                L, S, R, X, O, Semicolon, K, F, Period, Comma, B, T, Slash,
+            ],
+        }
+    }
+
+}
+
+// The key with the given name, ignoring case: a letter on the given
+// layout, a digit, or one of the NAMED_KEYS.
+pub fn scancode(name: &str, layout: Layout) -> Option<Scancode> {
+    let name = name.to_lowercase();
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c @ 'a'..='z'), None) =>
+            Some(layout.letters()[c as usize - 'a' as usize]),
+        (Some(c @ '0'..='9'), None) => Some(DIGITS[c as usize - '0' as usize]),
+        _ => NAMED_KEYS.iter()
+            .find(|&&(key_name, _)| key_name == name)
+            .map(|&(_, scancode)| scancode),
+    }
+}
+
+// The name of the key, as scancode takes it.
+fn key_name(scancode: Scancode, layout: Layout) -> Option<String> {
+    if let Some(letter) = layout.letters().iter().position(|&s| s == scancode) {
+        return Some(((b'a' + letter as u8) as char).to_string());
+    }
+    if let Some(digit) = DIGITS.iter().position(|&s| s == scancode) {
+        return Some(digit.to_string());
+    }
+    NAMED_KEYS.iter()
+        .find(|&&(_, named)| named == scancode)
+        .map(|&(name, _)| name.to_string())
+}
+
+// Things the emulator can be told to do from the keyboard or a controller.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    Pause,
+    Reset,
+    // For as long as the key is held.
+    Rewind,
+    FrameAdvance,
+    Slower,
+    Faster,
+    // For as long as the key is held.
+    FastForward,
+    SaveState(u8),
+    LoadState(u8),
+    Screenshot,
+    // Switches to the next palette.
+    NextTheme,
+    // Switches between a window and fullscreen.
+    Fullscreen,
+    // Switches pixels fading out on or off.
+    Phosphor,
+}
+
+impl Action {
+
+    // Look up an action by name, such as "pause" or "save 3".
+    pub fn from_name(name: &str) -> Option<Action> {
+        let slot = |prefix: &str| name.strip_prefix(prefix)
+            .and_then(|slot| slot.parse().ok())
+            .filter(|slot| (1..=9).contains(slot));
+        match name {
+            "quit" => Some(Action::Quit),
+            "pause" => Some(Action::Pause),
+            "reset" => Some(Action::Reset),
+            "rewind" => Some(Action::Rewind),
+            "advance" => Some(Action::FrameAdvance),
+            "slower" => Some(Action::Slower),
+            "faster" => Some(Action::Faster),
+            "fast forward" => Some(Action::FastForward),
+            "screenshot" => Some(Action::Screenshot),
+            "next theme" => Some(Action::NextTheme),
+            "fullscreen" => Some(Action::Fullscreen),
+            "phosphor" => Some(Action::Phosphor),
+            _ => slot("save ").map(Action::SaveState)
+                .or_else(|| slot("load ").map(Action::LoadState)),
+        }
+    }
+
+}
+
+// What a key or button does.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Binding {
+    // Presses the keypad key with the given number.
+    Key(usize),
+    Action(Action),
+}
+
+impl Binding {
+
+    // A keypad key in hex, an action, or "none" for nothing at all.
+    pub fn from_name(name: &str) -> Option<Option<Binding>> {
+        let name = name.to_lowercase();
+        if name == "none" {
+            return Some(None);
+        }
+        match usize::from_str_radix(&name, 16) {
+            Ok(key) if key < 16 => Some(Some(Binding::Key(key))),
+            _ => Action::from_name(&name).map(|action| {
+                Some(Binding::Action(action))
+            }),
+        }
+    }
+
+}
+
+// Keyboard keys, and whether shift is held, to the keypad keys and actions
+// they are bound to. By default the keypad is laid out on the left of the
+// keyboard, in the same place whatever the layout:
+//
+//     1 2 3 c        1 2 3 4
+//     4 5 6 d   ->   q w e r   (on QWERTY)
+//     7 8 9 e        a s d f
+//     a 0 b f        z x c v
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    layout: Layout,
+    bindings: HashMap<(Scancode, bool), Binding>,
+}
+
+impl Default for Keymap {
+
+    fn default() -> Keymap {
+        Keymap::new(Layout::default())
+    }
+
+}
+
+impl Keymap {
+
+    // The default bindings, with letters in later bindings named after the
+    // layout.
+    pub fn new(layout: Layout) -> Keymap {
+        let keypad = [
+            Scancode::X, Scancode::Num1, Scancode::Num2, Scancode::Num3,
+            Scancode::Q, Scancode::W, Scancode::E, Scancode::A,
+            Scancode::S, Scancode::D, Scancode::Z, Scancode::C,
+            Scancode::Num4, Scancode::R, Scancode::F, Scancode::V,
+        ];
+        let actions = [
+            (Scancode::Escape, Action::Quit),
+            (Scancode::Return, Action::Pause),
+            (Scancode::Backspace, Action::Reset),
+            (Scancode::Delete, Action::Reset),
+            (Scancode::Grave, Action::Rewind),
+            (Scancode::Period, Action::FrameAdvance),
+            (Scancode::Minus, Action::Slower),
+            (Scancode::Equals, Action::Faster),
+            (Scancode::Tab, Action::FastForward),
+            (Scancode::F10, Action::NextTheme),
+            (Scancode::F11, Action::Fullscreen),
+            (Scancode::P, Action::Phosphor),
+            (Scancode::F12, Action::Screenshot),
+        ];
+        let slots = [
+            Scancode::F1, Scancode::F2, Scancode::F3, Scancode::F4,
+            Scancode::F5, Scancode::F6, Scancode::F7, Scancode::F8,
+            Scancode::F9,
+        ];
+        let mut bindings = HashMap::new();
+        for (key, &scancode) in keypad.iter().enumerate() {
+            bindings.insert((scancode, false), Binding::Key(key));
+        }
+        for &(scancode, action) in actions.iter() {
+            bindings.insert((scancode, false), Binding::Action(action));
+        }
+        for (slot, &scancode) in (1..).zip(slots.iter()) {
+            bindings.insert((scancode, false),
+                            Binding::Action(Action::LoadState(slot)));
+            bindings.insert((scancode, true),
+                            Binding::Action(Action::SaveState(slot)));
+        }
+        Keymap { layout, bindings }
+    }
+
+    // Bind keyboard keys, by name and optionally prefixed with "shift+", to
+    // keypad keys in hex, to actions, or to "none". For instance:
+    //
+    //     { "up": "5", "p": "pause", "shift+s": "screenshot", "f12": "none" }
+    pub fn bind(&mut self, bindings: &HashMap<String, String>)
+        -> Result<(), String> {
+        for (key, binding) in bindings.iter() {
+            let lower = key.to_lowercase();
+            let (name, shift) = match lower.strip_prefix("shift+") {
+                Some(name) => (name, true),
+                None => (lower.as_str(), false),
+            };
+            let scancode = scancode(name, self.layout)
+                .ok_or_else(|| format!("unknown key {}", key))?;
+            match Binding::from_name(binding) {
+                Some(Some(binding)) =>
+                    self.bindings.insert((scancode, shift), binding),
+                Some(None) => self.bindings.remove(&(scancode, shift)),
+                None => return Err(format!(
+                    "expected keypad key 0 to f, an action or none, got {}",
+                    binding)),
+            };
+        }
+        Ok(())
+    }
+
+    // The name of a key bound to the action, as bind takes it, if any. Of
+    // several keys, keys without shift come first.
+    pub fn key_for(&self, action: Action) -> Option<String> {
+        let &(scancode, shift) = self.bindings.iter()
+            .filter(|&(_, &binding)| binding == Binding::Action(action))
+            .map(|(key, _)| key)
+            .min_by_key(|&&(scancode, shift)| (shift, scancode as i32))?;
+        let name = key_name(scancode, self.layout)?;
+        match shift {
+            true => Some(format!("shift+{}", name)),
+            false => Some(name),
+        }
+    }
+
+    // The state of the keypad, given which keyboard keys are pressed.
+    pub fn keys<F>(&self, pressed: F) -> [bool; 16]
+        where F: Fn(Scancode) -> bool {
+        let mut keys = [false; 16];
+        for (&(scancode, _), binding) in self.bindings.iter() {
+            if let Binding::Key(key) = *binding {
+                keys[key] |= pressed(scancode);
+            }
+        }
+        keys
+    }
+
+    // The action bound to the key. With shift held, keys not bound with
+    // shift act as if it was not held.
+    pub fn action(&self, scancode: Scancode, shift: bool) -> Option<Action> {
+        let binding = self.bindings.get(&(scancode, shift))
+            .or_else(|| self.bindings.get(&(scancode, false)));
+        match binding {
+            Some(&Binding::Action(action)) => Some(action),
+            _ => None,
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{scancode, Action, Keymap, Layout};
+    use sdl2::keyboard::Scancode;
+    use std::collections::HashMap;
+
+    fn bindings(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter()
+            .map(|&(key, binding)| (key.to_string(), binding.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_scancode() {
+        assert_eq!(Some(Scancode::Q), scancode("q", Layout::Qwerty));
+        assert_eq!(Some(Scancode::Q), scancode("Q", Layout::Qwerty));
+        assert_eq!(Some(Scancode::A), scancode("q", Layout::Azerty));
+        assert_eq!(Some(Scancode::X), scancode("q", Layout::Dvorak));
+        assert_eq!(Some(Scancode::Num0), scancode("0", Layout::Dvorak));
+        assert_eq!(Some(Scancode::Kp5), scancode("Keypad 5", Layout::Qwerty));
+        assert_eq!(Some(Scancode::F1), scancode("f1", Layout::Qwerty));
+        assert_eq!(None, scancode("qq", Layout::Qwerty));
+        assert_eq!(None, scancode("", Layout::Qwerty));
+    }
+
+    #[test]
+    fn test_layouts_cover_every_key_once() {
+        for &layout in [Layout::Qwerty, Layout::Azerty, Layout::Dvorak].iter() {
+            let mut letters = layout.letters().to_vec();
+            letters.sort_by_key(|&scancode| scancode as i32);
+            letters.dedup();
+            assert_eq!(26, letters.len());
+        }
+    }
+
+    #[test]
+    fn test_action_from_name() {
+        assert_eq!(Some(Action::Pause), Action::from_name("pause"));
+        assert_eq!(Some(Action::NextTheme), Action::from_name("next theme"));
+        assert_eq!(Some(Action::SaveState(3)), Action::from_name("save 3"));
+        assert_eq!(Some(Action::LoadState(9)), Action::from_name("load 9"));
+        assert_eq!(None, Action::from_name("load 10"));
+        assert_eq!(None, Action::from_name("save"));
+        assert_eq!(None, Action::from_name("dance"));
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+        let keys = keymap.keys(|scancode| scancode == Scancode::W);
+        assert!(keys[5]);
+        assert_eq!(1, keys.iter().filter(|&&pressed| pressed).count());
+        assert_eq!(Some(Action::Quit), keymap.action(Scancode::Escape, false));
+        assert_eq!(Some(Action::LoadState(2)),
+                   keymap.action(Scancode::F2, false));
+        assert_eq!(Some(Action::SaveState(2)),
+                   keymap.action(Scancode::F2, true));
+        // Shift does not get in the way of other actions.
+        assert_eq!(Some(Action::Pause), keymap.action(Scancode::Return, true));
+        assert_eq!(None, keymap.action(Scancode::W, false));
+    }
+
+    #[test]
+    fn test_key_for() {
+        //given
+        let mut keymap = Keymap::new(Layout::Azerty);
+        keymap.bind(&bindings(&[("q", "rewind"), ("shift+1", "pause"),
+                                ("return", "none")]))
+            .unwrap();
+        //when
+        let key_for = |action| keymap.key_for(action);
+        //then
+        assert_eq!(Some("backspace".to_string()), key_for(Action::Reset));
+        assert_eq!(Some("q".to_string()), key_for(Action::Rewind));
+        assert_eq!(Some("shift+1".to_string()), key_for(Action::Pause));
+        assert_eq!(Some("f3".to_string()), key_for(Action::LoadState(3)));
+        assert_eq!(Some("shift+f3".to_string()),
+                   key_for(Action::SaveState(3)));
+    }
+
+    #[test]
+    fn test_bind() {
+        //given
+        let mut keymap = Keymap::new(Layout::Azerty);
+        //when
+        keymap.bind(&bindings(&[("up", "5"), ("a", "pause"),
+                                ("shift+s", "screenshot"), ("escape", "none"),
+                                ("f12", "a")]))
+            .unwrap();
+        //then
+        // Both keys press 5.
+        assert!(keymap.keys(|scancode| scancode == Scancode::Up)[5]);
+        assert!(keymap.keys(|scancode| scancode == Scancode::W)[5]);
+        assert!(keymap.keys(|scancode| scancode == Scancode::F12)[0xa]);
+        // The key labelled a on AZERTY.
+        assert_eq!(Some(Action::Pause), keymap.action(Scancode::Q, false));
+        assert_eq!(Some(Action::Screenshot), keymap.action(Scancode::S, true));
+        assert_eq!(None, keymap.action(Scancode::S, false));
+        assert_eq!(None, keymap.action(Scancode::Escape, false));
+        assert!(keymap.bind(&bindings(&[("up", "10")])).is_err());
+        assert!(keymap.bind(&bindings(&[("nope", "1")])).is_err());
+    }
+
+}
@@ -1,10 +1,34 @@
-pub const GFX_W: usize = 132;
+pub const GFX_W: usize = 128;
 pub const GFX_H: usize = 64;
+pub const NUM_PLANES: usize = 2;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum Mode { Standard, Super }
+// Graphics pixels for a single bitplane, indexed by x and then y.
+pub type Plane = [[bool; GFX_H]; GFX_W];
 
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Mode { Standard, Super, XoChip }
+
+pub mod asm;
+pub mod config;
+pub mod debugger;
+pub mod disasm;
 pub mod emu;
-pub mod metro;
+pub mod gamepad;
+#[cfg(test)]
+mod golden;
+pub mod headless;
+pub mod instruction;
+pub mod keymap;
+pub mod movie;
+pub mod palette;
+pub mod phosphor;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod romdb;
+pub mod scheduler;
+pub mod state;
+pub mod timing;
 pub mod ui;
+pub mod watch;
 pub mod wav;
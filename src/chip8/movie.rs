@@ -0,0 +1,388 @@
+use super::Mode;
+use super::emu::Emu;
+use super::quirks::Quirks;
+use super::timing::Timing;
+use serde_json;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Bumped whenever the layout of a movie changes.
+pub const VERSION: u32 = 3;
+
+// Input from the user that affects the emulator.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    // The state of all keys, one bit per key with key 0 in bit 0.
+    Keys(u16),
+    Reset,
+}
+
+impl Input {
+
+    pub fn keys(keys: &[bool; 16]) -> Input {
+        let mask = keys.iter().enumerate()
+            .filter(|&(_, &pressed)| pressed)
+            .fold(0, |mask, (key, _)| mask | (1 << key));
+        Input::Keys(mask)
+    }
+
+    // Apply the input to the emulator.
+    pub fn apply(self, emu: &mut Emu) {
+        match self {
+            Input::Keys(mask) => for (key, pressed) in
+                emu.keys.iter_mut().enumerate() {
+                *pressed = mask & (1 << key) != 0;
+            },
+            Input::Reset => emu.reset(),
+        }
+    }
+
+}
+
+// Input that arrived just before the cycle with the given number executed.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub cycle: u64,
+    pub input: Input,
+}
+
+// Reasons a movie may be refused.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MovieError {
+    // The movie was recorded by a version of the emulator with a different
+    // movie layout.
+    UnsupportedVersion { found: u32 },
+    // The movie was recorded while running a different rom.
+    RomMismatch { expected: String, found: String },
+    // The movie could not be encoded or decoded.
+    Corrupt(String),
+    // A save state restored to was not saved earlier on in the run of the
+    // movie, so the movie would not play back the same.
+    ForeignState,
+}
+
+impl fmt::Display for MovieError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MovieError::UnsupportedVersion { found } =>
+                write!(f, "Unsupported movie version {} (expected {})",
+                       found, VERSION),
+            MovieError::RomMismatch { ref expected, ref found } =>
+                write!(f, "Movie belongs to a different rom (sha1 {}, \
+                           running {})", found, expected),
+            MovieError::Corrupt(ref msg) => write!(f, "Corrupt movie: {}", msg),
+            MovieError::ForeignState =>
+                write!(f, "Save state is not from the run of the movie"),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+// A recording of all input to the emulator from the moment it started,
+// stamped with the cycle at which it arrived. Along with the rom, the random
+// seed and the settings, that is all it takes to replay a run exactly.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Movie {
+    version: u32,
+    pub rom_hash: String,
+    pub seed: u64,
+    pub mode: Mode,
+    pub quirks: Quirks,
+    pub timing: Timing,
+    // The number of cycles the movie lasts.
+    pub length: u64,
+    // Ordered by cycle.
+    events: Vec<Event>,
+}
+
+impl Movie {
+
+    // Start a movie of the emulator, which must not have run yet.
+    pub fn new(emu: &Emu, mode: Mode, quirks: Quirks) -> Movie {
+        debug_assert_eq!(0, emu.cycles());
+        Movie {
+            version: VERSION,
+            rom_hash: emu.rom_hash(),
+            seed: emu.seed(),
+            mode,
+            quirks,
+            timing: emu.timing(),
+            length: 0,
+            events: vec![Event { cycle: 0, input: Input::keys(&emu.keys) }],
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Movie, Box<dyn Error>> {
+        let movie: Movie = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|err| MovieError::Corrupt(err.to_string()))?;
+        if movie.version != VERSION {
+            return Err(Box::new(
+                MovieError::UnsupportedVersion { found: movie.version }));
+        }
+        Ok(movie)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_vec_pretty(self)
+            .map_err(|err| MovieError::Corrupt(err.to_string()))?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    Recording,
+    // Playing back, with the index of the next event.
+    Playing(usize),
+    // Played back to the end, input is passed on but not recorded.
+    Finished,
+}
+
+// A movie being recorded or played back. When played back, the input from
+// the movie is fed to the emulator at the same cycles it was recorded, and
+// input from the user is ignored. Restoring an earlier state of the
+// emulator, such as from a save state, picks up recording from there.
+pub struct Session {
+    movie: Movie,
+    state: State,
+}
+
+impl Session {
+
+    // Record the emulator, which must not have run yet.
+    pub fn record(emu: &Emu, mode: Mode, quirks: Quirks) -> Session {
+        Session { movie: Movie::new(emu, mode, quirks), state: State::Recording }
+    }
+
+    // Play back the movie on the emulator, which must not have run yet and
+    // be running the same rom. The emulator is seeded and timed as in the
+    // movie.
+    pub fn play(movie: Movie, emu: &mut Emu) -> Result<Session, MovieError> {
+        if movie.rom_hash != emu.rom_hash() {
+            return Err(MovieError::RomMismatch {
+                expected: emu.rom_hash(),
+                found: movie.rom_hash
+            });
+        }
+        debug_assert_eq!(0, emu.cycles());
+        emu.set_seed(movie.seed);
+        emu.set_timing(movie.timing);
+        Ok(Session { movie, state: State::Playing(0) })
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    // Whether the movie was recorded to, and so needs writing out.
+    pub fn recording(&self) -> bool {
+        self.state == State::Recording
+    }
+
+    // Feed the emulator the input due before its next cycle, when playing
+    // back. Returns true when that finishes playback.
+    pub fn before_cycle(&mut self, emu: &mut Emu) -> bool {
+        let mut next = match self.state {
+            State::Playing(next) => next,
+            _ => return false,
+        };
+        let events = &self.movie.events;
+        while next < events.len() && events[next].cycle <= emu.cycles() {
+            events[next].input.apply(emu);
+            next += 1;
+        }
+        self.state = if next == events.len() &&
+                        emu.cycles() >= self.movie.length {
+            State::Finished
+        } else {
+            State::Playing(next)
+        };
+        self.state == State::Finished
+    }
+
+    // Input from the user, applied and recorded unless playing back.
+    pub fn input(&mut self, emu: &mut Emu, input: Input) {
+        match self.state {
+            State::Playing(_) => {},
+            State::Recording => {
+                input.apply(emu);
+                self.movie.events.push(Event { cycle: emu.cycles(), input });
+            },
+            State::Finished => input.apply(emu),
+        }
+    }
+
+    // Check that the emulator may be restored to the saved one, which must
+    // have been saved earlier on in the run of the movie: with the same
+    // seed, and no further along than the emulator is now. A state from
+    // another run would not play back from the start of the movie.
+    pub fn check_state(&self, emu: &Emu, saved: &Emu)
+        -> Result<(), MovieError> {
+        if saved.seed() != self.movie.seed || saved.cycles() > emu.cycles() {
+            return Err(MovieError::ForeignState);
+        }
+        Ok(())
+    }
+
+    // Pick up recording from the current state of the emulator, which was
+    // restored to an earlier point. Input recorded from that point on is
+    // dropped.
+    pub fn rerecord(&mut self, emu: &Emu) {
+        let cycle = emu.cycles();
+        self.movie.events.retain(|event| event.cycle < cycle);
+        self.movie.events.push(Event { cycle, input: Input::keys(&emu.keys) });
+        self.state = State::Recording;
+    }
+
+    // Finish recording at the current cycle of the emulator, returning the
+    // movie.
+    pub fn finish(mut self, emu: &Emu) -> Movie {
+        if self.recording() {
+            self.movie.length = emu.cycles();
+        }
+        self.movie
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Event, Input, Movie, MovieError, Session};
+    use super::super::Mode;
+    use super::super::emu::Emu;
+    use super::super::quirks::Quirks;
+
+    // 200: v0 = random byte
+    // 202: if v1 key then v2 += 1
+    // 204: jump 200
+    const ROM: [u8; 8] = [0xc0, 0xff, 0xe1, 0xa1, 0x72, 0x01, 0x12, 0x00];
+
+    fn clone(emu: &Emu) -> Emu {
+        ::bincode::deserialize(&::bincode::serialize(emu).unwrap()).unwrap()
+    }
+
+    fn emu() -> Emu {
+        let mut emu = Emu::new(Quirks::default());
+        emu.load_rom(ROM.to_vec()).unwrap();
+        emu
+    }
+
+    // Run for the given number of cycles, pressing and releasing key 0 in
+    // turn every 7 cycles.
+    fn record(emu: &mut Emu, session: &mut Session, cycles: u64) {
+        for _ in 0..cycles {
+            session.before_cycle(emu);
+            if emu.cycles().is_multiple_of(7) {
+                let keys = [emu.cycles().is_multiple_of(14); 16];
+                session.input(emu, Input::keys(&keys));
+            }
+            emu.step().unwrap();
+        }
+    }
+
+    fn play(emu: &mut Emu, session: &mut Session, cycles: u64) {
+        for _ in 0..cycles {
+            session.before_cycle(emu);
+            // Ignored during playback.
+            session.input(emu, Input::Reset);
+            emu.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_input_keys() {
+        let mut keys = [false; 16];
+        keys[0] = true;
+        keys[0xf] = true;
+        assert_eq!(Input::Keys(0x8001), Input::keys(&keys));
+        let mut emu = emu();
+        Input::Keys(0x8001).apply(&mut emu);
+        assert_eq!(keys, emu.keys);
+    }
+
+    #[test]
+    fn test_playback_matches_recording() {
+        //given
+        let mut emu1 = emu();
+        let mut session = Session::record(&emu1, Mode::Standard,
+                                          Quirks::default());
+        record(&mut emu1, &mut session, 100);
+        session.input(&mut emu1, Input::Reset);
+        record(&mut emu1, &mut session, 100);
+        let movie = session.finish(&emu1);
+        assert_eq!(200, movie.length);
+        //when
+        let mut emu2 = emu();
+        let mut session = Session::play(movie, &mut emu2).unwrap();
+        play(&mut emu2, &mut session, 200);
+        //then
+        assert!(session.before_cycle(&mut emu2));
+        assert!(!session.recording());
+        assert_eq!(emu1.v(), emu2.v());
+        assert_eq!(emu1.pc(), emu2.pc());
+    }
+
+    #[test]
+    fn test_rerecord() {
+        //given
+        let mut emu = emu();
+        let mut session = Session::record(&emu, Mode::Standard,
+                                          Quirks::default());
+        record(&mut emu, &mut session, 30);
+        let saved = clone(&emu);
+        record(&mut emu, &mut session, 30);
+        //when
+        emu.restore(saved);
+        session.rerecord(&emu);
+        //then
+        let movie = session.finish(&emu);
+        assert_eq!(30, movie.length);
+        assert!(movie.events.iter().all(|event| event.cycle <= 30));
+        assert_eq!(Some(&Event { cycle: 30, input: Input::keys(&emu.keys) }),
+                   movie.events.last());
+    }
+
+    #[test]
+    fn test_check_state() {
+        //given
+        let mut emu = emu();
+        emu.set_seed(1);
+        let mut session = Session::record(&emu, Mode::Standard,
+                                          Quirks::default());
+        record(&mut emu, &mut session, 30);
+        let earlier = clone(&emu);
+        record(&mut emu, &mut session, 30);
+        let mut later = clone(&emu);
+        let mut other = self::emu();
+        other.set_seed(2);
+        for _ in 0..30 {
+            later.step().unwrap();
+            other.step().unwrap();
+        }
+        //when
+        let check = |saved| session.check_state(&emu, saved);
+        //then
+        assert_eq!(Ok(()), check(&earlier));
+        assert_eq!(Err(MovieError::ForeignState), check(&later));
+        assert_eq!(Err(MovieError::ForeignState), check(&other));
+    }
+
+    #[test]
+    fn test_rejects_other_rom() {
+        let movie = Movie::new(&emu(), Mode::Standard, Quirks::default());
+        let mut emu = Emu::new(Quirks::default());
+        emu.load_rom(vec![0x12, 0x00]).unwrap();
+        match Session::play(movie, &mut emu) {
+            Err(MovieError::RomMismatch { .. }) => {},
+            _ => panic!("expected rom mismatch"),
+        }
+    }
+
+}
@@ -0,0 +1,144 @@
+// A color as red, green and blue.
+pub type Rgb = [u8; 3];
+
+// Names by which the built-in themes can be selected, e.g. from the command
+// line. Cycled through in this order.
+pub const THEME_NAMES: [&str; 6] =
+    ["default", "octo", "green", "amber", "lcd", "contrast"];
+
+// The colors of the screen, indexed by the combination of bitplanes a
+// pixel is set in: none, the first, the second or both. Only XO-CHIP makes
+// use of the second bitplane.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Palette(pub [Rgb; 4]);
+
+impl Default for Palette {
+
+    fn default() -> Palette {
+        Palette([[0x1c, 0x28, 0x41], [0xff, 0xff, 0xff],
+                 [0x5b, 0x8b, 0xd9], [0xa9, 0xc4, 0xef]])
+    }
+
+}
+
+impl Palette {
+
+    // Look up a theme by one of the names in THEME_NAMES.
+    pub fn theme(name: &str) -> Option<Palette> {
+        match name {
+            "default" => Some(Palette::default()),
+            // The colors Octo starts with.
+            "octo" => Some(Palette([[0x99, 0x66, 0x00], [0xff, 0xcc, 0x00],
+                                    [0xff, 0x66, 0x00], [0x66, 0x22, 0x00]])),
+            // A green phosphor monitor.
+            "green" => Some(Palette([[0x0a, 0x14, 0x0a], [0x33, 0xff, 0x66],
+                                     [0x1a, 0x80, 0x33], [0x99, 0xff, 0xb3]])),
+            // An amber phosphor monitor.
+            "amber" => Some(Palette([[0x1a, 0x0f, 0x00], [0xff, 0xb0, 0x00],
+                                     [0x80, 0x58, 0x00], [0xff, 0xd8, 0x80]])),
+            // The grey LCD of the HP48 calculators.
+            "lcd" => Some(Palette([[0x9e, 0xa8, 0x8c], [0x28, 0x2c, 0x24],
+                                   [0x6a, 0x70, 0x5c], [0x48, 0x4e, 0x40]])),
+            "contrast" => Some(Palette([[0x00, 0x00, 0x00],
+                                        [0xff, 0xff, 0xff],
+                                        [0xff, 0xff, 0x00],
+                                        [0x00, 0xff, 0xff]])),
+            _ => None,
+        }
+    }
+
+    // The name of the theme this palette is, if any.
+    pub fn theme_name(&self) -> Option<&'static str> {
+        THEME_NAMES.iter()
+            .find(|&&name| Palette::theme(name) == Some(*self))
+            .cloned()
+    }
+
+    // The palettes to cycle through: this one, followed by every theme
+    // other than this one.
+    pub fn cycle(self) -> Vec<Palette> {
+        let themes = THEME_NAMES.iter()
+            .filter_map(|&name| Palette::theme(name))
+            .filter(|&theme| theme != self);
+        Some(self).into_iter().chain(themes).collect()
+    }
+
+    // Replace colors of this palette with colors in hex, such as "#1c2841".
+    // Fewer than four colors may be given, the rest being kept.
+    pub fn with_colors<S: AsRef<str>>(self, colors: &[S])
+        -> Result<Palette, String> {
+        if colors.is_empty() || colors.len() > 4 {
+            return Err("expected 1 to 4 colors".to_string());
+        }
+        let mut palette = self;
+        for (rgb, color) in palette.0.iter_mut().zip(colors.iter()) {
+            *rgb = parse_color(color.as_ref()).ok_or_else(|| {
+                format!("expected color as #rrggbb, got {}", color.as_ref())
+            })?;
+        }
+        Ok(palette)
+    }
+
+}
+
+// Read a color in hex, with or without a leading #.
+pub fn parse_color(color: &str) -> Option<Rgb> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{parse_color, Palette, THEME_NAMES};
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(Some([0x1c, 0x28, 0x41]), parse_color("#1c2841"));
+        assert_eq!(Some([0xff, 0x00, 0xAB]), parse_color("FF00ab"));
+        assert_eq!(None, parse_color("#1c284"));
+        assert_eq!(None, parse_color("#1c284g"));
+        assert_eq!(None, parse_color("#1c28é"));
+    }
+
+    #[test]
+    fn test_with_colors() {
+        let amber = Palette::theme("amber").unwrap();
+        let palette = amber.with_colors(&["#000000", "#00ff00"]).unwrap();
+        assert_eq!([0, 0, 0], palette.0[0]);
+        assert_eq!([0, 0xff, 0], palette.0[1]);
+        assert_eq!(amber.0[2..], palette.0[2..]);
+        assert!(amber.with_colors::<&str>(&[]).is_err());
+        assert!(amber.with_colors(&["red"]).is_err());
+    }
+
+    #[test]
+    fn test_themes() {
+        for &name in THEME_NAMES.iter() {
+            let theme = Palette::theme(name).unwrap();
+            assert_eq!(Some(name), theme.theme_name());
+        }
+        assert_eq!(None, Palette::theme("pink"));
+        let custom = Palette::default().with_colors(&["#123456"]).unwrap();
+        assert_eq!(None, custom.theme_name());
+    }
+
+    #[test]
+    fn test_cycle() {
+        let custom = Palette::default().with_colors(&["#123456"]).unwrap();
+        let cycle = custom.cycle();
+        assert_eq!(THEME_NAMES.len() + 1, cycle.len());
+        assert_eq!(custom, cycle[0]);
+        assert_eq!(Some("default"), cycle[1].theme_name());
+        // A theme comes first, and only once.
+        let cycle = Palette::theme("amber").unwrap().cycle();
+        assert_eq!(THEME_NAMES.len(), cycle.len());
+        assert_eq!(Some("amber"), cycle[0].theme_name());
+        assert_eq!(Some("default"), cycle[1].theme_name());
+    }
+
+}
@@ -0,0 +1,142 @@
+use super::palette::Rgb;
+use std::time::Duration;
+
+// How long pixels take to fade, unless told otherwise.
+pub const DEFAULT_DECAY_MS: u64 = 120;
+
+// The part of its glow a pixel has left once the decay time is up.
+const GLOW_LEFT: f64 = 0.1;
+
+// Fades pixels out over a decay time rather than at once, as the phosphor
+// of a CRT does. Games draw sprites by XOR, erasing and drawing them again
+// from one frame to the next, which makes them flicker. With pixels going
+// dark lingering for a while, they flicker a lot less.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Phosphor {
+    decay: Duration,
+    // The color shown for each pixel, as last drawn.
+    shown: Vec<[f64; 3]>,
+}
+
+impl Phosphor {
+
+    pub fn new(decay: Duration) -> Phosphor {
+        Phosphor { decay, shown: Vec::new() }
+    }
+
+    // Blend a frame, the color of each pixel, with the frame shown the given
+    // time before. Pixels lit up show at once, while pixels gone back to the
+    // background fade towards it. Returns whether any pixel is still fading.
+    pub fn apply(&mut self, frame: &mut [Rgb], background: Rgb,
+                 elapsed: Duration) -> bool {
+        // Such as when the resolution changes.
+        if self.shown.len() != frame.len() {
+            self.shown = frame.iter().map(|&rgb| to_f64(rgb)).collect();
+            return false;
+        }
+        let decay = self.decay.as_secs_f64();
+        let kept = match decay > 0.0 {
+            true => GLOW_LEFT.powf(elapsed.as_secs_f64() / decay),
+            false => 0.0,
+        };
+        let mut fading = false;
+        for (rgb, shown) in frame.iter_mut().zip(self.shown.iter_mut()) {
+            let target = to_f64(*rgb);
+            if *rgb != background {
+                *shown = target;
+                continue;
+            }
+            for (shown, target) in shown.iter_mut().zip(target.iter()) {
+                *shown = target + (*shown - target) * kept;
+                // Close enough not to tell apart.
+                if (*shown - target).abs() < 1.0 {
+                    *shown = *target;
+                }
+            }
+            fading |= *shown != target;
+            *rgb = [shown[0].round() as u8, shown[1].round() as u8,
+                    shown[2].round() as u8];
+        }
+        fading
+    }
+
+}
+
+fn to_f64(rgb: Rgb) -> [f64; 3] {
+    [rgb[0] as f64, rgb[1] as f64, rgb[2] as f64]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Phosphor;
+    use std::time::Duration;
+
+    const BLACK: [u8; 3] = [0, 0, 0];
+    const WHITE: [u8; 3] = [0xff, 0xff, 0xff];
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_lit_pixels_show_at_once() {
+        //given
+        let mut phosphor = Phosphor::new(ms(100));
+        phosphor.apply(&mut [BLACK, BLACK], BLACK, ms(0));
+        //when
+        let mut frame = [WHITE, BLACK];
+        let fading = phosphor.apply(&mut frame, BLACK, ms(16));
+        //then
+        assert_eq!([WHITE, BLACK], frame);
+        assert!(!fading);
+    }
+
+    #[test]
+    fn test_dark_pixels_fade() {
+        //given
+        let mut phosphor = Phosphor::new(ms(100));
+        phosphor.apply(&mut [WHITE, BLACK], BLACK, ms(0));
+        //when
+        let mut half = [BLACK, BLACK];
+        let fading = phosphor.apply(&mut half, BLACK, ms(50));
+        let mut decayed = [BLACK, BLACK];
+        phosphor.apply(&mut decayed, BLACK, ms(25));
+        let mut gone = [BLACK, BLACK];
+        let still_fading = phosphor.apply(&mut gone, BLACK, ms(200));
+        //then
+        // A third of the glow is left halfway through.
+        assert_eq!([[81, 81, 81], BLACK], half);
+        assert!(fading);
+        assert_eq!([[45, 45, 45], BLACK], decayed);
+        assert_eq!([BLACK, BLACK], gone);
+        assert!(!still_fading);
+    }
+
+    #[test]
+    fn test_fades_towards_the_background() {
+        //given
+        let background = [0x9e, 0xa8, 0x8c];
+        let mut phosphor = Phosphor::new(ms(100));
+        phosphor.apply(&mut [BLACK], background, ms(0));
+        //when
+        let mut frame = [background];
+        phosphor.apply(&mut frame, background, ms(100));
+        //then
+        assert_eq!([[0x8e, 0x97, 0x7e]], frame);
+    }
+
+    #[test]
+    fn test_restarts_on_resolution_change() {
+        //given
+        let mut phosphor = Phosphor::new(ms(100));
+        phosphor.apply(&mut [WHITE], BLACK, ms(0));
+        //when
+        let mut frame = [BLACK, BLACK];
+        let fading = phosphor.apply(&mut frame, BLACK, ms(16));
+        //then
+        assert_eq!([BLACK, BLACK], frame);
+        assert!(!fading);
+    }
+
+}
@@ -0,0 +1,139 @@
+// Over the years the various CHIP-8 interpreters disagreed on the finer
+// details of a handful of opcodes. ROMs tend to rely on the behaviour of the
+// interpreter they were written for, so the emulator must be told which set
+// of behaviours (quirks) to follow. See https://chip8.gulrak.net/ for an
+// overview of the differences between platforms.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Quirks {
+    // 8xy6 and 8xye shift vy and store the result in vx, instead of shifting
+    // vx in place.
+    pub shift_uses_vy: bool,
+    // Where fx55 and fx65 leave ram_idx.
+    pub load_store_idx: LoadStoreIdx,
+    // bnnn is interpreted as bxnn: jump to xnn plus vx, instead of nnn
+    // plus v0.
+    pub jump_uses_vx: bool,
+    // 8xy1, 8xy2 and 8xy3 reset vf to 0.
+    pub logic_resets_vf: bool,
+    // dxyn clips sprites at the screen edges instead of wrapping them around
+    // to the opposite side.
+    pub clip_sprites: bool,
+    // dxyn waits for the next 60Hz vertical blank before drawing, which
+    // limits drawing to one sprite per frame.
+    pub display_wait: bool,
+}
+
+// Where fx55 and fx65 leave ram_idx, having stored or loaded v0 to vx.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LoadStoreIdx {
+    // Where it was.
+    Unchanged,
+    // Pointing at the last address stored or loaded, as CHIP-48 and
+    // SUPER-CHIP 1.0 do.
+    PlusX,
+    // Pointing just past the last address stored or loaded, as the COSMAC
+    // VIP does.
+    PlusXPlusOne,
+}
+
+// Names by which the presets can be selected, e.g. from the command line.
+pub const PRESET_NAMES: [&str; 6] =
+    ["vip", "chip48", "schip1.0", "schip1.1", "xochip", "modern"];
+
+impl Quirks {
+
+    // The original interpreter for the COSMAC VIP (1977).
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_idx: LoadStoreIdx::PlusXPlusOne,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+
+    // CHIP-48 for the HP-48 graphing calculators (1990).
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_idx: LoadStoreIdx::PlusX,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    // SUPER-CHIP 1.0 for the HP-48 graphing calculators (1991).
+    pub fn schip_1_0() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_idx: LoadStoreIdx::PlusX,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    // SUPER-CHIP 1.1 for the HP-48 graphing calculators (1991).
+    pub fn schip_1_1() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_idx: LoadStoreIdx::Unchanged,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    // XO-CHIP, as implemented by the Octo development environment (2014).
+    pub fn xo_chip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_idx: LoadStoreIdx::PlusXPlusOne,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+
+    // Follows the most recent descriptions of the instruction set, which is
+    // what a majority of programmers had in mind when writing their ROMs.
+    // This is the behaviour the emulator has always had.
+    pub fn modern() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_idx: LoadStoreIdx::Unchanged,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+
+    // Look up a preset by one of the names in PRESET_NAMES.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "vip" => Some(Quirks::cosmac_vip()),
+            "chip48" => Some(Quirks::chip48()),
+            "schip1.0" => Some(Quirks::schip_1_0()),
+            "schip1.1" => Some(Quirks::schip_1_1()),
+            "xochip" => Some(Quirks::xo_chip()),
+            "modern" => Some(Quirks::modern()),
+            _ => None,
+        }
+    }
+
+}
+
+impl Default for Quirks {
+
+    fn default() -> Self {
+        Quirks::modern()
+    }
+}
@@ -0,0 +1,170 @@
+use super::emu::Emu;
+use bincode;
+use std::collections::VecDeque;
+
+// Snapshots taken per second, one for each timer update.
+pub const SNAPSHOTS_PER_SECOND: usize = 60;
+
+// Keeps the recent history of the emulator as a ring buffer of snapshots,
+// so that it can be played backwards. The oldest snapshots are dropped once
+// either the number of snapshots or the memory they take up exceeds its
+// limit.
+pub struct Rewind {
+    snapshots: VecDeque<Vec<u8>>,
+    // Total size of the snapshots in bytes.
+    size: usize,
+    max_snapshots: usize,
+    max_size: usize,
+}
+
+impl Rewind {
+
+    // Create a buffer holding up to the given number of seconds of history,
+    // within the given number of bytes.
+    pub fn new(seconds: usize, max_size: usize) -> Self {
+        Rewind {
+            snapshots: VecDeque::new(),
+            size: 0,
+            max_snapshots: seconds * SNAPSHOTS_PER_SECOND,
+            max_size,
+        }
+    }
+
+    // Take a snapshot of the emulator.
+    pub fn push(&mut self, emu: &Emu) {
+        if self.max_snapshots == 0 {
+            return;
+        }
+        // Most of ram is typically left zeroed, which compresses well.
+        let snapshot = pack(&bincode::serialize(emu)
+                                .expect("emulator state is serialisable"));
+        self.size += snapshot.len();
+        self.snapshots.push_back(snapshot);
+        while self.snapshots.len() > self.max_snapshots ||
+              (self.size > self.max_size && self.snapshots.len() > 1) {
+            if let Some(oldest) = self.snapshots.pop_front() {
+                self.size -= oldest.len();
+            }
+        }
+    }
+
+    // Restore the emulator to the most recent snapshot and drop it. Returns
+    // false when there is no history left.
+    pub fn pop(&mut self, emu: &mut Emu) -> bool {
+        let snapshot = match self.snapshots.pop_back() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        self.size -= snapshot.len();
+        let saved = bincode::deserialize(&unpack(&snapshot))
+            .expect("snapshot taken from emulator state");
+        emu.restore(saved);
+        true
+    }
+
+}
+
+// Compress data using the PackBits run-length encoding. Each run starts with
+// a header byte n: for n < 128, n + 1 literal bytes follow; otherwise the
+// single byte that follows repeats 257 - n times.
+fn pack(data: &[u8]) -> Vec<u8> {
+    let mut packed = Vec::new();
+    let mut idx = 0;
+    while idx < data.len() {
+        let run = data[idx..].iter().take(128)
+            .take_while(|&&byte| byte == data[idx])
+            .count();
+        if run >= 3 {
+            packed.push((257 - run) as u8);
+            packed.push(data[idx]);
+            idx += run;
+            continue;
+        }
+        // Gather literals up to the next run of three identical bytes.
+        let start = idx;
+        while idx < data.len() && idx - start < 128 {
+            if idx + 2 < data.len() &&
+               data[idx] == data[idx + 1] && data[idx] == data[idx + 2] {
+                break;
+            }
+            idx += 1;
+        }
+        packed.push((idx - start - 1) as u8);
+        packed.extend_from_slice(&data[start..idx]);
+    }
+    packed
+}
+
+fn unpack(packed: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut idx = 0;
+    while idx < packed.len() {
+        let n = packed[idx] as usize;
+        if n < 128 {
+            data.extend_from_slice(&packed[idx + 1..idx + n + 2]);
+            idx += n + 2;
+        } else {
+            data.extend(std::iter::repeat_n(packed[idx + 1], 257 - n));
+            idx += 2;
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{pack, unpack, Rewind};
+    use super::super::emu::Emu;
+    use super::super::quirks::Quirks;
+
+    #[test]
+    fn test_pack_round_trip() {
+        let mut data = vec![0; 1000];
+        data.extend((0..300).map(|n| n as u8));
+        data.extend(&[1, 1, 2, 2, 2, 3]);
+        let packed = pack(&data);
+        assert!(packed.len() < 400);
+        assert_eq!(data, unpack(&packed));
+        assert_eq!(Vec::<u8>::new(), unpack(&pack(&[])));
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut emu = Emu::new(Quirks::default());
+        // 200: v0 += 1
+        // 202: jump 200
+        emu.load_rom(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut rewind = Rewind::new(1, 1 << 20);
+        for _ in 0..3 {
+            rewind.push(&emu);
+            emu.execute_cycle().unwrap();
+            emu.execute_cycle().unwrap();
+        }
+        assert_eq!(3, emu.v()[0]);
+        assert!(rewind.pop(&mut emu));
+        assert_eq!(2, emu.v()[0]);
+        assert!(rewind.pop(&mut emu));
+        assert!(rewind.pop(&mut emu));
+        assert_eq!(0, emu.v()[0]);
+        assert!(!rewind.pop(&mut emu));
+    }
+
+    #[test]
+    fn test_history_limits() {
+        let mut emu = Emu::new(Quirks::default());
+        let mut rewind = Rewind::new(1, 1 << 20);
+        for _ in 0..100 {
+            rewind.push(&emu);
+        }
+        let mut count = 0;
+        while rewind.pop(&mut emu) { count += 1; }
+        assert_eq!(60, count);
+        let mut rewind = Rewind::new(1, 1);
+        rewind.push(&emu);
+        rewind.push(&emu);
+        assert!(rewind.pop(&mut emu));
+        assert!(!rewind.pop(&mut emu));
+    }
+
+}
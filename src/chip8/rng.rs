@@ -0,0 +1,71 @@
+extern crate rand;
+
+// The source of random numbers for cxnn. Given the same seed, it produces
+// the same sequence, so runs can be reproduced exactly. Its state is part
+// of the emulator state, and is saved along with it.
+//
+// Uses the xorshift64* generator, which is small, fast and more than random
+// enough for games.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero, and similar seeds should not give
+        // similar sequences, so spread the seed out with splitmix64.
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        Rng { seed, state: if z == 0 { 1 } else { z } }
+    }
+
+    // Seed from the thread rng, for when reproducibility is not needed.
+    pub fn from_entropy() -> Self {
+        Rng::new(rand::random::<u64>())
+    }
+
+    // The seed the generator started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        // The high bits are the most random.
+        (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Rng;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let seq_a: Vec<u8> = (0..32).map(|_| a.next_u8()).collect();
+        let seq_b: Vec<u8> = (0..32).map(|_| b.next_u8()).collect();
+        let seq_c: Vec<u8> = (0..32).map(|_| c.next_u8()).collect();
+        assert_eq!(seq_a, seq_b);
+        assert!(seq_a != seq_c);
+        assert_eq!(42, a.seed());
+    }
+
+    #[test]
+    fn test_zero_seed() {
+        let mut rng = Rng::new(0);
+        assert!((0..32).any(|_| rng.next_u8() != 0));
+    }
+
+}
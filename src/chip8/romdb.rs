@@ -0,0 +1,302 @@
+use super::Mode;
+use super::config;
+use super::gamepad::Buttons;
+use super::keymap::Keymap;
+use super::palette::{self, Palette};
+use super::quirks::{self, Quirks};
+use super::timing::{self, Timing};
+use serde_json;
+use sha1_smol::Sha1;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+
+// The database shipped with the emulator, covering the roms in roms/.
+const BUNDLED: &str = include_str!("romdb.json");
+
+// Names by which the platforms can be selected, e.g. from the command line.
+pub const PLATFORM_NAMES: [&str; 3] = ["chip8", "schip", "xochip"];
+
+// The platform a rom was written for.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Platform {
+    #[serde(rename = "chip8")]
+    Chip8,
+    #[serde(rename = "schip")]
+    Schip,
+    #[serde(rename = "xochip")]
+    XoChip,
+}
+
+impl Platform {
+
+    // Look up a platform by one of the names in PLATFORM_NAMES.
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match name {
+            "chip8" => Some(Platform::Chip8),
+            "schip" => Some(Platform::Schip),
+            "xochip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => PLATFORM_NAMES[0],
+            Platform::Schip => PLATFORM_NAMES[1],
+            Platform::XoChip => PLATFORM_NAMES[2],
+        }
+    }
+
+    // The mode to start the emulator in.
+    pub fn mode(self) -> Mode {
+        match self {
+            Platform::Chip8 | Platform::Schip => Mode::Standard,
+            Platform::XoChip => Mode::XoChip,
+        }
+    }
+
+}
+
+// What is known about a rom, and the settings it plays best with. Settings
+// left out fall back to the defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RomInfo {
+    pub title: String,
+    pub author: Option<String>,
+    pub platform: Option<Platform>,
+    // One of the quirks::PRESET_NAMES.
+    pub quirks: Option<String>,
+    // One of the timing::TIMING_NAMES.
+    pub timing: Option<String>,
+    // Given fixed timing.
+    pub cycles_per_frame: Option<u64>,
+    // One of the palette::THEME_NAMES.
+    pub theme: Option<String>,
+    // Up to four colors in hex, in place of those of the theme, see
+    // Palette::with_colors.
+    pub colors: Option<Vec<String>>,
+    // Keypad keys and actions by keyboard key, on top of the user's, see
+    // Keymap::bind.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub bindings: HashMap<String, String>,
+    // Keypad keys and actions by controller button, on top of the user's,
+    // see Buttons::bind.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub buttons: HashMap<String, String>,
+}
+
+impl RomInfo {
+
+    pub fn quirks(&self) -> Option<Quirks> {
+        self.quirks.as_ref().and_then(|name| Quirks::preset(name))
+    }
+
+    pub fn timing(&self) -> Option<Timing> {
+        let timing = self.timing.as_ref()
+            .and_then(|name| Timing::from_name(name));
+        timing::with_cycles_per_frame(timing, self.cycles_per_frame)
+    }
+
+    // The theme and colors of the rom, if any, on top of the given palette.
+    pub fn palette(&self, palette: Palette) -> Palette {
+        let palette = self.theme.as_ref()
+            .and_then(|name| Palette::theme(name))
+            .unwrap_or(palette);
+        self.colors.as_ref()
+            .and_then(|colors| palette.with_colors(colors).ok())
+            .unwrap_or(palette)
+    }
+
+    // Names that do not match a preset, timing model, theme or key.
+    fn check(&self) -> Result<(), String> {
+        if self.quirks.is_some() && self.quirks().is_none() {
+            return Err(format!("{}: expected quirks, one of: {}", self.title,
+                               quirks::PRESET_NAMES.join(", ")));
+        }
+        let timing = self.timing.as_ref().map(|name| Timing::from_name(name));
+        if timing == Some(None) {
+            return Err(format!("{}: expected timing, one of: {}", self.title,
+                               timing::TIMING_NAMES.join(", ")));
+        }
+        if self.cycles_per_frame == Some(0) {
+            return Err(format!("{}: expected cycles per frame above 0",
+                               self.title));
+        }
+        let theme = self.theme.as_ref().map(|name| Palette::theme(name));
+        if theme == Some(None) {
+            return Err(format!("{}: expected theme, one of: {}", self.title,
+                               palette::THEME_NAMES.join(", ")));
+        }
+        if let Some(ref colors) = self.colors {
+            Palette::default().with_colors(colors)
+                .map_err(|err| format!("{}: {}", self.title, err))?;
+        }
+        Keymap::default().bind(&self.bindings)
+            .and_then(|_| Buttons::default().bind(&self.buttons))
+            .map_err(|err| format!("{}: {}", self.title, err))
+    }
+
+}
+
+// The SHA-1 of a rom, in hex, by which it is looked up.
+pub fn rom_hash(rom: &[u8]) -> String {
+    Sha1::from(rom).digest().to_string()
+}
+
+// Information on roms, by the SHA-1 of the rom. Stored as a JSON object
+// from hash to RomInfo, e.g.
+//
+//     {
+//       "5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b": {
+//         "title": "Space Invaders",
+//         "author": "David Winter",
+//         "platform": "chip8",
+//         "quirks": "modern"
+//       }
+//     }
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomDb {
+    entries: HashMap<String, RomInfo>,
+}
+
+impl RomDb {
+
+    pub fn parse(json: &str) -> Result<RomDb, Box<dyn Error>> {
+        let entries: HashMap<String, RomInfo> = serde_json::from_str(json)?;
+        for info in entries.values() {
+            info.check()?;
+        }
+        Ok(RomDb { entries })
+    }
+
+    pub fn bundled() -> RomDb {
+        RomDb::parse(BUNDLED).expect("bundled rom database is valid")
+    }
+
+    // The bundled database, with entries replaced or added by those in
+    // romdb.json in the config directory, if any.
+    pub fn load() -> Result<RomDb, Box<dyn Error>> {
+        let mut db = RomDb::bundled();
+        let path = match config::config_dir() {
+            Some(dir) => dir.join("romdb.json"),
+            None => return Ok(db),
+        };
+        match fs::read_to_string(&path) {
+            Ok(json) => db.merge(RomDb::parse(&json)
+                .map_err(|err| format!("{}: {}", path.display(), err))?),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => return Err(Box::new(err)),
+        }
+        Ok(db)
+    }
+
+    // Take the entries of the other database over those of this one.
+    pub fn merge(&mut self, other: RomDb) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn get(&self, rom_hash: &str) -> Option<&RomInfo> {
+        self.entries.get(rom_hash)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{rom_hash, Platform, RomDb};
+    use super::super::palette::Palette;
+    use super::super::quirks::Quirks;
+    use super::super::timing::Timing;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_bundled_covers_roms() {
+        let db = RomDb::bundled();
+        let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+        for entry in fs::read_dir(roms).unwrap() {
+            let path = entry.unwrap().path();
+            let hash = rom_hash(&fs::read(&path).unwrap());
+            assert!(db.get(&hash).is_some(), "{} missing", path.display());
+        }
+        let invaders = db.get("5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b")
+            .unwrap();
+        assert_eq!("Space Invaders", invaders.title);
+        assert_eq!(Some(Platform::Chip8), invaders.platform);
+        assert_eq!(Some(Quirks::modern()), invaders.quirks());
+    }
+
+    #[test]
+    fn test_merge() {
+        //given
+        let mut db = RomDb::bundled();
+        let local = RomDb::parse(r#"{
+            "5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b": {
+                "title": "Invaders", "quirks": "vip", "timing": "vip"
+            },
+            "00": { "title": "Mine", "cycles_per_frame": 20,
+                    "bindings": { "space": "5" }, "buttons": { "a": "5" } }
+        }"#).unwrap();
+        //when
+        db.merge(local);
+        //then
+        let invaders = db.get("5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b")
+            .unwrap();
+        assert_eq!("Invaders", invaders.title);
+        assert_eq!(None, invaders.platform);
+        assert_eq!(Some(Quirks::cosmac_vip()), invaders.quirks());
+        assert_eq!(Some(Timing::CosmacVip), invaders.timing());
+        assert_eq!(Some(Timing::Fixed(20)), db.get("00").unwrap().timing());
+        assert!(db.get("f13766c14aeb02ad8d4d103cb5eadd282d20cddc").is_some());
+    }
+
+    #[test]
+    fn test_platform_names() {
+        for &name in super::PLATFORM_NAMES.iter() {
+            assert_eq!(name, Platform::from_name(name).unwrap().name());
+        }
+        assert_eq!(None, Platform::from_name("vip"));
+    }
+
+    #[test]
+    fn test_rejects_unknown_names() {
+        assert!(RomDb::parse(r#"{"00": {"title": "x", "quirks": "y"}}"#)
+                .is_err());
+        assert!(RomDb::parse(r#"{"00": {"title": "x", "timing": "y"}}"#)
+                .is_err());
+        assert!(RomDb::parse(r#"{"00": {"platform": "chip8"}}"#).is_err());
+        assert!(RomDb::parse(r#"{"00": {"title": "x", 
+                                        "bindings": {"space": "y"}}}"#)
+                .is_err());
+        assert!(RomDb::parse(r#"{"00": {"title": "x", 
+                                        "buttons": {"a": "y"}}}"#)
+                .is_err());
+        assert!(RomDb::parse(r#"{"00": {"title": "x", "theme": "y"}}"#)
+                .is_err());
+        assert!(RomDb::parse(r#"{"00": {"title": "x", "colors": ["y"]}}"#)
+                .is_err());
+    }
+
+    #[test]
+    fn test_palette() {
+        //given
+        let db = RomDb::parse(r##"{
+            "00": { "title": "Amber", "theme": "amber" },
+            "01": { "title": "Dark", "colors": ["#000000"] },
+            "02": { "title": "Plain" }
+        }"##).unwrap();
+        let amber = Palette::theme("amber").unwrap();
+        let user = Palette::theme("lcd").unwrap();
+        //when
+        let palette = |hash| db.get(hash).unwrap().palette(user);
+        //then
+        assert_eq!(amber, palette("00"));
+        assert_eq!([0, 0, 0], palette("01").0[0]);
+        assert_eq!(user.0[1..], palette("01").0[1..]);
+        assert_eq!(user, palette("02"));
+    }
+
+}
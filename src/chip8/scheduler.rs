@@ -0,0 +1,163 @@
+use std::time::{Duration, Instant};
+
+// The most frames the scheduler will run back to back to catch up.
+const MAX_FRAMES_BEHIND: u32 = 5;
+
+// Speeds that can be stepped through at runtime, as multiples of the normal
+// speed.
+pub const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+pub const MIN_SPEED: f64 = SPEEDS[0];
+pub const MAX_SPEED: f64 = SPEEDS[SPEEDS.len() - 1];
+
+// The next speed up from the given one, if any.
+pub fn faster(speed: f64) -> f64 {
+    SPEEDS.iter().cloned().find(|&s| s > speed).unwrap_or(speed)
+}
+
+// The next speed down from the given one, if any.
+pub fn slower(speed: f64) -> f64 {
+    SPEEDS.iter().cloned().rev().find(|&s| s < speed).unwrap_or(speed)
+}
+
+// Paces the emulator at a steady number of frames per second. Frames are
+// due at fixed intervals from the start, rather than from when the previous
+// frame ran, so a frame run late is made up for by running the next one
+// early and the speed does not drift. When too far behind, such as after
+// the emulator was held up, the time owed is written off instead.
+pub struct Scheduler {
+    // How long a frame lasts at normal speed.
+    normal_duration: Duration,
+    frame_duration: Duration,
+    next_frame: Instant,
+}
+
+impl Scheduler {
+
+    pub fn new(frames_per_second: u64, now: Instant) -> Self {
+        let frame_duration = Duration::from_secs(1) / frames_per_second as u32;
+        Scheduler {
+            normal_duration: frame_duration,
+            frame_duration,
+            next_frame: now,
+        }
+    }
+
+    // Run frames at a multiple of the normal speed, from the next frame on.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.frame_duration = self.normal_duration.div_f64(speed);
+    }
+
+    // Make a frame due straight away, counting time from now on.
+    pub fn restart(&mut self, now: Instant) {
+        self.next_frame = now;
+    }
+
+    // Whether a frame is due to run. If so, the frame is taken as run.
+    pub fn frame_due(&mut self, now: Instant) -> bool {
+        if now < self.next_frame {
+            return false;
+        }
+        self.next_frame += self.frame_duration;
+        if now > self.next_frame + self.frame_duration * MAX_FRAMES_BEHIND {
+            self.next_frame = now + self.frame_duration;
+        }
+        true
+    }
+
+    // The time left until the next frame is due.
+    pub fn until_next_frame(&self, now: Instant) -> Duration {
+        self.next_frame.saturating_duration_since(now)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{faster, slower, Scheduler, MAX_SPEED, MIN_SPEED};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_faster_and_slower() {
+        assert_eq!(2.0, faster(1.0));
+        assert_eq!(2.0, faster(1.5));
+        assert_eq!(MAX_SPEED, faster(MAX_SPEED));
+        assert_eq!(0.5, slower(1.0));
+        assert_eq!(1.0, slower(1.5));
+        assert_eq!(MIN_SPEED, slower(MIN_SPEED));
+    }
+
+    #[test]
+    fn test_set_speed() {
+        //given
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(50, start);
+        let ms = Duration::from_millis;
+        //when
+        scheduler.set_speed(4.0);
+        let due = (0..100)
+            .filter(|&t| scheduler.frame_due(start + ms(t * 5)))
+            .count();
+        //then
+        assert_eq!(100, due);
+        assert_eq!(ms(500), scheduler.until_next_frame(start));
+    }
+
+    #[test]
+    fn test_restart() {
+        //given
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(50, start);
+        assert!(scheduler.frame_due(start));
+        let now = start + Duration::from_millis(1);
+        //when
+        scheduler.restart(now);
+        //then
+        assert!(scheduler.frame_due(now));
+        assert!(!scheduler.frame_due(now));
+    }
+
+    #[test]
+    fn test_frames_do_not_drift() {
+        //given
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(50, start);
+        let ms = Duration::from_millis;
+        //when
+        // Polled a little late every time.
+        let due = (0..100)
+            .filter(|&t| scheduler.frame_due(start + ms(t * 21)))
+            .count();
+        //then
+        assert_eq!(100, due);
+        assert_eq!(ms(2000), scheduler.until_next_frame(start));
+    }
+
+    #[test]
+    fn test_catches_up() {
+        //given
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(50, start);
+        let late = start + Duration::from_millis(65);
+        //when
+        let due = (0..100).filter(|_| scheduler.frame_due(late)).count();
+        //then
+        // The frames due at 0, 20, 40 and 60ms.
+        assert_eq!(4, due);
+        assert_eq!(Duration::from_millis(15), scheduler.until_next_frame(late));
+    }
+
+    #[test]
+    fn test_writes_off_time_owed() {
+        //given
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(50, start);
+        let late = start + Duration::from_secs(10);
+        //when
+        let due = (0..100).filter(|_| scheduler.frame_due(late)).count();
+        //then
+        assert_eq!(1, due);
+        assert_eq!(Duration::from_millis(20), scheduler.until_next_frame(late));
+    }
+
+}
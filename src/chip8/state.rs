@@ -0,0 +1,361 @@
+use super::emu::Emu;
+use bincode;
+use serde_json;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Bumped whenever the layout of the emulator state changes, which makes
+// older save states unloadable.
+pub const VERSION: u32 = 7;
+
+// Names by which the formats can be selected, e.g. from the command line.
+pub const FORMAT_NAMES: [&str; 2] = ["binary", "json"];
+
+// How a save state is written to disk.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    // Compact, for everyday use.
+    Binary,
+    // Human readable, for inspecting or editing a state by hand.
+    Json,
+}
+
+impl Format {
+
+    // Look up a format by one of the names in FORMAT_NAMES.
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "binary" => Some(Format::Binary),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+}
+
+// Reasons a save state may be refused.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StateError {
+    // The state was saved by a version of the emulator with a different
+    // state layout.
+    UnsupportedVersion { found: u32 },
+    // The state was saved while running a different rom.
+    RomMismatch { expected: String, found: String },
+    // The state could not be encoded or decoded.
+    Corrupt(String),
+}
+
+impl fmt::Display for StateError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::UnsupportedVersion { found } =>
+                write!(f, "Unsupported save state version {} (expected {})",
+                       found, VERSION),
+            StateError::RomMismatch { ref expected, ref found } =>
+                write!(f, "Save state belongs to a different rom (sha1 {}, \
+                           running {})", found, expected),
+            StateError::Corrupt(ref msg) =>
+                write!(f, "Corrupt save state: {}", msg),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+// Precedes the emulator state, so it can be checked before the state itself
+// is decoded.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    rom_hash: String,
+}
+
+#[derive(Serialize)]
+struct SaveStateRef<'a> {
+    version: u32,
+    rom_hash: String,
+    emu: &'a Emu,
+}
+
+#[derive(Deserialize)]
+struct SaveState {
+    version: u32,
+    rom_hash: String,
+    emu: Emu,
+}
+
+// Encode the full state of the emulator, tagged with the state version and
+// the hash of the rom.
+pub fn save(emu: &Emu, format: Format) -> Result<Vec<u8>, StateError> {
+    let state = SaveStateRef {
+        version: VERSION,
+        rom_hash: emu.rom_hash(),
+        emu,
+    };
+    match format {
+        Format::Binary => bincode::serialize(&state)
+            .map_err(|err| StateError::Corrupt(err.to_string())),
+        Format::Json => serde_json::to_vec(&state)
+            .map_err(|err| StateError::Corrupt(err.to_string())),
+    }
+}
+
+// Decode the emulator saved in an encoded state, to restore the emulator
+// to. The state must be of the current version and saved while running the
+// same rom as the emulator.
+pub fn read(emu: &Emu, data: &[u8], format: Format)
+    -> Result<Emu, StateError> {
+    let header: Header = decode(data, format)?;
+    if header.version != VERSION {
+        return Err(StateError::UnsupportedVersion { found: header.version });
+    }
+    if header.rom_hash != emu.rom_hash() {
+        return Err(StateError::RomMismatch {
+            expected: emu.rom_hash(),
+            found: header.rom_hash
+        });
+    }
+    let state: SaveState = decode(data, format)?;
+    // The cached rom is part of the state, make sure it was not tampered
+    // with.
+    if state.emu.rom_hash() != state.rom_hash {
+        return Err(StateError::Corrupt("rom does not match hash".to_string()));
+    }
+    // Nor may it call more subroutines than there is room for on the stack.
+    if state.emu.sp() > state.emu.stack().len() {
+        return Err(StateError::Corrupt("stack overflow".to_string()));
+    }
+    debug_assert_eq!(VERSION, state.version);
+    Ok(state.emu)
+}
+
+fn decode<T>(data: &[u8], format: Format) -> Result<T, StateError>
+    where T: for<'de> ::serde::Deserialize<'de> {
+    match format {
+        Format::Binary => bincode::deserialize(data)
+            .map_err(|err| StateError::Corrupt(err.to_string())),
+        Format::Json => serde_json::from_slice(data)
+            .map_err(|err| StateError::Corrupt(err.to_string())),
+    }
+}
+
+// The numbered save state files for a rom, kept next to it. For instance,
+// slot 1 of roms/brix.ch8 is roms/brix.state1, or roms/brix.state1.json.
+pub struct Slots {
+    path_to_rom: PathBuf,
+    format: Format,
+}
+
+impl Slots {
+
+    pub fn new(path_to_rom: &Path, format: Format) -> Self {
+        Slots { path_to_rom: path_to_rom.to_path_buf(), format }
+    }
+
+    pub fn path(&self, slot: u8) -> PathBuf {
+        let path = self.path_to_rom.with_extension(format!("state{}", slot));
+        match self.format {
+            Format::Binary => path,
+            Format::Json => path.with_extension(format!("state{}.json", slot)),
+        }
+    }
+
+    // Save the emulator to the slot, returning the file written.
+    pub fn save(&self, slot: u8, emu: &Emu) -> Result<PathBuf, Box<dyn Error>> {
+        let path = self.path(slot);
+        fs::write(&path, save(emu, self.format)?)?;
+        Ok(path)
+    }
+
+    // Read the emulator saved in the slot, to restore the emulator to,
+    // along with the file read.
+    pub fn read(&self, slot: u8, emu: &Emu)
+        -> Result<(PathBuf, Emu), Box<dyn Error>> {
+        let path = self.path(slot);
+        let saved = read(emu, &fs::read(&path)?, self.format)?;
+        Ok((path, saved))
+    }
+
+}
+
+// Serde support for ram, which must be of the full XO-CHIP size.
+pub mod ram_serde {
+
+    use super::super::emu::XO_CHIP_RAM_SIZE;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(ram: &[u8], s: S)
+        -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(ram)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D)
+        -> Result<Vec<u8>, D::Error> {
+        let ram = Vec::<u8>::deserialize(d)?;
+        if ram.len() != XO_CHIP_RAM_SIZE {
+            return Err(D::Error::invalid_length(ram.len(), &"ram size"));
+        }
+        Ok(ram)
+    }
+
+}
+
+// Serde support for the bitplanes, packed 8 pixels to a byte.
+pub mod gfx_serde {
+
+    use super::super::{GFX_H, GFX_W, NUM_PLANES, Plane};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    const PACKED_SIZE: usize = NUM_PLANES * GFX_W * GFX_H / 8;
+
+    pub fn serialize<S: Serializer>(gfx: &[Plane; NUM_PLANES], s: S)
+        -> Result<S::Ok, S::Error> {
+        let mut packed = vec![0u8; PACKED_SIZE];
+        let pixels = gfx.iter().flat_map(|plane| plane.iter().flatten());
+        for (idx, &pix) in pixels.enumerate() {
+            if pix { packed[idx / 8] |= 0x80 >> (idx % 8); }
+        }
+        s.serialize_bytes(&packed)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D)
+        -> Result<[Plane; NUM_PLANES], D::Error> {
+        let packed = Vec::<u8>::deserialize(d)?;
+        if packed.len() != PACKED_SIZE {
+            return Err(D::Error::invalid_length(packed.len(), &"gfx size"));
+        }
+        let mut gfx = [[[false; GFX_H]; GFX_W]; NUM_PLANES];
+        let pixels = gfx.iter_mut()
+            .flat_map(|plane| plane.iter_mut().flat_map(|col| col.iter_mut()));
+        for (idx, pix) in pixels.enumerate() {
+            *pix = packed[idx / 8] & (0x80 >> (idx % 8)) != 0;
+        }
+        Ok(gfx)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{read, save, Format, StateError, VERSION};
+    use super::super::emu::Emu;
+    use super::super::quirks::Quirks;
+
+    fn load(emu: &mut Emu, data: &[u8], format: Format)
+        -> Result<(), StateError> {
+        let saved = read(emu, data, format)?;
+        emu.restore(saved);
+        Ok(())
+    }
+
+    // 200: v0 += 1
+    // 202: sprite v0 v0 5 (the font for 0)
+    // 204: jump 200
+    fn running_emu() -> Emu {
+        let mut emu = Emu::new(Quirks::default());
+        emu.load_rom(vec![0x70, 0x01, 0xd0, 0x05, 0x12, 0x00]).unwrap();
+        for _ in 0..5 {
+            emu.execute_cycle().unwrap();
+        }
+        emu
+    }
+
+    fn round_trip(format: Format) {
+        let mut emu = running_emu();
+        let state = save(&emu, format).unwrap();
+        let (pc, v, gfx) = (emu.pc(), *emu.v(), emu.gfx);
+        emu.execute_cycle().unwrap();
+        emu.execute_cycle().unwrap();
+        load(&mut emu, &state, format).unwrap();
+        assert_eq!(pc, emu.pc());
+        assert_eq!(v, *emu.v());
+        assert!(gfx == emu.gfx);
+        assert!(emu.draw);
+    }
+
+    #[test]
+    fn test_round_trip_binary() {
+        round_trip(Format::Binary);
+    }
+
+    #[test]
+    fn test_round_trip_json() {
+        round_trip(Format::Json);
+    }
+
+    #[test]
+    fn test_round_trip_random_state() {
+        let mut emu = Emu::new(Quirks::default());
+        // 200: v0 = random byte
+        // 202: jump 200
+        emu.load_rom(vec![0xc0, 0xff, 0x12, 0x00]).unwrap();
+        emu.set_seed(7);
+        for _ in 0..6 {
+            emu.execute_cycle().unwrap();
+        }
+        let state = save(&emu, Format::Binary).unwrap();
+        let random = |emu: &mut Emu| -> Vec<u8> {
+            (0..8).map(|_| {
+                emu.execute_cycle().unwrap();
+                emu.execute_cycle().unwrap();
+                emu.v()[0]
+            }).collect()
+        };
+        let before = random(&mut emu);
+        load(&mut emu, &state, Format::Binary).unwrap();
+        assert_eq!(before, random(&mut emu));
+    }
+
+    #[test]
+    fn test_rejects_other_rom() {
+        let state = save(&running_emu(), Format::Binary).unwrap();
+        let mut emu = Emu::new(Quirks::default());
+        emu.load_rom(vec![0x12, 0x00]).unwrap();
+        match load(&mut emu, &state, Format::Binary) {
+            Err(StateError::RomMismatch { .. }) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(0x200, emu.pc());
+    }
+
+    #[test]
+    fn test_rejects_other_version() {
+        let mut emu = running_emu();
+        let state = save(&emu, Format::Json).unwrap();
+        let state = String::from_utf8(state).unwrap().replacen(
+            &format!("\"version\":{}", VERSION), "\"version\":999", 1);
+        assert_eq!(Err(StateError::UnsupportedVersion { found: 999 }),
+                   load(&mut emu, state.as_bytes(), Format::Json));
+    }
+
+    #[test]
+    fn test_rejects_stack_overflow() {
+        let mut emu = running_emu();
+        let pc = emu.pc();
+        let state = save(&emu, Format::Json).unwrap();
+        let state = String::from_utf8(state).unwrap().replacen(
+            &format!("\"sp\":{}", emu.sp()), "\"sp\":17", 1);
+        assert!(state.contains("\"sp\":17"));
+        match load(&mut emu, state.as_bytes(), Format::Json) {
+            Err(StateError::Corrupt(_)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(pc, emu.pc());
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        let mut emu = running_emu();
+        match load(&mut emu, &[1, 2, 3], Format::Binary) {
+            Err(StateError::Corrupt(_)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+}
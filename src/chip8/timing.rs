@@ -0,0 +1,170 @@
+use super::instruction::Instruction;
+
+// Names by which the timing models can be selected, e.g. from the command
+// line.
+pub const TIMING_NAMES: [&str; 2] = ["fixed", "vip"];
+
+// Cycles per frame given fixed timing, close to the 500 instructions per
+// second the emulator has always run at.
+pub const DEFAULT_CYCLES_PER_FRAME: u64 = 8;
+
+// Machine cycles of the COSMAC VIP's 1802 processor per 60Hz frame, at its
+// clock rate of 1.7609MHz and 8 clock pulses per machine cycle.
+const VIP_CYCLES_PER_FRAME: u64 = 3668;
+// Machine cycles per frame taken by the display: the DMA transfer of 128
+// lines of 8 bytes, plus the interrupt routine that sets it up and counts
+// down the timers.
+const VIP_DISPLAY_CYCLES: u64 = 1024 + 46;
+// Machine cycles per frame left to run the interpreter.
+pub const VIP_INTERPRETER_CYCLES: u64 = 
+    VIP_CYCLES_PER_FRAME - VIP_DISPLAY_CYCLES;
+// Machine cycles the interpreter takes to fetch and decode an instruction.
+const VIP_FETCH_CYCLES: u64 = 40;
+
+// How long instructions take to execute, which determines how many of them
+// run in a frame.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Timing {
+    // Every instruction takes as long, with the given number of cycles
+    // run per frame.
+    Fixed(u64),
+    // Each instruction takes as long as on the original COSMAC VIP
+    // interpreter, with the timers counted down on the vertical blank.
+    // Speed sensitive games play as they did on the original hardware.
+    CosmacVip,
+}
+
+impl Default for Timing {
+
+    fn default() -> Timing {
+        Timing::Fixed(DEFAULT_CYCLES_PER_FRAME)
+    }
+
+}
+
+impl Timing {
+
+    // Look up a timing model by one of the names in TIMING_NAMES.
+    pub fn from_name(name: &str) -> Option<Timing> {
+        match name {
+            "fixed" => Some(Timing::Fixed(DEFAULT_CYCLES_PER_FRAME)),
+            "vip" => Some(Timing::CosmacVip),
+            _ => None,
+        }
+    }
+
+}
+
+// Apply the number of cycles per frame, if any, to the timing, which must 
+// then be fixed. A number of cycles per frame alone also selects fixed 
+// timing.
+pub fn with_cycles_per_frame(timing: Option<Timing>, 
+                             cycles_per_frame: Option<u64>) -> Option<Timing> {
+    match (timing, cycles_per_frame) {
+        (None, Some(n)) | (Some(Timing::Fixed(_)), Some(n)) =>
+            Some(Timing::Fixed(n)),
+        (timing, _) => timing,
+    }
+}
+
+// Machine cycles the COSMAC VIP interpreter takes to fetch, decode and
+// execute an instruction, approximating the figures from Laurence Scotford's
+// analysis of the interpreter. `vx` is the value of the instruction's first
+// register before it executed, and `skipped` whether a skip instruction
+// skipped. Unknown instructions, and those the VIP did not have, take as
+// long as the simplest instructions. Drawing leaves out the wait for the
+// vertical blank, see Emu::step.
+pub fn vip_cycles(instruction: Option<Instruction>, vx: u8, skipped: bool)
+    -> u64 {
+    let skip = if skipped {4} else {0};
+    let execute = match instruction {
+        // Clears the 256 bytes of display memory a byte at a time.
+        Some(Instruction::Clear) => 3078,
+        Some(Instruction::Return) => 10,
+        Some(Instruction::Jump(_)) => 12,
+        Some(Instruction::Call(_)) => 26,
+        Some(Instruction::SkipIfEqual(..)) |
+        Some(Instruction::SkipIfNotEqual(..)) => 10 + skip,
+        Some(Instruction::SkipIfRegEqual(..)) |
+        Some(Instruction::SkipIfRegNotEqual(..)) => 14 + skip,
+        Some(Instruction::Set(..)) => 6,
+        Some(Instruction::Add(..)) => 10,
+        // The arithmetic and logic instructions are assembled on the stack
+        // and executed as 1802 code.
+        Some(Instruction::SetReg(..)) | Some(Instruction::Or(..)) |
+        Some(Instruction::And(..)) | Some(Instruction::Xor(..)) |
+        Some(Instruction::AddReg(..)) | Some(Instruction::Sub(..)) |
+        Some(Instruction::ShiftRight(..)) | 
+        Some(Instruction::SubReversed(..)) |
+        Some(Instruction::ShiftLeft(..)) => 44,
+        Some(Instruction::SetIndex(_)) => 12,
+        Some(Instruction::JumpOffset(_)) => 22,
+        Some(Instruction::Random(..)) => 36,
+        // Sprite rows on a byte boundary are copied straight to display
+        // memory. Otherwise each row is shifted into two bytes first.
+        Some(Instruction::Draw(_, _, n)) => {
+            let rows = if n == 0 {16} else {n as u64};
+            let per_row = if vx.is_multiple_of(8) {46} else {92};
+            26 + rows * per_row
+        },
+        Some(Instruction::SkipIfKey(_)) |
+        Some(Instruction::SkipIfNotKey(_)) => 14 + skip,
+        Some(Instruction::GetDelay(_)) => 10,
+        Some(Instruction::WaitKey(_)) => 16,
+        Some(Instruction::SetDelay(_)) | Some(Instruction::SetSound(_)) => 10,
+        Some(Instruction::AddIndex(_)) => 16,
+        Some(Instruction::Font(_)) => 16,
+        // Each digit is found by repeated subtraction.
+        Some(Instruction::Bcd(_)) => {
+            let vx = vx as u64;
+            84 + 16 * (vx / 100 + vx / 10 % 10 + vx % 10)
+        },
+        Some(Instruction::Store(x)) | Some(Instruction::Load(x)) =>
+            14 + 14 * (x as u64 + 1),
+        _ => 6,
+    };
+    VIP_FETCH_CYCLES + execute
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{vip_cycles, with_cycles_per_frame, Timing};
+    use super::super::instruction::Instruction;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Some(Timing::CosmacVip), Timing::from_name("vip"));
+        assert_eq!(Some(Timing::Fixed(8)), Timing::from_name("fixed"));
+        assert_eq!(None, Timing::from_name("fast"));
+    }
+
+    #[test]
+    fn test_with_cycles_per_frame() {
+        assert_eq!(Some(Timing::Fixed(20)), 
+                   with_cycles_per_frame(None, Some(20)));
+        assert_eq!(Some(Timing::Fixed(20)), 
+                   with_cycles_per_frame(Some(Timing::default()), Some(20)));
+        assert_eq!(Some(Timing::CosmacVip), 
+                   with_cycles_per_frame(Some(Timing::CosmacVip), Some(20)));
+        assert_eq!(None, with_cycles_per_frame(None, None));
+    }
+
+    #[test]
+    fn test_vip_cycles() {
+        assert_eq!(46, vip_cycles(Some(Instruction::Set(0, 1)), 0, false));
+        assert_eq!(50, vip_cycles(Some(Instruction::SkipIfEqual(0, 1)), 0,
+                                  false));
+        assert_eq!(54, vip_cycles(Some(Instruction::SkipIfEqual(0, 1)), 0,
+                                  true));
+        // Unaligned sprites take longer.
+        let aligned = vip_cycles(Some(Instruction::Draw(0, 1, 5)), 8, false);
+        let unaligned = vip_cycles(Some(Instruction::Draw(0, 1, 5)), 9, false);
+        assert_eq!(40 + 26 + 5 * 46, aligned);
+        assert_eq!(40 + 26 + 5 * 92, unaligned);
+        // 1 + 2 + 3 subtractions.
+        assert_eq!(40 + 84 + 16 * 6, vip_cycles(Some(Instruction::Bcd(0)), 123,
+                                                false));
+    }
+
+}
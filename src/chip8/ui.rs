@@ -1,21 +1,148 @@
 use sdl2;
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::controller::GameController;
 use sdl2::event::Event;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use sdl2::keyboard::Scancode;
-use sdl2::Sdl;
-use super::{GFX_H, GFX_W, Mode, wav};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+use sdl2::{GameControllerSubsystem, Sdl};
+use super::{GFX_H, GFX_W, NUM_PLANES, Plane, wav};
+use super::gamepad::{Buttons, Gamepad, PadEvent};
+use super::keymap::{Action, Keymap};
+use super::emu::FRAMES_PER_SECOND;
+use super::palette::{Palette, Rgb};
+use super::phosphor::{self, Phosphor};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
-const SCALE: usize = 8;
+// Screen pixels per high resolution pixel, unless told otherwise.
+pub const DEFAULT_SCALE: usize = 8;
+const TITLE: &str = "chip8";
 
-pub struct BeepCallback;
+// Sample values for the XO-CHIP audio pattern bits. Kept well within range
+// to be about as loud as the standard beep.
+const PATTERN_BIT_ON: u8 = 0xc0;
+const PATTERN_BIT_OFF: u8 = 0x40;
+// The value of silence, for unsigned 8 bit samples.
+const SILENCE: f64 = 128.0;
+
+// Names by which the ways of scaling can be selected, e.g. from the command
+// line.
+pub const SCALING_NAMES: [&str; 2] = ["fit", "integer"];
+
+// How the screen is scaled up to the window. Either way the screen keeps
+// its shape, with bars filling the rest of the window.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Scaling {
+    // As large as fits.
+    #[default]
+    Fit,
+    // As large as fits by a whole number of window pixels per pixel, for
+    // pixels all of the same size.
+    Integer,
+}
+
+impl Scaling {
+
+    // Look up a way of scaling by one of the names in SCALING_NAMES.
+    pub fn from_name(name: &str) -> Option<Scaling> {
+        match name {
+            "fit" => Some(Scaling::Fit),
+            "integer" => Some(Scaling::Integer),
+            _ => None,
+        }
+    }
+
+}
+
+// How the window looks, sounds and responds to keys and controllers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    // Screen pixels per high resolution pixel, as the window opens.
+    pub scale: usize,
+    pub fullscreen: bool,
+    pub scaling: Scaling,
+    pub mute: bool,
+    // Loudness of the beep as a percentage.
+    pub volume: u8,
+    // Pitch of the standard beep in Hz, or None for that of the recorded
+    // waveform.
+    pub tone: Option<f64>,
+    pub palette: Palette,
+    // Whether pixels fade out, and how long they take to, see Phosphor.
+    pub phosphor: bool,
+    pub decay: Duration,
+    pub keymap: Keymap,
+    pub buttons: Buttons,
+}
+
+impl Default for Settings {
+
+    fn default() -> Settings {
+        Settings {
+            scale: DEFAULT_SCALE,
+            fullscreen: false,
+            scaling: Scaling::default(),
+            mute: false,
+            volume: 100,
+            tone: None,
+            palette: Palette::default(),
+            phosphor: false,
+            decay: Duration::from_millis(phosphor::DEFAULT_DECAY_MS),
+            keymap: Keymap::default(),
+            buttons: Buttons::default(),
+        }
+    }
+
+}
+
+pub struct BeepCallback {
+    // XO-CHIP audio pattern of 128 1-bit samples. None plays the standard 
+    // beep instead.
+    pattern: Option<[u8; 16]>,
+    // Pattern bits played per output sample.
+    step: f64,
+    // Position within the pattern, in bits.
+    phase: f64,
+    // Samples of the standard beep played per output sample, and the 
+    // position within it.
+    beep_step: f64,
+    beep_phase: f64,
+    // Scales samples about silence.
+    volume: f64,
+}
 
 impl BeepCallback {
-    fn new() -> Self {
-        BeepCallback
+    fn new(settings: &Settings) -> Self {
+        let beep_step = match settings.tone {
+            Some(tone) => tone * wav::SAMPLES as f64 / 
+                wav::SAMPLE_RATE_HZ as f64,
+            None => 1.0,
+        };
+        BeepCallback { 
+            pattern: None, 
+            step: 0.0, 
+            phase: 0.0,
+            beep_step,
+            beep_phase: 0.0,
+            volume: settings.volume as f64 / 100.0,
+        }
+    }
+
+    fn scale(&self, sample: u8) -> u8 {
+        (SILENCE + (sample as f64 - SILENCE) * self.volume) as u8
+    }
+
+    // Set the audio pattern and the pitch to play it at. XO-CHIP plays the
+    // pattern at 4000*2^((pitch-64)/48) bits per second.
+    fn set_sound(&mut self, pattern: Option<[u8; 16]>, pitch: u8) {
+        let rate = 4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0);
+        self.pattern = pattern;
+        self.step = rate / wav::SAMPLE_RATE_HZ as f64;
     }
 }
 
@@ -23,9 +150,20 @@ impl AudioCallback for BeepCallback {
     type Channel = u8;
 
     fn callback(&mut self, out: &mut [u8]) {
-        assert!(out.len() == wav::PLAYBACK_BUFFER.len());
-        for i in 0..wav::PLAYBACK_BUFFER.len() {
-            out[i] = wav::PLAYBACK_BUFFER[i];
+        match self.pattern {
+            None => for sample in out.iter_mut() {
+                let idx = self.beep_phase as usize % wav::SAMPLES;
+                *sample = self.scale(wav::PLAYBACK_BUFFER[idx]);
+                self.beep_phase = (self.beep_phase + self.beep_step) % 
+                    wav::SAMPLES as f64;
+            },
+            Some(pattern) => for sample in out.iter_mut() {
+                let bit = self.phase as usize % 128;
+                let on = pattern[bit / 8] & (0b1000_0000 >> (bit % 8)) != 0;
+                *sample = self.scale(
+                    if on {PATTERN_BIT_ON} else {PATTERN_BIT_OFF});
+                self.phase = (self.phase + self.step) % 128.0;
+            },
         }
     }    
 
@@ -34,23 +172,57 @@ impl AudioCallback for BeepCallback {
 pub struct Ui {
     sdl_ctx: Sdl,
     canvas: Canvas<Window>,
+    // Makes the texture the screen is drawn through, one texel per pixel.
+    // Textures borrow their creator, so one is made for every frame rather
+    // than kept alongside it.
+    texture_creator: TextureCreator<WindowContext>,
     audio: AudioDevice<BeepCallback>,
+    settings: Settings,
+    // The screen last drawn, for screenshots.
+    screen: Option<(bool, Box<[Plane; NUM_PLANES]>)>,
+    // None when controllers cannot be used at all.
+    controller_subsystem: Option<GameControllerSubsystem>,
+    // Controllers plugged in, by id.
+    controllers: HashMap<u32, GameController>,
+    gamepad: Gamepad,
+    // The palettes cycled through, and which of them is in use.
+    palettes: Vec<Palette>,
+    palette_idx: usize,
+    // Some when pixels fade out, and whether they are still fading since
+    // the screen was last drawn.
+    phosphor: Option<Phosphor>,
+    fading: bool,
+    last_draw: Instant,
 }
 
 impl Ui {
     
-    pub fn new() -> Self {
+    // Open a window scaled up from the high resolution screen, or covering
+    // the whole desktop. The window can be resized at will.
+    pub fn new(settings: Settings) -> Self {
+        let Settings { scale, fullscreen, scaling, .. } = settings;
         let sdl_ctx = sdl2::init().unwrap();
         let video_subsystem = sdl_ctx.video().unwrap();
-        let window = video_subsystem.window("chip8", 
-                                     (GFX_W * SCALE) as u32, 
-                                     (GFX_H * SCALE) as u32)
-                                    .position_centered()
-                                    .build()
-                                    .unwrap();
-
-        let canvas = window.into_canvas().build().unwrap();
+        let mut window = video_subsystem.window(TITLE, 
+                                     (GFX_W * scale) as u32, 
+                                     (GFX_H * scale) as u32);
+        window.position_centered().resizable();
+        if fullscreen {
+            window.fullscreen_desktop();
+        }
+        let window = window.build().unwrap();
+
+        let mut canvas = window.into_canvas().build().unwrap();
+        // Drawn at the resolution of the screen, then scaled up to the
+        // window. Set again as the resolution changes.
+        let (width, height) = resolution(false);
+        canvas.set_logical_size(width as u32, height as u32).unwrap();
+        canvas.set_integer_scale(scaling == Scaling::Integer).unwrap();
         
+        // Scaled up without smoothing, keeping pixels sharp.
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+        let texture_creator = canvas.texture_creator();
+
         let audio_subsystem = sdl_ctx.audio().unwrap();
         let audio_spec = AudioSpecDesired {
             freq: Some(wav::SAMPLE_RATE_HZ as i32),
@@ -59,81 +231,249 @@ impl Ui {
         };
     
         let audio = audio_subsystem.open_playback(None, &audio_spec, |_| {
-            BeepCallback::new()
+            BeepCallback::new(&settings)
         }).unwrap();
 
-        Ui { sdl_ctx, canvas, audio }
+        // Controllers already plugged in are reported as added once events
+        // are polled.
+        let controller_subsystem = sdl_ctx.game_controller()
+            .map_err(|err| eprintln!("Controllers unavailable: {}", err))
+            .ok();
+        let gamepad = Gamepad::new(settings.buttons.clone());
+        let palettes = settings.palette.cycle();
+        let phosphor = match settings.phosphor {
+            true => Some(Phosphor::new(settings.decay)),
+            false => None,
+        };
+
+        Ui { 
+            sdl_ctx, canvas, texture_creator, audio, settings, screen: None,
+            controller_subsystem, controllers: HashMap::new(), gamepad,
+            palettes, palette_idx: 0, phosphor, fading: false, 
+            last_draw: Instant::now(),
+        }
     }
 
     pub fn beep(&self, on: bool) {
-        match on {
+        match on && !self.settings.mute {
             true => self.audio.resume(),
             false => self.audio.pause()
         }
     }
 
-    pub fn refresh_gfx(&mut self, mode: Mode, gfx: &[[bool; GFX_H]; GFX_W]) {
-        let bg = Color::RGB(0x1c, 0x28, 0x41);
-        let fg = Color::RGB(0xff, 0xff, 0xff);
-        let projection_factor = match mode { 
-            //
-            // For STANDARD mode, the 64x32 gfx subscreen will be projected 
-            // to fit the entire viewable area. The excess between 64x32 and
-            // 128x64 will be projected offscreen. 
-            // +-----------------------+-----------------------+
-            // |                       |                       |
-            // |         64x32         |                       |
-            // |                       |                       |
-            // +-----------------------+    drawn offscreen    |
-            // |                                               |
-            // |                                               |
-            // |                                               |
-            // +-----------------------------------------------+ (128x64)
-            Mode::Standard => SCALE * 2, 
-            Mode::Super => SCALE, 
+    // Report an error that halted the emulator, both on the console and in
+    // the window title. The error remains visible until cleared. 
+    pub fn show_error<E: Display>(&mut self, err: &E) {
+        eprintln!("Emulator halted: {}", err);
+        self.beep(false);
+        let title = match self.settings.keymap.key_for(Action::Reset) {
+            Some(key) =>
+                format!("{} - {} (press {} to reset)", TITLE, err, key),
+            None => format!("{} - {}", TITLE, err),
         };
-        for x in 0..GFX_W {
-            for y in 0..GFX_H {
-                let pix_on = gfx[x][y];
-                let color = if pix_on {fg} else {bg};
-                let rx = (x * projection_factor) as i32;
-                let ry = (y * projection_factor) as i32;
-                let rw = projection_factor as u32;
-                let rh = projection_factor as u32;
-                let rect = Rect::new(rx, ry, rw, rh);
-                self.canvas.set_draw_color(color);
-                self.canvas.fill_rect(rect).expect("Failed to draw rect");
+        self.canvas.window_mut().set_title(&title)
+            .expect("Failed to set window title");
+    }
+
+    // Remove the error reported by show_error, if any.
+    pub fn clear_error(&mut self) {
+        self.canvas.window_mut().set_title(TITLE)
+            .expect("Failed to set window title");
+    }
+
+    // Change the audio pattern and pitch. Applies to XO-CHIP mode only.
+    pub fn set_sound(&mut self, pattern: Option<[u8; 16]>, pitch: u8) {
+        self.audio.lock().set_sound(pattern, pitch);
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.settings.keymap
+    }
+
+    // Switch between a window and covering the whole desktop.
+    pub fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        if let Err(err) = window.set_fullscreen(fullscreen) {
+            eprintln!("Failed to switch fullscreen: {}", err);
+        }
+        self.redraw();
+    }
+
+    // Switch the phosphor filter on or off, returning whether it is on.
+    pub fn toggle_phosphor(&mut self) -> bool {
+        self.phosphor = match self.phosphor {
+            Some(_) => None,
+            None => Some(Phosphor::new(self.settings.decay)),
+        };
+        self.fading = false;
+        self.phosphor.is_some()
+    }
+
+    // Draw the screen again as pixels fade, at most once a frame. Pixels
+    // carry on fading when the emulator draws nothing new.
+    pub fn fade(&mut self) {
+        let frame = Duration::from_secs(1) / FRAMES_PER_SECOND as u32;
+        if self.fading && self.last_draw.elapsed() >= frame {
+            self.redraw();
+        }
+    }
+
+    // Draw the screen last drawn again, such as when the window changes.
+    pub fn redraw(&mut self) {
+        if let Some((hires, gfx)) = self.screen.take() {
+            self.refresh_gfx(hires, &gfx);
+        }
+    }
+
+    // Write the screen last drawn, at the scale the window opened at, to a
+    // binary PPM image.
+    pub fn screenshot(&self, path: &Path) -> io::Result<()> {
+        let (hires, gfx) = match self.screen {
+            Some((hires, ref gfx)) => (hires, gfx),
+            None => return Err(io::Error::other("nothing drawn yet")),
+        };
+        let factor = self.settings.scale * GFX_W / resolution(hires).0;
+        let (width, height) = (GFX_W * self.settings.scale, 
+                               GFX_H * self.settings.scale);
+        let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        for y in 0..height {
+            for x in 0..width {
+                let (x, y) = (x / factor, y / factor);
+                out.extend_from_slice(&color(&self.settings.palette, gfx, 
+                                             x, y));
             }
         }
+        fs::write(path, out)
+    }
+
+    // Switch to the next palette, see Palette::cycle, and redraw the screen
+    // in it.
+    pub fn next_palette(&mut self) -> Palette {
+        self.palette_idx = (self.palette_idx + 1) % self.palettes.len();
+        self.settings.palette = self.palettes[self.palette_idx];
+        self.redraw();
+        self.settings.palette
+    }
+
+    // Draw the screen into the texture, at the resolution the emulator is
+    // in, and have it scaled up to the window in one go.
+    pub fn refresh_gfx(&mut self, hires: bool, gfx: &[Plane; NUM_PLANES]) {
+        let (width, height) = resolution(hires);
+        if self.canvas.logical_size() != (width as u32, height as u32) {
+            self.canvas.set_logical_size(width as u32, height as u32)
+                .expect("Failed to scale window");
+        }
+        let palette = self.settings.palette;
+        let mut frame: Vec<Rgb> = (0..width * height)
+            .map(|idx| color(&palette, gfx, idx % width, idx / width))
+            .collect();
+        let now = Instant::now();
+        if let Some(ref mut phosphor) = self.phosphor {
+            self.fading = phosphor.apply(&mut frame, palette.0[0], 
+                                         now - self.last_draw);
+        }
+        self.last_draw = now;
+        let mut texture = self.texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24,
+                                      width as u32, height as u32)
+            .expect("Failed to create texture");
+        texture.with_lock(None, |pixels, pitch| {
+            for (y, row) in frame.chunks(width).enumerate() {
+                for (x, rgb) in row.iter().enumerate() {
+                    let idx = y * pitch + x * 3;
+                    pixels[idx..idx + 3].copy_from_slice(rgb);
+                }
+            }
+        }).expect("Failed to draw texture");
+        // Clears the bars around the screen.
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.canvas.copy(&texture, None, None)
+            .expect("Failed to draw texture");
         self.canvas.present();
+        self.screen = Some((hires, Box::new(*gfx)));
     } 
     
     pub fn poll_event(&self) -> Option<Event> {
         let mut event_pump = self.sdl_ctx.event_pump().unwrap();
-        return event_pump.poll_event();
+        event_pump.poll_event()
     }
 
+    // Take in a controller event: open controllers as they are plugged in,
+    // and close them as they are unplugged.
+    pub fn controller_event(&mut self, event: &Event) -> Vec<PadEvent> {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let opened = self.controller_subsystem.as_ref()
+                    .map(|subsystem| subsystem.open(which));
+                match opened {
+                    Some(Ok(controller)) => {
+                        println!("Controller connected: {}", 
+                                 controller.name());
+                        self.controllers.insert(controller.instance_id(), 
+                                                controller);
+                    },
+                    Some(Err(err)) => 
+                        eprintln!("Failed to open controller: {}", err),
+                    None => {},
+                }
+                vec![]
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(controller) = self.controllers.remove(&which) {
+                    println!("Controller disconnected: {}", controller.name());
+                }
+                self.gamepad.handle(event)
+            },
+            _ => self.gamepad.handle(event),
+        }
+    }
+
+    // The state of the keypad, from the keyboard and controllers together.
     pub fn get_updated_keys(&self) -> [bool; 16] {
         let event_pump = self.sdl_ctx.event_pump().unwrap();
         let keyboard_state = event_pump.keyboard_state();
-        let mut keys = [false; 16];
-        keys[0x0] = keyboard_state.is_scancode_pressed(Scancode::X);
-        keys[0x1] = keyboard_state.is_scancode_pressed(Scancode::Num1);
-        keys[0x2] = keyboard_state.is_scancode_pressed(Scancode::Num2);
-        keys[0x3] = keyboard_state.is_scancode_pressed(Scancode::Num3);
-        keys[0x4] = keyboard_state.is_scancode_pressed(Scancode::Q);
-        keys[0x5] = keyboard_state.is_scancode_pressed(Scancode::W);
-        keys[0x6] = keyboard_state.is_scancode_pressed(Scancode::E);
-        keys[0x7] = keyboard_state.is_scancode_pressed(Scancode::A);
-        keys[0x8] = keyboard_state.is_scancode_pressed(Scancode::S);
-        keys[0x9] = keyboard_state.is_scancode_pressed(Scancode::D);
-        keys[0xA] = keyboard_state.is_scancode_pressed(Scancode::Z);
-        keys[0xB] = keyboard_state.is_scancode_pressed(Scancode::C);
-        keys[0xC] = keyboard_state.is_scancode_pressed(Scancode::Num4);
-        keys[0xD] = keyboard_state.is_scancode_pressed(Scancode::R);
-        keys[0xE] = keyboard_state.is_scancode_pressed(Scancode::F);
-        keys[0xF] = keyboard_state.is_scancode_pressed(Scancode::V);
+        let mut keys = self.settings.keymap.keys(|scancode| {
+            keyboard_state.is_scancode_pressed(scancode)
+        });
+        for (key, &held) in keys.iter_mut().zip(self.gamepad.keys().iter()) {
+            *key |= held;
+        }
         keys
     }
 
 }
+
+// The part of the screen shown, in pixels across and down.
+fn resolution(hires: bool) -> (usize, usize) {
+    match hires { 
+        //
+        // For low resolution, only the 64x32 gfx subscreen is shown, scaled
+        // up to fit the entire viewable area. 
+        // +-----------------------+-----------------------+
+        // |                       |                       |
+        // |         64x32         |                       |
+        // |                       |                       |
+        // +-----------------------+        not shown      |
+        // |                                               |
+        // |                                               |
+        // |                                               |
+        // +-----------------------------------------------+ (128x64)
+        false => (GFX_W / 2, GFX_H / 2), 
+        true => (GFX_W, GFX_H), 
+    }
+}
+
+// The color of the pixel, by the bitplanes it is set in.
+fn color(palette: &Palette, gfx: &[Plane; NUM_PLANES], x: usize, y: usize) 
+    -> Rgb {
+    let set = |plane: &Plane| plane.get(x)
+        .and_then(|col| col.get(y))
+        .cloned()
+        .unwrap_or(false);
+    palette.0[set(&gfx[0]) as usize | (set(&gfx[1]) as usize) << 1]
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+// Registers that can be watched.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Register {
+    // One of v0 to vf.
+    V(usize),
+    // The index register.
+    I,
+}
+
+// Something to keep an eye on while a rom runs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Watchpoint {
+    // The ram address is written by fx33, fx55 or 5xy2.
+    Write(u16),
+    // The ram address is read by fx65, dxyn or 5xy3.
+    Read(u16),
+    // The register changes, optionally only when it changes to the given
+    // value.
+    Register(Register, Option<u16>),
+}
+
+// What to do when a watchpoint is hit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatchAction {
+    // Stop the emulator.
+    Halt,
+    // Report the hit and keep running.
+    Log,
+}
+
+// Records the instruction that hit a watchpoint.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    pub action: WatchAction,
+    pub pc: u16,
+    pub opcode: u16,
+    // The value read, written or assigned.
+    pub value: u16,
+}
+
+impl Watchpoint {
+
+    // Parse a watchpoint written as r:ADDR or w:ADDR for a ram address, or
+    // as vX, vX=VALUE, i or i=VALUE for a register. Numbers are in hex.
+    pub fn parse(s: &str) -> Result<Watchpoint, String> {
+        let invalid = || format!("Invalid watchpoint: {}", s);
+        let hex = |digits: &str| u16::from_str_radix(digits, 16)
+            .map_err(|_| invalid());
+        let (target, value) = match s.find('=') {
+            Some(idx) => (&s[..idx], Some(hex(&s[idx + 1..])?)),
+            None => (s, None),
+        };
+        if target.starts_with("r:") && value.is_none() {
+            Ok(Watchpoint::Read(hex(&target[2..])?))
+        } else if target.starts_with("w:") && value.is_none() {
+            Ok(Watchpoint::Write(hex(&target[2..])?))
+        } else if target == "i" {
+            Ok(Watchpoint::Register(Register::I, value))
+        } else if target.starts_with('v') && target.len() == 2 {
+            let x = hex(&target[1..])?;
+            match value {
+                Some(value) if value > 0xff => Err(invalid()),
+                _ => Ok(Watchpoint::Register(Register::V(x as usize), value)),
+            }
+        } else {
+            Err(invalid())
+        }
+    }
+
+}
+
+impl fmt::Display for Watchpoint {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Watchpoint::Read(addr) => write!(f, "r:{:03x}", addr),
+            Watchpoint::Write(addr) => write!(f, "w:{:03x}", addr),
+            Watchpoint::Register(reg, value) => {
+                match reg {
+                    Register::V(x) => write!(f, "v{:x}", x)?,
+                    Register::I => write!(f, "i")?,
+                }
+                match value {
+                    Some(value) => write!(f, "={:x}", value),
+                    None => Ok(()),
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Display for WatchHit {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Watchpoint {} hit by {:04x} at {:03x}, value {:02x}",
+               self.watchpoint, self.opcode, self.pc, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Register, Watchpoint};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(Watchpoint::Read(0x300)), Watchpoint::parse("r:300"));
+        assert_eq!(Ok(Watchpoint::Write(0x3ff)), Watchpoint::parse("w:3ff"));
+        assert_eq!(Ok(Watchpoint::Register(Register::V(0xa), None)),
+                   Watchpoint::parse("va"));
+        assert_eq!(Ok(Watchpoint::Register(Register::V(3), Some(0x10))),
+                   Watchpoint::parse("v3=10"));
+        assert_eq!(Ok(Watchpoint::Register(Register::I, Some(0x2a0))),
+                   Watchpoint::parse("i=2a0"));
+        assert!(Watchpoint::parse("v3=100").is_err());
+        assert!(Watchpoint::parse("vg").is_err());
+        assert!(Watchpoint::parse("r:300=1").is_err());
+        assert!(Watchpoint::parse("x").is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for s in &["r:300", "w:2a0", "v3", "vf=1", "i", "i=2a0"] {
+            assert_eq!(*s, Watchpoint::parse(s).unwrap().to_string());
+        }
+    }
+
+}
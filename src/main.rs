@@ -1,57 +1,190 @@
-mod chip8;
- 
+extern crate bincode;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sha1_smol;
 extern crate sdl2;
-extern crate time;
-
-use chip8::{GFX_H,GFX_W,Mode};
-use chip8::emu::Emu;
-use chip8::ui::Ui;
-use chip8::metro::Metronome;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+
+mod chip8;
+
+use chip8::{Mode,NUM_PLANES,Plane};
+use chip8::asm;
+use chip8::config::Config;
+use chip8::debugger::{Command, Debugger};
+use chip8::disasm::{self, Syntax};
+use chip8::emu::{Emu, EmuError, FRAMES_PER_SECOND};
+use chip8::gamepad::PadEvent;
+use chip8::headless::{self, Limit, Script};
+use chip8::keymap::Action;
+use chip8::palette::{self, Palette};
+use chip8::phosphor;
+use chip8::quirks::{self, Quirks};
+use chip8::rewind::Rewind;
+use chip8::romdb::{self, Platform, RomDb, RomInfo};
+use chip8::state::{self, Slots};
+use chip8::scheduler::{self, Scheduler};
+use chip8::timing::{self, Timing};
+use chip8::ui::{self, Scaling, Ui};
+use chip8::movie::{Input, Movie, Session};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Mod;
+use std::collections::HashMap;
 use std::env;
-use std::io::Read;
-use std::path::Path;
-use std::fs::File;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::io::{self, BufRead, Read};
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::process;
 use std::sync::mpsc::{self, Sender, Receiver};
 use std::thread;
+use std::time::{Duration, Instant};
+
+// Exit codes: when something fails, or when the command line is invalid.
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
 
-// Load the emulator with the indicated ROM. 
-fn load_rom(emu: &mut Emu, path_to_rom: &Path) { 
-    let mut file = File::open(&path_to_rom).unwrap();
+const USAGE: &str = "\
+Usage: chip8 [run] [OPTIONS] PATH_TO_ROM
+       chip8 run --headless (--cycles N | --frames N) [OPTIONS] PATH_TO_ROM
+       chip8 disasm [--syntax SYNTAX] [--xo-chip] PATH_TO_ROM
+       chip8 asm PATH_TO_SOURCE [-o PATH_TO_ROM]
+       chip8 info PATH_TO_ROM
+       chip8 --help";
+
+// The usage followed by a description of each option, for --help.
+fn help() -> String {
+    format!("\
+{usage}
+
+Runs a CHIP-8, SUPER-CHIP or XO-CHIP rom, or disassembles, assembles or
+describes one. Settings not given are taken from the rom database.
+
+Options:
+  --quirks PRESET         Opcode behaviour: {quirks}
+  --mode PLATFORM         Platform to start in: {platforms}
+  --xo-chip               Same as --mode xochip
+  --timing TIMING         Instruction timing: {timings}
+  --cycles-per-frame N    Instructions per frame, given fixed timing
+  --speed N               Multiple of the normal speed, {min} to {max}
+  --seed N                Seed for the random number generator
+  --scale N               Window size, in pixels per pixel (default {scale})
+  --fullscreen            Fill the screen
+  --scaling SCALING       Scaling to the window: {scalings}
+  --mute                  Never beep
+  --paused                Start paused
+  --theme THEME           Colors of the screen: {themes}
+  --colors LIST           Up to 4 colors as #rrggbb, separated by commas
+  --phosphor              Fade pixels out, as on a CRT, against flicker
+  --decay MS              Time pixels take to fade (default {decay})
+  --keymap PATH           Key bindings to use, as a JSON object
+  --debug                 Start in the debugger
+  --state-format FORMAT   Save state format: {formats}
+  --rewind-seconds N      Seconds of play kept to rewind (default 30)
+  --rewind-megabytes N    Memory kept for rewinding (default 64)
+  --record PATH           Record a movie of the input
+  --play PATH             Play back a movie
+  --headless              Run without a window, as fast as possible
+  --cycles N              Headless: cycles to run for
+  --frames N              Headless: frames to run for
+  --input PATH            Headless: script of keys to press
+  --dump PATH             Headless: write the final state here
+  -h, --help              Show this help
+", usage = USAGE, quirks = quirks::PRESET_NAMES.join(", "), 
+       platforms = romdb::PLATFORM_NAMES.join(", "),
+       timings = timing::TIMING_NAMES.join(", "),
+       min = scheduler::MIN_SPEED, max = scheduler::MAX_SPEED,
+       scale = ui::DEFAULT_SCALE, themes = palette::THEME_NAMES.join(", "),
+       decay = phosphor::DEFAULT_DECAY_MS,
+       scalings = ui::SCALING_NAMES.join(", "),
+       formats = state::FORMAT_NAMES.join(", "))
+}
+
+// Print the usage and exit, for an invalid command line.
+fn usage_error() -> ! {
+    eprintln!("{}\nTry 'chip8 --help' for more information.", USAGE);
+    process::exit(EXIT_USAGE);
+}
+
+// The config file, falling back to the defaults when it cannot be read.
+fn load_config() -> Config {
+    Config::load().unwrap_or_else(|err| {
+        eprintln!("Failed to read the config file: {}", err);
+        Config::default()
+    })
+}
+
+// The rom database, falling back to the bundled one when the user's 
+// overrides cannot be read.
+fn load_romdb() -> RomDb {
+    RomDb::load().unwrap_or_else(|err| {
+        eprintln!("Failed to read the rom database: {}", err);
+        RomDb::bundled()
+    })
+}
+
+// Read the indicated ROM. 
+fn read_rom(path_to_rom: &Path) -> Result<Vec<u8>, Box<dyn Error>> { 
+    let mut file = File::open(path_to_rom)?;
     let mut rom: Vec<u8> = Vec::new();
-    file.read_to_end(&mut rom).unwrap();
-    emu.load_rom(rom);
+    file.read_to_end(&mut rom)?;
+    Ok(rom)
 }
 
 // Messages that get passed from the ui to the emulator.
-enum UiToEmuMsg { Keys([bool; 16]), Paused(bool), Quit, Reset }
+enum UiToEmuMsg { 
+    Keys([bool; 16]), 
+    Paused(bool), 
+    Quit, 
+    Reset, 
+    Debug(Command),
+    SaveState(u8),
+    LoadState(u8),
+    Rewind(bool),
+    FrameAdvance,
+    // Run at a multiple of the normal speed.
+    Speed(f64),
+    // Run as fast as possible, or stop doing so.
+    FastForward(bool),
+}
 
 // Messages that get passed from the emulator to the ui.
-enum EmuToUiMsg { Beeping(bool), Draw(Mode, Box<[[bool; GFX_H]; GFX_W]>), QuitAck }
+enum EmuToUiMsg { 
+    Beeping(bool), 
+    Draw(bool, Box<[Plane; NUM_PLANES]>), 
+    Sound(Option<[u8; 16]>, u8), 
+    Error(EmuError), 
+    QuitAck 
+}
 
 // Drives user interaction. Responsible for processing keypresses, updating
 // the screen and playing audible beeps. Communicates with the emulator by
 // exchanging messages across a two way channel. 
 //
 // Runs on the main thread.
-fn ui_exec(mut ui: Ui, tx: Sender<UiToEmuMsg>, rx: Receiver<EmuToUiMsg>) {
-    let mut refresh_gfx_rate = Metronome::new(120);
-    let mut paused = false;
+fn ui_exec(mut ui: Ui, tx: Sender<UiToEmuMsg>, rx: Receiver<EmuToUiMsg>,
+           mut speed: f64, mut paused: bool, path_to_rom: &Path) {
+    tx.send(UiToEmuMsg::Speed(speed)).unwrap();
+    tx.send(UiToEmuMsg::Paused(paused)).unwrap();
     'ui_exec_loop: loop {
-        process_key_presses(&mut ui, &tx, &mut paused); 
-        if process_emu_events(&mut ui, &rx, &paused, &mut refresh_gfx_rate) {
+        process_key_presses(&mut ui, &tx, &mut paused, &mut speed, 
+                            path_to_rom); 
+        if process_emu_events(&mut ui, &rx) {
             break 'ui_exec_loop;
         }
+        ui.fade();
         // Short sleep to free up cpu cycles
         let dur = std::time::Duration::from_millis(1);
         std::thread::sleep(dur);    
     }
 }
 
-// Poll for and handle key press events. 
+// Poll for and handle key press and controller events. Keys and buttons
+// bound to actions signal the emulator to take them, others update the
+// keypad.
 fn process_key_presses(ui: &mut Ui, tx: &Sender<UiToEmuMsg>, 
-                    paused: &mut bool) {
+                    paused: &mut bool, speed: &mut f64, path_to_rom: &Path) {
     match ui.poll_event() {
         None => {},
         Some(event) => {
@@ -59,35 +192,60 @@ fn process_key_presses(ui: &mut Ui, tx: &Sender<UiToEmuMsg>,
                 Event::Quit{..} => {
                     tx.send(UiToEmuMsg::Paused(*paused)).unwrap(); 
                 },
-                Event::KeyDown{keycode,..} => match keycode {
-                    Option::Some(Keycode::Escape) => {
-                        // Signal emulator with intention to quit
-                        // and allow it to shutdown gracefully.
-                        tx.send(UiToEmuMsg::Quit).unwrap(); 
-                    },
-                    Option::Some(Keycode::Return) => {
-                        // Signal emulator to pause.
-                        *paused ^= true; 
-                        tx.send(UiToEmuMsg::Paused(*paused)).unwrap();
-                    },
-                    Option::Some(Keycode::Backspace) => {
-                        // Signal emulator to reset.
-                        tx.send(UiToEmuMsg::Reset).unwrap();
-                        *paused = false;
-                        tx.send(UiToEmuMsg::Paused(*paused)).unwrap();
-                    },
-                    _ => if !*paused {
-                        // A key was pressed, signal emulator with updated
-                        // key states.
-                        tx.send(UiToEmuMsg::Keys(
-                                ui.get_updated_keys())).unwrap();
-                    }, 
+                Event::KeyDown{scancode: Some(scancode),keymod,repeat,..} => {
+                    let shift = keymod.intersects(Mod::LSHIFTMOD | 
+                                                  Mod::RSHIFTMOD);
+                    match ui.keymap().action(scancode, shift) {
+                        // Held actions start once, however long the key 
+                        // is held.
+                        Some(Action::Rewind) | Some(Action::FastForward)
+                            if repeat => {},
+                        Some(action) => start_action(ui, tx, paused, speed, 
+                                                     path_to_rom, action),
+                        None => {
+                            // A key was pressed, signal emulator with 
+                            // updated key states. Passed on while paused 
+                            // too, so keys can be held while advancing frame
+                            // by frame.
+                            tx.send(UiToEmuMsg::Keys(
+                                    ui.get_updated_keys())).unwrap();
+                        },
+                    }
                 },
-                Event::KeyUp{..} => if !*paused {
-                    // A key was released, signal emulator with updated
-                    // key states.
-                    tx.send(UiToEmuMsg::Keys(
-                            ui.get_updated_keys())).unwrap();
+                Event::KeyUp{scancode: Some(scancode),keymod,..} => {
+                    let shift = keymod.intersects(Mod::LSHIFTMOD | 
+                                                  Mod::RSHIFTMOD);
+                    match ui.keymap().action(scancode, shift) {
+                        Some(action) => stop_action(tx, action),
+                        None => {
+                            // A key was released, signal emulator with 
+                            // updated key states.
+                            tx.send(UiToEmuMsg::Keys(
+                                    ui.get_updated_keys())).unwrap();
+                        },
+                    }
+                },
+                Event::Window{win_event: WindowEvent::SizeChanged(..), ..} |
+                Event::Window{win_event: WindowEvent::Exposed, ..} => {
+                    // Scaled to the new size, or drawn again where it was
+                    // hidden.
+                    ui.redraw();
+                },
+                Event::ControllerButtonDown{..} | 
+                Event::ControllerButtonUp{..} | 
+                Event::ControllerDeviceAdded{..} | 
+                Event::ControllerDeviceRemoved{..} => {
+                    for pad_event in ui.controller_event(&event) {
+                        match pad_event {
+                            PadEvent::Keys => tx.send(UiToEmuMsg::Keys(
+                                    ui.get_updated_keys())).unwrap(),
+                            PadEvent::Pressed(action) => 
+                                start_action(ui, tx, paused, speed, 
+                                             path_to_rom, action),
+                            PadEvent::Released(action) => 
+                                stop_action(tx, action),
+                        }
+                    }
                 },
                 _ => {}
             }
@@ -95,21 +253,113 @@ fn process_key_presses(ui: &mut Ui, tx: &Sender<UiToEmuMsg>,
     }
 }
 
+// Take an action, on a key or button being pressed.
+fn start_action(ui: &mut Ui, tx: &Sender<UiToEmuMsg>, paused: &mut bool, 
+                speed: &mut f64, path_to_rom: &Path, action: Action) {
+    match action {
+        Action::Quit => {
+            // Signal emulator with intention to quit and allow it to 
+            // shutdown gracefully.
+            tx.send(UiToEmuMsg::Quit).unwrap(); 
+        },
+        Action::Pause => {
+            // Signal emulator to pause.
+            *paused ^= true; 
+            tx.send(UiToEmuMsg::Paused(*paused)).unwrap();
+        },
+        Action::Reset => {
+            // Signal emulator to reset.
+            ui.clear_error();
+            tx.send(UiToEmuMsg::Reset).unwrap();
+            *paused = false;
+            tx.send(UiToEmuMsg::Paused(*paused)).unwrap();
+        },
+        Action::Rewind => {
+            // Signal emulator to rewind for as long as the key is held.
+            ui.clear_error();
+            tx.send(UiToEmuMsg::Rewind(true)).unwrap();
+        },
+        Action::FrameAdvance => {
+            // Signal emulator to pause, if not already, and run a single 
+            // frame.
+            *paused = true;
+            tx.send(UiToEmuMsg::Paused(*paused)).unwrap();
+            tx.send(UiToEmuMsg::FrameAdvance).unwrap();
+        },
+        Action::Slower => {
+            // Signal emulator to slow down a step.
+            *speed = scheduler::slower(*speed);
+            println!("Speed {}x", speed);
+            tx.send(UiToEmuMsg::Speed(*speed)).unwrap();
+        },
+        Action::Faster => {
+            // Signal emulator to speed up a step.
+            *speed = scheduler::faster(*speed);
+            println!("Speed {}x", speed);
+            tx.send(UiToEmuMsg::Speed(*speed)).unwrap();
+        },
+        Action::FastForward => {
+            // Signal emulator to run as fast as it can for as long as the
+            // key is held.
+            tx.send(UiToEmuMsg::FastForward(true)).unwrap();
+        },
+        Action::SaveState(slot) => {
+            tx.send(UiToEmuMsg::SaveState(slot)).unwrap();
+        },
+        Action::LoadState(slot) => {
+            tx.send(UiToEmuMsg::LoadState(slot)).unwrap();
+        },
+        Action::NextTheme => {
+            let palette = ui.next_palette();
+            println!("Theme {}", palette.theme_name().unwrap_or("custom"));
+        },
+        Action::Fullscreen => ui.toggle_fullscreen(),
+        Action::Phosphor => match ui.toggle_phosphor() {
+            true => println!("Phosphor on"),
+            false => println!("Phosphor off"),
+        },
+        Action::Screenshot => {
+            let path = screenshot_path(path_to_rom);
+            match ui.screenshot(&path) {
+                Ok(()) => println!("Saved screenshot to {}", path.display()),
+                Err(err) => eprintln!("Failed to save screenshot: {}", err),
+            }
+        },
+    }
+}
+
+// Stop an action held down, on its key or button being released.
+fn stop_action(tx: &Sender<UiToEmuMsg>, action: Action) {
+    match action {
+        Action::Rewind => tx.send(UiToEmuMsg::Rewind(false)).unwrap(),
+        Action::FastForward => 
+            tx.send(UiToEmuMsg::FastForward(false)).unwrap(),
+        _ => {},
+    }
+}
+
+// The first free path for a screenshot next to the rom, such as 
+// roms/brix.screenshot1.ppm.
+fn screenshot_path(path_to_rom: &Path) -> PathBuf {
+    (1..).map(|n| path_to_rom.with_extension(format!("screenshot{}.ppm", n)))
+        .find(|path| !path.exists())
+        .expect("a free path")
+}
+
 // Poll for and handle emulator events. Returns true if emulator acknowledged 
 // earlier quit signal. 
-fn process_emu_events(ui: &mut Ui, rx: &Receiver<EmuToUiMsg>, paused: &bool, 
-                      refresh_gfx_rate: &mut Metronome) -> bool {
+fn process_emu_events(ui: &mut Ui, rx: &Receiver<EmuToUiMsg>) -> bool {
 
     if let Ok(emu_event) = rx.try_recv() {
         match emu_event {
             // Handle beeb state change signalled by emulator.
             EmuToUiMsg::Beeping(on) => ui.beep(on),
             // Handle draw event signalled by emulator.
-            EmuToUiMsg::Draw(ref mode, ref gfx) => {
-                refresh_gfx_rate.on_tick(|| {
-                    if !*paused { ui.refresh_gfx(*mode, gfx); }
-                });
-            },
+            EmuToUiMsg::Draw(hires, ref gfx) => ui.refresh_gfx(hires, gfx),
+            // Handle XO-CHIP audio pattern change signalled by emulator.
+            EmuToUiMsg::Sound(pattern, pitch) => ui.set_sound(pattern, pitch),
+            // The emulator has halted, it will resume once reset.
+            EmuToUiMsg::Error(ref err) => ui.show_error(err),
             // Emulator has acknowledged the earlier quit signal.
             // The ui thread may shutdown in response.
             EmuToUiMsg::QuitAck => return true,
@@ -120,97 +370,949 @@ fn process_emu_events(ui: &mut Ui, rx: &Receiver<EmuToUiMsg>, paused: &bool,
 }
 
 // Drives the emulator. Communicates with the user interface by exchanging
-// messages across a two way channel. 
+// messages across a two way channel. Should the rom fail, the emulator halts
+// and reports the error to the ui, until it is reset. When debugging, the
+// debugger decides when the emulator may run. When a movie is recorded, it
+// is written out on quit.
 //
 // Assigned its own thread. 
-fn emu_exec(mut emu: Emu, tx: Sender<EmuToUiMsg>, rx: Receiver<UiToEmuMsg>) {
-    let mut clock_rate = Metronome::new(500);
-    let mut update_timers_rate = Metronome::new(60);
-    let mut paused = false;
-    let mut beeping = false;
+fn emu_exec(mut emu: Emu, mut debugger: Option<Debugger>, slots: Slots,
+            mut rewind: Rewind, mut movie: Option<(Session, PathBuf)>,
+            tx: Sender<EmuToUiMsg>, rx: Receiver<UiToEmuMsg>) {
+    let mut scheduler = Scheduler::new(FRAMES_PER_SECOND, Instant::now());
+    let mut flags = EmuFlags::default();
     'emu_exec_loop: loop {
-        if process_ui_events(&mut emu, &tx, &rx, &mut paused) {
+        if process_ui_events(&mut emu, &mut debugger, &slots, &mut movie, &tx,
+                             &rx, &mut flags) {
             break 'emu_exec_loop;
         }
-        signal_draw_event(&mut emu, &tx, &paused, &mut clock_rate); 
-        update_timers(&mut emu, &tx, &paused, &mut beeping, 
-                      &mut update_timers_rate);
-        // Short sleep to free up cpu cycles
-        let dur = std::time::Duration::from_millis(1);
+        flags.stopped = (flags.paused && !flags.advancing) || flags.halted || 
+            flags.rewinding || 
+            debugger.as_ref().is_some_and(|dbg| !dbg.running());
+ 
+        // Fast forwarding runs a frame on every pass, without sleeping, as
+        // long as there are frames to run.
+        let now = Instant::now();
+        scheduler.set_speed(flags.speed);
+        if flags.fast_forward {
+            scheduler.restart(now);
+        }
+        if scheduler.frame_due(now) {
+            run_frame(&mut emu, &mut debugger, &mut rewind, &mut movie, &tx,
+                      &mut flags);
+        }
+        if flags.fast_forward && !flags.stopped {
+            continue;
+        }
+        // Sleep until the next frame is due, waking up often enough to
+        // respond to the ui.
+        let dur = scheduler.until_next_frame(Instant::now())
+            .min(Duration::from_millis(1));
         std::thread::sleep(dur);   
     }
+    if let Some((session, path)) = movie {
+        if session.recording() {
+            match session.finish(&emu).write(&path) {
+                Ok(()) => println!("Saved movie to {}", path.display()),
+                Err(err) => eprintln!("Failed to save movie: {}", err),
+            }
+        }
+    }
+}
+
+// The run state of the emulator thread.
+struct EmuFlags {
+    // Paused by the user.
+    paused: bool,
+    // Running to the end of the frame while paused.
+    advancing: bool,
+    // Halted by an error, until reset.
+    halted: bool,
+    // Playing back the rewind buffer instead of running.
+    rewinding: bool,
+    // Not executing instructions, for any of the above reasons or because
+    // the debugger holds the emulator.
+    stopped: bool,
+    // The beep state last signalled to the ui.
+    beeping: bool,
+    // The multiple of the normal speed to run at.
+    speed: f64,
+    // Running as fast as possible, regardless of the speed.
+    fast_forward: bool,
+}
+
+impl Default for EmuFlags {
+
+    fn default() -> EmuFlags {
+        EmuFlags {
+            paused: false,
+            advancing: false,
+            halted: false,
+            rewinding: false,
+            stopped: false,
+            beeping: false,
+            speed: 1.0,
+            fast_forward: false,
+        }
+    }
+
 }
 
 // Poll for and handle UI events. Returns true if Quit signal received from UI.
-fn process_ui_events(emu: &mut Emu, tx: &Sender<EmuToUiMsg>,  
-                     rx: &Receiver<UiToEmuMsg>, paused: &mut bool) -> bool {
-    match rx.try_recv() {
-        Ok(ui_to_emu_msg) => 
-            match ui_to_emu_msg {
-                // New key press states.
-                UiToEmuMsg::Keys(new_keys) => emu.keys = new_keys,
-                // Reset everything.
-                UiToEmuMsg::Reset => emu.reset(),
-                // Pause or unpause.
-                UiToEmuMsg::Paused(p) => *paused = p,
-                // Acknowledge quit and shut down gracefully.
-                UiToEmuMsg::Quit => {
-                    tx.send(EmuToUiMsg::QuitAck).unwrap();
-                    return true;
-                }, 
+fn process_ui_events(emu: &mut Emu, debugger: &mut Option<Debugger>,
+                     slots: &Slots, movie: &mut Option<(Session, PathBuf)>,
+                     tx: &Sender<EmuToUiMsg>, rx: &Receiver<UiToEmuMsg>, 
+                     flags: &mut EmuFlags) -> bool {
+    if let Ok(ui_to_emu_msg) = rx.try_recv() {
+        match ui_to_emu_msg {
+            // New key press states.
+            UiToEmuMsg::Keys(new_keys) => 
+                apply_input(emu, movie, Input::keys(&new_keys)),
+            // Reset everything.
+            UiToEmuMsg::Reset => {
+                apply_input(emu, movie, Input::Reset);
+                flags.halted = false;
             },
-        _ => {},
+            // Pause or unpause.
+            UiToEmuMsg::Paused(p) => flags.paused = p,
+            // Run to the end of the frame, then stay paused.
+            UiToEmuMsg::FrameAdvance => flags.advancing = true,
+            // Change how fast frames are run.
+            UiToEmuMsg::Speed(speed) => flags.speed = speed,
+            UiToEmuMsg::FastForward(on) => flags.fast_forward = on,
+            // Start or stop playing back the rewind buffer. Rewinding also
+            // recovers from errors. A movie picks up recording from where
+            // the rewinding stopped.
+            UiToEmuMsg::Rewind(on) => {
+                if flags.rewinding && !on {
+                    if let Some((ref mut session, _)) = *movie {
+                        session.rerecord(emu);
+                    }
+                }
+                flags.rewinding = on;
+                if on { flags.halted = false; }
+            },
+            // Debugger command typed on the command line.
+            UiToEmuMsg::Debug(cmd) => if let Some(ref mut dbg) = *debugger {
+                print_debug_output(&dbg.handle(cmd, emu));
+            },
+            // Save the full emulator state.
+            UiToEmuMsg::SaveState(slot) => match slots.save(slot, emu) {
+                Ok(path) => println!("Saved state to {}", path.display()),
+                Err(err) => eprintln!("Failed to save state: {}", err),
+            },
+            // Restore the emulator state, which also recovers from errors.
+            // A movie picks up recording from the restored state, which
+            // must have been saved earlier on in its run.
+            UiToEmuMsg::LoadState(slot) => {
+                let loaded = slots.read(slot, emu)
+                    .and_then(|(path, saved)| {
+                        if let Some((ref session, _)) = *movie {
+                            session.check_state(emu, &saved)?;
+                        }
+                        Ok((path, saved))
+                    });
+                match loaded {
+                    Ok((path, saved)) => {
+                        emu.restore(saved);
+                        println!("Loaded state from {}", path.display());
+                        flags.halted = false;
+                        if let Some((ref mut session, _)) = *movie {
+                            session.rerecord(emu);
+                        }
+                    },
+                    Err(err) => eprintln!("Failed to load state: {}", err),
+                }
+            },
+            // Acknowledge quit and shut down gracefully.
+            UiToEmuMsg::Quit => {
+                tx.send(EmuToUiMsg::QuitAck).unwrap();
+                return true;
+            }, 
+        }
     }  
     false
 }
 
-// Signal the ui with a draw event.
-fn signal_draw_event(emu: &mut Emu, tx: &Sender<EmuToUiMsg>, paused: &bool,
-                     clock_rate: &mut Metronome) {
-    clock_rate.on_tick(|| {
-        if !paused {
-            let _ = &mut emu.execute_cycle();
-            if emu.draw {
-                tx.send(EmuToUiMsg::Draw(emu.mode, Box::new(emu.gfx))).unwrap();
-                emu.draw = false;
+// Pass input from the user on to the emulator, by way of the movie if any.
+fn apply_input(emu: &mut Emu, movie: &mut Option<(Session, PathBuf)>, 
+               input: Input) {
+    match *movie {
+        Some((ref mut session, _)) => session.input(emu, input),
+        None => input.apply(emu),
+    }
+}
+
+// Run a frame's worth of cycles, then signal the ui with a single draw 
+// event. Should a cycle fail, the emulator is halted and the ui signalled 
+// with the error instead. Each frame is recorded in the rewind buffer. While
+// rewinding, the emulator steps back a frame instead.
+fn run_frame(emu: &mut Emu, debugger: &mut Option<Debugger>,
+             rewind: &mut Rewind, movie: &mut Option<(Session, PathBuf)>,
+             tx: &Sender<EmuToUiMsg>, flags: &mut EmuFlags) {
+    if flags.rewinding && rewind.pop(emu) {
+        signal_beeping(emu, tx, flags);
+    }
+    while !flags.stopped {
+        if let Some(ref mut dbg) = *debugger {
+            if let Some(report) = dbg.before_cycle(emu) {
+                print_debug_output(&report);
             }
-         } 
-    });
+            if !dbg.running() { break; }
+        }
+        if let Some((ref mut session, _)) = *movie {
+            if session.before_cycle(emu) {
+                println!("Movie finished");
+            }
+        }
+        let frame_done = match emu.step() {
+            Ok(frame_done) => frame_done,
+            Err(err) => {
+                flags.halted = true;
+                tx.send(EmuToUiMsg::Error(err)).unwrap();
+                break;
+            },
+        };
+        if frame_done {
+            rewind.push(emu);
+            flags.advancing = false;
+            signal_beeping(emu, tx, flags);
+        }
+        if let Some(ref mut dbg) = *debugger {
+            if let Some(report) = dbg.after_cycle(emu) {
+                print_debug_output(&report);
+            }
+        }
+        if frame_done { break; }
+    }
+    signal_changes(emu, tx);
+}
+
+// Signal the ui with the graphics and sound, where changed.
+fn signal_changes(emu: &mut Emu, tx: &Sender<EmuToUiMsg>) {
+    if emu.draw {
+        tx.send(EmuToUiMsg::Draw(emu.hires(), Box::new(emu.gfx))).unwrap();
+        emu.draw = false;
+    }
+    if emu.sound_changed {
+        let (pattern, pitch) = emu.sound();
+        tx.send(EmuToUiMsg::Sound(pattern, pitch)).unwrap();
+        emu.sound_changed = false;
+    }
+}
+
+// Signal the ui if the beep state changed.
+fn signal_beeping(emu: &Emu, tx: &Sender<EmuToUiMsg>, flags: &mut EmuFlags) {
+    if flags.beeping != emu.beeping() {
+        flags.beeping ^= true; 
+        tx.send(EmuToUiMsg::Beeping(flags.beeping)).unwrap();
+    }
+}
+
+// Print the output of the debugger, followed by a fresh prompt.
+fn print_debug_output(output: &str) {
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    print!("(chip8) ");
+    io::Write::flush(&mut io::stdout()).unwrap();
+}
+
+// Reads debugger commands from standard input and passes them on to the
+// emulator. Invalid commands are reported straight away.
+//
+// Assigned its own thread.
+fn debug_repl_exec(tx: Sender<UiToEmuMsg>) {
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            print_debug_output("");
+            continue;
+        }
+        match Command::parse(&line) {
+            Ok(cmd) => if tx.send(UiToEmuMsg::Debug(cmd)).is_err() { break; },
+            Err(msg) => print_debug_output(&msg),
+        }
+    }
 }
 
-// Update the emulator timers and signal the ui if the beep state changed.
-fn update_timers(emu: &mut Emu, tx: &Sender<EmuToUiMsg>, paused: &bool, 
-                 beeping: &mut bool, update_timers_rate: &mut Metronome) {
-    update_timers_rate.on_tick(|| {
-        if !paused { 
-            emu.update_timers(); 
-            if *beeping != emu.beeping() {
-                *beeping ^= true; 
-                tx.send(EmuToUiMsg::Beeping(*beeping)).unwrap();
+// Options given on the command line.
+struct Options<'a> {
+    platform: Option<Platform>,
+    quirks: Option<Quirks>,
+    debug: bool,
+    // Window settings.
+    scale: Option<usize>,
+    fullscreen: bool,
+    scaling: Option<Scaling>,
+    mute: bool,
+    paused: bool,
+    // Whether pixels fade out, and over how many milliseconds.
+    phosphor: bool,
+    decay: Option<u64>,
+    // Colors in hex, in place of those of the theme.
+    theme: Option<Palette>,
+    colors: Option<Vec<&'a str>>,
+    // Key bindings on top of the user's and the rom's, see Keymap::bind.
+    keymap: Option<&'a Path>,
+    state_format: state::Format,
+    rewind_seconds: usize,
+    rewind_megabytes: usize,
+    // Seed for the random number generator, random if not given.
+    seed: Option<u64>,
+    timing: Option<Timing>,
+    cycles_per_frame: Option<u64>,
+    // Multiple of the normal speed to run at.
+    speed: Option<f64>,
+    // Run without a ui for a limited time, see headless_main.
+    headless: bool,
+    limit: Option<Limit>,
+    input: Option<&'a Path>,
+    dump: Option<&'a Path>,
+    // Movie to record to or play back.
+    record: Option<&'a Path>,
+    play: Option<&'a Path>,
+    path_to_rom: &'a Path,
+}
+
+// Parse the command line: the rom path, optionally preceded by the options
+// described by help. Prints usage and exits on invalid arguments, or prints
+// help and exits when asked.
+fn parse_args(args: &[String]) -> Options<'_> {
+    let mut quirks = None;
+    let mut platform = None;
+    let mut debug = false;
+    let mut scale = None;
+    let mut fullscreen = false;
+    let mut scaling = None;
+    let mut mute = false;
+    let mut paused = false;
+    let mut phosphor = false;
+    let mut decay = None;
+    let mut theme = None;
+    let mut colors = None;
+    let mut keymap = None;
+    let mut state_format = state::Format::Binary;
+    let mut rewind_seconds = 30;
+    let mut rewind_megabytes = 64;
+    let mut seed = None;
+    let mut timing = None;
+    let mut cycles_per_frame = None;
+    let mut speed = None;
+    let mut headless = false;
+    let mut limit = None;
+    let mut input = None;
+    let mut dump = None;
+    let mut record = None;
+    let mut play = None;
+    let mut path_to_rom = None;
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quirks" => match args.next().and_then(|p| Quirks::preset(p)) {
+                Some(preset) => quirks = Some(preset),
+                None => {
+                    eprintln!("Expected quirks preset, one of: {}", 
+                              quirks::PRESET_NAMES.join(", "));
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--mode" => match args.next().and_then(|p| Platform::from_name(p)) {
+                Some(p) => platform = Some(p),
+                None => {
+                    eprintln!("Expected platform, one of: {}", 
+                              romdb::PLATFORM_NAMES.join(", "));
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--xo-chip" => platform = Some(Platform::XoChip),
+            "--debug" => debug = true,
+            "--scale" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) if n > 0 => scale = Some(n),
+                _ => {
+                    eprintln!("Expected scale, a number above 0");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--fullscreen" => fullscreen = true,
+            "--scaling" => 
+                match args.next().and_then(|s| Scaling::from_name(s)) {
+                    Some(s) => scaling = Some(s),
+                    None => {
+                        eprintln!("Expected scaling, one of: {}", 
+                                  ui::SCALING_NAMES.join(", "));
+                        process::exit(EXIT_USAGE);
+                    }
+                },
+            "--mute" => mute = true,
+            "--paused" => paused = true,
+            "--phosphor" => phosphor = true,
+            "--decay" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) if n > 0 => decay = Some(n),
+                _ => {
+                    eprintln!("Expected decay time in milliseconds, above 0");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--theme" => match args.next().and_then(|t| Palette::theme(t)) {
+                Some(palette) => theme = Some(palette),
+                None => {
+                    eprintln!("Expected theme, one of: {}", 
+                              palette::THEME_NAMES.join(", "));
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--colors" => match args.next().map(|list| split_colors(list)) {
+                Some(list) if Palette::default().with_colors(&list).is_ok() =>
+                    colors = Some(list),
+                _ => {
+                    eprintln!("Expected 1 to 4 colors as #rrggbb, separated \
+                               by commas");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--keymap" => match args.next() {
+                Some(path) => keymap = Some(Path::new(path)),
+                None => {
+                    eprintln!("Expected path to the key bindings");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "-h" | "--help" => {
+                print!("{}", help());
+                process::exit(0);
+            },
+            "--rewind-seconds" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => rewind_seconds = n,
+                None => {
+                    eprintln!("Expected number of seconds to rewind");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--rewind-megabytes" => 
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => rewind_megabytes = n,
+                    None => {
+                        eprintln!("Expected rewind memory budget in megabytes");
+                        process::exit(EXIT_USAGE);
+                    }
+                },
+            "--seed" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => seed = Some(n),
+                None => {
+                    eprintln!("Expected random seed, a number");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--timing" => match args.next().and_then(|t| Timing::from_name(t)) {
+                Some(t) => timing = Some(t),
+                None => {
+                    eprintln!("Expected timing, one of: {}", 
+                              timing::TIMING_NAMES.join(", "));
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--cycles-per-frame" => 
+                match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => cycles_per_frame = Some(n),
+                    _ => {
+                        eprintln!("Expected number of cycles per frame");
+                        process::exit(EXIT_USAGE);
+                    }
+                },
+            "--speed" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) if (scheduler::MIN_SPEED..=scheduler::MAX_SPEED)
+                    .contains(&n) => speed = Some(n),
+                _ => {
+                    eprintln!("Expected speed from {} to {}", 
+                              scheduler::MIN_SPEED, scheduler::MAX_SPEED);
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--headless" => headless = true,
+            "--cycles" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => limit = Some(Limit::Cycles(n)),
+                None => {
+                    eprintln!("Expected number of cycles to run");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--frames" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => limit = Some(Limit::Frames(n)),
+                None => {
+                    eprintln!("Expected number of frames to run");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--input" => match args.next() {
+                Some(path) => input = Some(Path::new(path)),
+                None => {
+                    eprintln!("Expected path to input script");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--dump" => match args.next() {
+                Some(path) => dump = Some(Path::new(path)),
+                None => {
+                    eprintln!("Expected path to write the dump to");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--record" => match args.next() {
+                Some(path) => record = Some(Path::new(path)),
+                None => {
+                    eprintln!("Expected path to record the movie to");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--play" => match args.next() {
+                Some(path) => play = Some(Path::new(path)),
+                None => {
+                    eprintln!("Expected path to the movie to play");
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--state-format" => 
+                match args.next().and_then(|f| state::Format::from_name(f)) {
+                    Some(format) => state_format = format,
+                    None => {
+                        eprintln!("Expected save state format, one of: {}", 
+                                  state::FORMAT_NAMES.join(", "));
+                        process::exit(EXIT_USAGE);
+                    }
+                },
+            _ if path_to_rom.is_none() && !arg.starts_with("--") => 
+                path_to_rom = Some(Path::new(arg)),
+            _ => usage_error(),
+        }
+    }
+    let path_to_rom = match path_to_rom {
+        Some(path_to_rom) => path_to_rom,
+        None => usage_error(),
+    };
+    // A headless run must know when to stop, which a movie does. It has no
+    // way to take input from the user, so cannot record.
+    let headless_invalid = headless && 
+        ((limit.is_none() && play.is_none()) || debug || record.is_some() ||
+         (input.is_some() && play.is_some()));
+    // Only fixed timing runs a set number of cycles per frame.
+    let timing_invalid = 
+        timing == Some(Timing::CosmacVip) && cycles_per_frame.is_some();
+    if headless_invalid || (record.is_some() && play.is_some()) || 
+        timing_invalid {
+        usage_error();
+    }
+    Options { 
+        platform, quirks, debug, scale, fullscreen, scaling, mute, paused, 
+        phosphor, decay, theme, colors, keymap, state_format, rewind_seconds,
+        rewind_megabytes, seed, timing, cycles_per_frame, speed, headless, 
+        limit, input, dump, record, play, path_to_rom
+    }
+}
+
+// Key bindings from a file, a JSON object such as the bindings of the config
+// file.
+fn read_bindings(path: &Path)
+    -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let json = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+// Colors separated by commas, as given on the command line.
+fn split_colors(list: &str) -> Vec<&str> {
+    list.split(',').map(str::trim).collect()
+}
+
+// The mode, quirks and timing to run the rom with. Settings given on the
+// command line take precedence over those in the rom database, which take
+// precedence over those in the config file, and then the defaults. Roms 
+// with the .xo8 extension start in XO-CHIP mode by default.
+fn settings(opts: &Options, info: Option<&RomInfo>, config: &Config) 
+    -> (Mode, Quirks, Timing) {
+    let platform = opts.platform
+        .or_else(|| info.and_then(|info| info.platform));
+    let xo_chip = match platform {
+        Some(platform) => platform.mode() == Mode::XoChip,
+        None => opts.path_to_rom.extension() == Some(OsStr::new("xo8")),
+    };
+    // The config file holds defaults for CHIP-8 roms, XO-CHIP has quirks
+    // of its own.
+    let quirks = opts.quirks
+        .or_else(|| info.and_then(RomInfo::quirks))
+        .or_else(|| if xo_chip {Some(Quirks::xo_chip())} else {None})
+        .or_else(|| config.quirks())
+        .unwrap_or_default();
+    let timing = timing::with_cycles_per_frame(opts.timing, 
+                                               opts.cycles_per_frame)
+        .or_else(|| info.and_then(RomInfo::timing))
+        .or_else(|| config.timing())
+        .unwrap_or_default();
+    let mode = if xo_chip {Mode::XoChip} else {Mode::Standard};
+    (mode, quirks, timing)
+}
+
+// Run the emulator without a ui, as fast as it goes, feeding it the scripted
+// input or the movie if any. A movie is played to the end unless told
+// otherwise. Afterwards the registers and framebuffer are printed, or
+// written to the dump file. Exits with an error if the emulator fails.
+fn headless_main(mut emu: Emu, session: Option<Session>, opts: &Options) {
+    let script = match opts.input {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(source) => match Script::parse(&source) {
+                Ok(script) => script,
+                Err(err) => {
+                    eprintln!("{}: {}", path.display(), err);
+                    process::exit(EXIT_FAILURE);
+                }
+            },
+            Err(err) => {
+                eprintln!("Failed to read {}: {}", path.display(), err);
+                process::exit(EXIT_FAILURE);
             }
-        }                
-    });
+        },
+        None => Script::default(),
+    };
+    let result = match session {
+        Some(mut session) => {
+            let limit = opts.limit
+                .unwrap_or(Limit::Cycles(session.movie().length));
+            headless::run(&mut emu, limit, |emu| { 
+                session.before_cycle(emu); 
+            })
+        },
+        None => {
+            let limit = opts.limit.expect("limit checked by parse_args");
+            headless::run(&mut emu, limit, script.player())
+        },
+    };
+    let dump = headless::dump(&emu);
+    match opts.dump {
+        Some(path) => if let Err(err) = fs::write(path, dump) {
+            eprintln!("Failed to write {}: {}", path.display(), err);
+            process::exit(EXIT_FAILURE);
+        },
+        None => print!("{}", dump),
+    }
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+// Disassemble a rom to standard output, given the arguments following the
+// disasm subcommand. Prints usage and exits on invalid arguments.
+fn disasm_main(args: &[String]) {
+    let usage = "Usage: chip8 disasm [--syntax SYNTAX] [--xo-chip] PATH_TO_ROM";
+    let mut syntax = Syntax::Octo;
+    let mut xo_chip = false;
+    let mut path_to_rom = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--syntax" => match args.next().and_then(|s| Syntax::from_name(s)) {
+                Some(s) => syntax = s,
+                None => {
+                    eprintln!("Expected syntax, one of: {}", 
+                              disasm::SYNTAX_NAMES.join(", "));
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "--xo-chip" => xo_chip = true,
+            "-h" | "--help" => {
+                println!("{}", usage);
+                process::exit(0);
+            },
+            _ if path_to_rom.is_none() && !arg.starts_with("--") => 
+                path_to_rom = Some(Path::new(arg)),
+            _ => {
+                eprintln!("{}", usage);
+                process::exit(EXIT_USAGE);
+            }
+        }
+    }
+    let path_to_rom = match path_to_rom {
+        Some(path_to_rom) => path_to_rom,
+        None => {
+            eprintln!("{}", usage);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    if path_to_rom.extension() == Some(OsStr::new("xo8")) {
+        xo_chip = true;
+    }
+    let mode = if xo_chip {Mode::XoChip} else {Mode::Standard};
+    let mut rom = Vec::new();
+    if let Err(err) = File::open(path_to_rom)
+                           .and_then(|mut file| file.read_to_end(&mut rom)) {
+        eprintln!("Failed to read {}: {}", path_to_rom.display(), err);
+        process::exit(EXIT_FAILURE);
+    }
+    print!("{}", disasm::disassemble(&rom, mode, syntax));
+}
+
+// Assemble a source file into a rom, given the arguments following the asm
+// subcommand. Without -o, the rom is written next to the source with the 
+// .ch8 extension. Prints usage and exits on invalid arguments.
+fn asm_main(args: &[String]) {
+    let usage = "Usage: chip8 asm PATH_TO_SOURCE [-o PATH_TO_ROM]";
+    let mut path_to_source = None;
+    let mut path_to_rom = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" if path_to_rom.is_none() => match args.next() {
+                Some(path) => path_to_rom = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("{}", usage);
+                    process::exit(EXIT_USAGE);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", usage);
+                process::exit(0);
+            },
+            _ if path_to_source.is_none() && !arg.starts_with('-') => 
+                path_to_source = Some(Path::new(arg)),
+            _ => {
+                eprintln!("{}", usage);
+                process::exit(EXIT_USAGE);
+            }
+        }
+    }
+    let path_to_source = match path_to_source {
+        Some(path_to_source) => path_to_source,
+        None => {
+            eprintln!("{}", usage);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let path_to_rom = path_to_rom
+        .unwrap_or_else(|| path_to_source.with_extension("ch8"));
+    let source = match fs::read_to_string(path_to_source) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", path_to_source.display(), err);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+    let rom = match asm::assemble(&source) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("{}: {}", path_to_source.display(), err);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+    if let Err(err) = fs::write(&path_to_rom, rom) {
+        eprintln!("Failed to write {}: {}", path_to_rom.display(), err);
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+// Describe a rom, given the arguments following the info subcommand: its 
+// size and hash, and what the rom database knows about it. Prints usage and
+// exits on invalid arguments.
+fn info_main(args: &[String]) {
+    let usage = "Usage: chip8 info PATH_TO_ROM";
+    let path_to_rom = match args {
+        [arg] if arg == "-h" || arg == "--help" => {
+            println!("{}", usage);
+            process::exit(0);
+        },
+        [arg] if !arg.starts_with('-') => Path::new(arg),
+        _ => {
+            eprintln!("{}", usage);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let rom = match read_rom(path_to_rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", path_to_rom.display(), err);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+    let romdb = load_romdb();
+    let hash = romdb::rom_hash(&rom);
+    println!("File: {}", path_to_rom.display());
+    println!("Size: {} bytes", rom.len());
+    println!("SHA-1: {}", hash);
+    let info = match romdb.get(&hash) {
+        Some(info) => info,
+        None => return println!("Not in the rom database"),
+    };
+    println!("Title: {}", info.title);
+    if let Some(ref author) = info.author {
+        println!("Author: {}", author);
+    }
+    if let Some(platform) = info.platform {
+        println!("Platform: {}", platform.name());
+    }
+    if let Some(ref quirks) = info.quirks {
+        println!("Quirks: {}", quirks);
+    }
+    if let Some(ref timing) = info.timing {
+        println!("Timing: {}", timing);
+    }
+    if let Some(cycles_per_frame) = info.cycles_per_frame {
+        println!("Cycles per frame: {}", cycles_per_frame);
+    }
+    if let Some(ref theme) = info.theme {
+        println!("Theme: {}", theme);
+    }
+    if let Some(ref colors) = info.colors {
+        println!("Colors: {}", colors.join(", "));
+    }
 }
 
 // Entry point into the program. Takes care of basic setup such as reading
-// the rom path from the command line and kicking off the ui and emulator.
+// the rom path and options from the command line and kicking off the ui and
+// emulator.
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        print!("Usage: chip8 PATH_TO_ROM");
-        return;
-    }
-    let path_to_rom = Path::new(&args[1]);
-    let ui = Ui::new();
-    let mut emu = Emu::new();
-    load_rom(&mut emu, path_to_rom);
+    match args.get(1).map(String::as_str) {
+        Some("disasm") => return disasm_main(&args[2..]),
+        Some("asm") => return asm_main(&args[2..]),
+        Some("info") => return info_main(&args[2..]),
+        Some("help") => return print!("{}", help()),
+        _ => {}
+    }
+    // The run subcommand is the default.
+    let opts = match args.get(1).map(String::as_str) {
+        Some("run") => parse_args(&args[1..]),
+        _ => parse_args(&args),
+    };
+    // A movie is played back with the settings it was recorded with.
+    let played = opts.play.map(|path| match Movie::read(path) {
+        Ok(movie) => movie,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", path.display(), err);
+            process::exit(EXIT_FAILURE);
+        }
+    });
+    let rom = match read_rom(opts.path_to_rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Failed to load {}: {}", opts.path_to_rom.display(), err);
+            process::exit(EXIT_FAILURE);
+        }
+    };
+    // Known roms are run with the settings they play best with. Headless
+    // runs leave out the user's config file and rom database, so that they
+    // turn out the same on any machine.
+    let (config, romdb) = match opts.headless {
+        true => (Config::default(), RomDb::bundled()),
+        false => (load_config(), load_romdb()),
+    };
+    let info = romdb.get(&romdb::rom_hash(&rom));
+    if let (Some(info), false) = (info, opts.headless) {
+        match info.author {
+            Some(ref author) => println!("{} by {}", info.title, author),
+            None => println!("{}", info.title),
+        }
+    }
+    let (mode, quirks, timing) = match played {
+        Some(ref movie) => (movie.mode, movie.quirks, movie.timing),
+        None => settings(&opts, info, &config),
+    };
+    let mut emu = Emu::with_mode(mode, quirks);
+    if let Some(seed) = opts.seed {
+        emu.set_seed(seed);
+    }
+    emu.set_timing(timing);
+    if let Err(err) = emu.load_rom(rom) {
+        eprintln!("Failed to load {}: {}", opts.path_to_rom.display(), err);
+        process::exit(EXIT_FAILURE);
+    }
+    let session = match played {
+        Some(movie) => match Session::play(movie, &mut emu) {
+            Ok(session) => Some(session),
+            Err(err) => {
+                eprintln!("Failed to play {}: {}", 
+                          opts.play.unwrap_or(opts.path_to_rom).display(), err);
+                process::exit(EXIT_FAILURE);
+            }
+        },
+        None if opts.record.is_some() => 
+            Some(Session::record(&emu, mode, quirks)),
+        None => None,
+    };
+    if opts.headless {
+        return headless_main(emu, session, &opts);
+    }
+    // Recording carries on from a movie played back, once a state is 
+    // restored, and is written back to it.
+    let movie = session.map(|session| {
+        let path = opts.record.or(opts.play).unwrap_or(opts.path_to_rom);
+        (session, path.to_path_buf())
+    });
+    // Bindings for the rom go on top of the user's, and those given on the
+    // command line on top of both.
+    let (mut keymap, mut buttons) = (config.keymap(), config.buttons());
+    if let Some(info) = info {
+        keymap.bind(&info.bindings).expect("bindings checked by the romdb");
+        buttons.bind(&info.buttons).expect("buttons checked by the romdb");
+    }
+    if let Some(path) = opts.keymap {
+        let bound = read_bindings(path)
+            .and_then(|bindings| Ok(keymap.bind(&bindings)?));
+        if let Err(err) = bound {
+            eprintln!("Invalid key bindings in {}: {}", path.display(), err);
+            process::exit(EXIT_USAGE);
+        }
+    }
+    // Colors given on the command line go on top of the theme given there,
+    // or else on top of the rom's palette, itself on top of the user's.
+    let palette = opts.theme.unwrap_or_else(|| match info {
+        Some(info) => info.palette(config.palette()),
+        None => config.palette(),
+    });
+    let palette = match opts.colors {
+        Some(ref colors) => palette.with_colors(colors)
+            .expect("colors checked by parse_args"),
+        None => palette,
+    };
+    let ui = Ui::new(ui::Settings {
+        scale: opts.scale.or(config.scale).unwrap_or(ui::DEFAULT_SCALE),
+        fullscreen: opts.fullscreen || config.fullscreen,
+        scaling: opts.scaling.or_else(|| config.scaling()).unwrap_or_default(),
+        mute: opts.mute || config.mute,
+        volume: config.volume.unwrap_or(100),
+        tone: config.tone,
+        palette,
+        phosphor: opts.phosphor || config.phosphor,
+        decay: Duration::from_millis(opts.decay.or(config.decay)
+                                     .unwrap_or(phosphor::DEFAULT_DECAY_MS)),
+        keymap,
+        buttons,
+    });
     // The channels through which the ui and emulator will communicate.
     let (tx1, rx1) = mpsc::channel::<UiToEmuMsg>();
     let (tx2, rx2) = mpsc::channel::<EmuToUiMsg>();
+    // The debugger holds the emulator until told to continue. Commands are
+    // read from the command line in a thread of their own.
+    let debugger = if opts.debug {
+        let tx = tx1.clone();
+        thread::spawn(move || {
+            debug_repl_exec(tx);
+        });
+        let mut dbg = Debugger::new();
+        print_debug_output(&dbg.handle(Command::Help, &mut emu));
+        Some(dbg)
+    } else {
+        None
+    };
+    let slots = Slots::new(opts.path_to_rom, opts.state_format);
+    let rewind = Rewind::new(opts.rewind_seconds, 
+                             opts.rewind_megabytes * 1024 * 1024);
     // The emulator run in its own thread.
     thread::spawn(move || { 
-        emu_exec(emu, tx2, rx1); 
+        emu_exec(emu, debugger, slots, rewind, movie, tx2, rx1); 
     });
     // The ui runs on the main thread.
-    ui_exec(ui, tx1, rx2);
+    let speed = opts.speed.or(config.speed).unwrap_or(1.0);
+    ui_exec(ui, tx1, rx2, speed, opts.paused, opts.path_to_rom);
 }